@@ -12,6 +12,30 @@ if __name__ == '__main__':
 import substrateinterface
 from substrateinterface import SubstrateInterface, Keypair
 from substrateinterface.exceptions import SubstrateRequestException
+import hashlib
+import os
+import time
+
+# Must match MinCommitmentAge in the runtime
+MIN_COMMITMENT_AGE = 3
+
+# SCALE-encode a byte string: a compact length prefix followed by the bytes
+def scale_bytes(data):
+    length = len(data)
+    if length < 1 << 6:
+        prefix = (length << 2).to_bytes(1, 'little')
+    elif length < 1 << 14:
+        prefix = ((length << 2) | 1).to_bytes(2, 'little')
+    else:
+        prefix = ((length << 2) | 2).to_bytes(4, 'little')
+    return prefix + data
+
+# The hash that SiipModule::registration_commitment computes: blake2_256 of (domain, salt, owner)
+def commitment(domain, salt, public_key):
+    if isinstance(public_key, str):
+        public_key = bytes.fromhex(public_key.replace('0x', ''))
+    encoded = scale_bytes(domain.encode()) + scale_bytes(salt.encode()) + public_key
+    return hashlib.blake2b(encoded, digest_size=32).hexdigest()
 
 def register(domain, name, ip_addr, info, key):
     substrate = SubstrateInterface(
@@ -37,10 +61,30 @@ def register(domain, name, ip_addr, info, key):
     )
 
     keypair = Keypair.create_from_uri('//Alice')
+
+    # Registration happens in two steps, so nobody watching the transaction pool
+    # can see the domain and register it first. Step one commits to a hash of the domain...
+    salt = os.urandom(16).hex()
+    commit = substrate.compose_call(
+        call_module='SiipModule',
+        call_function='commit_registration',
+        call_params={
+            'commitment': '0x' + commitment(domain, salt, keypair.public_key),
+        }
+    )
+    extrinsic = substrate.create_signed_extrinsic(call=commit, keypair=keypair)
+    substrate.submit_extrinsic(extrinsic)
+
+    # ...and step two reveals it, once the commitment is MIN_COMMITMENT_AGE blocks old
+    start = substrate.get_block_number(substrate.get_chain_head())
+    while substrate.get_block_number(substrate.get_chain_head()) < start + MIN_COMMITMENT_AGE + 1:
+        time.sleep(1)
+
     call = substrate.compose_call(
         call_module='SiipModule',
-        call_function='register_certificate',
+        call_function='reveal_registration',
         call_params={
+            'salt': salt,
             'name': name,
             'domain': domain,
             'ip_addr': ip_addr,
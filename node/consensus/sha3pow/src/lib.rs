@@ -1,14 +1,19 @@
 use parity_scale_codec::{Decode, Encode};
-use rand::{SeedableRng, prelude::SmallRng, thread_rng};
 use sc_consensus_pow::{Error, PowAlgorithm};
 use sha3::{Digest, Sha3_256};
 use sp_api::ProvideRuntimeApi;
 use sp_consensus_pow::{DifficultyApi, Seal as RawSeal};
 use sp_core::{H256, U256};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::SaturatedConversion;
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::Block as BlockT;
+use std::convert::TryInto;
 use std::sync::Arc;
 
+#[cfg(test)]
+mod tests;
+
 /// Determine whether the given hash satisfies the given difficulty.
 /// The test is done by multiplying the two together. If the product
 /// overflows the bounds of U256, then the product (and thus the hash)
@@ -20,6 +25,150 @@ fn hash_meets_difficulty(hash: &H256, difficulty: U256) -> bool {
 	!overflowed
 }
 
+/// Number of 64-bit words in the scratchpad the memory-hard hash fills: 512 KiB per hash.
+pub const SCRATCHPAD_WORDS: usize = 1 << 16;
+
+/// The hash function that turns a `Compute` into the work of its seal.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowHash {
+	/// Plain Sha3-256: cheap, and easy to build ASICs and GPU kernels for.
+	Sha3,
+	/// Sha3-256 over a scratchpad that has to be filled and then read back in a data-dependent
+	/// order. Each hash needs `SCRATCHPAD_WORDS` words of memory, so mining is bound by memory
+	/// latency rather than hashing speed.
+	MemoryHard,
+}
+
+impl PowHash {
+	pub fn hash(&self, input: &[u8]) -> H256 {
+		match self {
+			PowHash::Sha3 => H256::from_slice(Sha3_256::digest(input).as_slice()),
+			PowHash::MemoryHard => memory_hard_hash(input),
+		}
+	}
+}
+
+/// Mixes two scratchpad words together.
+fn mix(a: u64, b: u64) -> u64 {
+	(a ^ b.rotate_left(23)).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (a >> 29)
+}
+
+/// An Argon2-like hash. Each scratchpad word depends on the one before it and on an earlier word
+/// the one before it picks, so the pad can't be filled in parallel or recomputed piecemeal. Four
+/// lanes then walk it in an order that depends on what they read, writing back as they go, and
+/// the result is hashed with Sha3.
+fn memory_hard_hash(input: &[u8]) -> H256 {
+	let seed = Sha3_256::digest(input);
+	let mut pad = vec![0u64; SCRATCHPAD_WORDS];
+	for (word, bytes) in pad.iter_mut().zip(seed.chunks(8)) {
+		*word = u64::from_le_bytes(bytes.try_into().expect("Sha3-256 output is 4 words; qed"));
+	}
+	for i in 4..SCRATCHPAD_WORDS {
+		let previous = pad[i - 1];
+		pad[i] = mix(previous, pad[(previous % i as u64) as usize]);
+	}
+
+	let mut lanes = [0u64; 4];
+	lanes.copy_from_slice(&pad[SCRATCHPAD_WORDS - 4..]);
+	for step in 0..SCRATCHPAD_WORDS {
+		let lane = step % 4;
+		let index = (lanes[lane] % SCRATCHPAD_WORDS as u64) as usize;
+		lanes[lane] = mix(lanes[lane], pad[index]);
+		pad[index] ^= lanes[(lane + 1) % 4];
+	}
+
+	let mut hasher = Sha3_256::new();
+	hasher.update(seed);
+	for lane in lanes.iter() {
+		hasher.update(lane.to_le_bytes());
+	}
+	H256::from_slice(hasher.finalize().as_slice())
+}
+
+/// When the chain moves from Sha3 to the memory-hard hash.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PowSchedule {
+	/// The first block sealed with the memory-hard hash. `None` keeps Sha3 forever.
+	pub memory_hard_from: Option<u64>,
+}
+
+impl PowSchedule {
+	/// The hash that seals block `number`.
+	pub fn hash_at(&self, number: u64) -> PowHash {
+		match self.memory_hard_from {
+			Some(from) if number >= from => PowHash::MemoryHard,
+			_ => PowHash::Sha3,
+		}
+	}
+}
+
+/// Where a nonce search is up to. A search tries `next`, `next + stride`, `next + 2 * stride`
+/// and so on, wrapping around at 2^256, so it can be stopped and resumed from its cursor, or
+/// split between workers that start at neighbouring nonces and stride by their number.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NonceCursor {
+	pub next: U256,
+	pub stride: U256,
+}
+
+impl NonceCursor {
+	pub fn new(next: U256, stride: U256) -> Self {
+		Self { next, stride }
+	}
+
+	/// The cursor of worker `worker` of `workers` searching from the nonce `seed` picks. The
+	/// workers of one seed never try the same nonce, and the same seed always gives the same search.
+	pub fn seeded(seed: u64, worker: u64, workers: u64) -> Self {
+		let start = U256::from(Sha3_256::digest(&seed.to_le_bytes()).as_slice());
+		Self::new(start.overflowing_add(U256::from(worker)).0, U256::from(workers.max(1)))
+	}
+
+	/// The nonce the search tries next.
+	pub fn nonce(&self) -> H256 {
+		let mut nonce = H256::zero();
+		self.next.to_big_endian(nonce.as_bytes_mut());
+		nonce
+	}
+
+	fn advance(&mut self) {
+		self.next = self.next.overflowing_add(self.stride).0;
+	}
+}
+
+/// What a call to `SiipPowAlgorithm::mine` found.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mined {
+	/// The seal, if one of the nonces tried solved the proof of work.
+	pub seal: Option<RawSeal>,
+	/// Where to resume the search: just past the last nonce tried.
+	pub cursor: NonceCursor,
+	/// How many hashes were computed, for hashrate accounting.
+	pub hashes: u64,
+}
+
+/// Checks that `seal` is a valid seal of `pre_hash`, computed with `hash`, meeting `difficulty`.
+fn verify_seal(hash: PowHash, pre_hash: &H256, seal: &RawSeal, difficulty: U256) -> bool {
+	// Try to construct a seal object by decoding the raw seal given
+	let seal = match Seal::decode(&mut &seal[..]) {
+		Ok(seal) => seal,
+		Err(_) => return false,
+	};
+
+	// See whether the hash meets the difficulty requirement. If not, fail fast.
+	if !hash_meets_difficulty(&seal.work, difficulty) {
+		return false;
+	}
+
+	// Make sure the provided work actually comes from the correct pre_hash
+	let compute = Compute {
+		difficulty,
+		pre_hash: *pre_hash,
+		nonce: seal.nonce,
+	};
+
+	compute.compute_with(hash) == seal
+}
+
 /// A Seal struct that will be encoded to a Vec<u8> as used as the
 /// `RawSeal` type.
 #[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
@@ -40,7 +189,11 @@ pub struct Compute {
 
 impl Compute {
 	pub fn compute(self) -> Seal {
-		let work = H256::from_slice(Sha3_256::digest(&self.encode()[..]).as_slice());
+		self.compute_with(PowHash::Sha3)
+	}
+
+	pub fn compute_with(self, hash: PowHash) -> Seal {
+		let work = hash.hash(&self.encode()[..]);
 
 		Seal {
 			nonce: self.nonce,
@@ -72,29 +225,7 @@ impl<B: BlockT<Hash = H256>> PowAlgorithm<B> for MinimalSha3Algorithm {
 		seal: &RawSeal,
 		difficulty: Self::Difficulty,
 	) -> Result<bool, Error<B>> {
-		// Try to construct a seal object by decoding the raw seal given
-		let seal = match Seal::decode(&mut &seal[..]) {
-			Ok(seal) => seal,
-			Err(_) => return Ok(false),
-		};
-
-		// See whether the hash meets the difficulty requirement. If not, fail fast.
-		if !hash_meets_difficulty(&seal.work, difficulty) {
-			return Ok(false);
-		}
-
-		// Make sure the provided work actually comes from the correct pre_hash
-		let compute = Compute {
-			difficulty,
-			pre_hash: *pre_hash,
-			nonce: seal.nonce,
-		};
-
-		if compute.compute() != seal {
-			return Ok(false);
-		}
-
-		Ok(true)
+		Ok(verify_seal(PowHash::Sha3, pre_hash, seal, difficulty))
 	}
 }
 
@@ -146,29 +277,88 @@ where
 		seal: &RawSeal,
 		difficulty: Self::Difficulty,
 	) -> Result<bool, Error<B>> {
-		// Try to construct a seal object by decoding the raw seal given
-		let seal = match Seal::decode(&mut &seal[..]) {
-			Ok(seal) => seal,
-			Err(_) => return Ok(false),
-		};
-
-		// See whether the hash meets the difficulty requirement. If not, fail fast.
-		if !hash_meets_difficulty(&seal.work, difficulty) {
-			return Ok(false);
-		}
+		Ok(verify_seal(PowHash::Sha3, pre_hash, seal, difficulty))
+	}
+}
 
-		// Make sure the provided work actually comes from the correct pre_hash
-		let compute = Compute {
-			difficulty,
-			pre_hash: *pre_hash,
-			nonce: seal.nonce,
-		};
+/// A minimal PoW algorithm that uses the memory-hard hash.
+/// Difficulty is fixed at 1_000, like `MinimalSha3Algorithm`.
+#[derive(Clone)]
+pub struct MinimalMemoryHardAlgorithm;
 
-		if compute.compute() != seal {
-			return Ok(false);
-		}
+impl<B: BlockT<Hash = H256>> PowAlgorithm<B> for MinimalMemoryHardAlgorithm {
+	type Difficulty = U256;
 
-		Ok(true)
+	fn difficulty(&self, _parent: B::Hash) -> Result<Self::Difficulty, Error<B>> {
+		Ok(U256::from(1_000))
+	}
+
+	fn verify(
+		&self,
+		_parent: &BlockId<B>,
+		pre_hash: &H256,
+		_pre_digest: Option<&[u8]>,
+		seal: &RawSeal,
+		difficulty: Self::Difficulty,
+	) -> Result<bool, Error<B>> {
+		Ok(verify_seal(PowHash::MemoryHard, pre_hash, seal, difficulty))
+	}
+}
+
+/// A PoW algorithm with a fixed difficulty that seals blocks with Sha3 or the memory-hard hash,
+/// as its `PowSchedule` says for their height.
+/// Needs a reference to the client so it can look up the height of a block's parent.
+pub struct ScheduledAlgorithm<C> {
+	client: Arc<C>,
+	schedule: PowSchedule,
+	difficulty: U256,
+}
+
+impl<C> ScheduledAlgorithm<C> {
+	pub fn new(client: Arc<C>, schedule: PowSchedule, difficulty: U256) -> Self {
+		Self { client, schedule, difficulty }
+	}
+}
+
+// Manually implement clone, for the same reason as Sha3Algorithm
+impl<C> Clone for ScheduledAlgorithm<C> {
+	fn clone(&self) -> Self {
+		Self::new(self.client.clone(), self.schedule, self.difficulty)
+	}
+}
+
+impl<C> ScheduledAlgorithm<C> {
+	/// The hash that seals the child of `parent`.
+	fn hash_after<B: BlockT<Hash = H256>>(&self, parent: &BlockId<B>) -> Result<PowHash, Error<B>>
+	where
+		C: HeaderBackend<B>,
+	{
+		let number = self.client.block_number_from_id(parent)
+			.map_err(|err| Error::Environment(format!("Fetching parent block number failed: {:?}", err)))?
+			.ok_or_else(|| Error::Environment(format!("Unknown parent block {:?}", parent)))?;
+		Ok(self.schedule.hash_at(number.saturated_into::<u64>().saturating_add(1)))
+	}
+}
+
+impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for ScheduledAlgorithm<C>
+where
+	C: HeaderBackend<B>,
+{
+	type Difficulty = U256;
+
+	fn difficulty(&self, _parent: B::Hash) -> Result<Self::Difficulty, Error<B>> {
+		Ok(self.difficulty)
+	}
+
+	fn verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &H256,
+		_pre_digest: Option<&[u8]>,
+		seal: &RawSeal,
+		difficulty: Self::Difficulty,
+	) -> Result<bool, Error<B>> {
+		Ok(verify_seal(self.hash_after(parent)?, pre_hash, seal, difficulty))
 	}
 }
 
@@ -176,44 +366,66 @@ pub trait SiipPowAlgorithm<B> : PowAlgorithm<B>
 	where B: BlockT<Hash=H256>,
 		Self: PowAlgorithm<B, Difficulty=U256>
 {
+	/// The hash that seals the child of `parent`.
+	fn pow_hash(&self, parent: &BlockId<B>) -> Result<PowHash, Error<B>>;
+
+	/// Tries up to `round` nonces from `cursor`, stopping at the first that solves the proof of work.
 	fn mine(
 		&self,
-		_parent: &BlockId<B>, // parent block hash is included in block header which is used to compute pre_hash, so we don't need it here
+		parent: &BlockId<B>, // parent block hash is included in block header which is used to compute pre_hash, but it also decides which hash to mine with
 		pre_hash: &<B as BlockT>::Hash,
 		_pre_digest: Option<&[u8]>, // pre runtime digests are included in block header which is used to compute pre_hash, so we shouldn't need it here
 		difficulty: <Self as PowAlgorithm<B>>::Difficulty,
+		mut cursor: NonceCursor, // The first nonce to try, and the step to the ones after it
 		round: u32 // The number of nonces to try during this call
-	) -> Result<Option<RawSeal>, Error<B>> {
-		// Get a randomness source from the environment; fail if one isn't available
-		let mut rng = SmallRng::from_rng(&mut thread_rng())
-			.map_err(|e| Error::Environment(format!("Initialize RNG failed for mining: {:?}", e)))?;
-	
+	) -> Result<Mined, Error<B>> {
+		let hash = self.pow_hash(parent)?;
+		let mut hashes = 0;
+
 		// Loop the specified number of times
-		for _ in 0..round {
-	
-			// Choose a new nonce
-			let nonce = H256::random_using(&mut rng);
-	
+		while hashes < u64::from(round) {
+
+			// Take the next nonce
+			let nonce = cursor.nonce();
+			cursor.advance();
+			hashes += 1;
+
 			// Calculate the seal
 			let compute = Compute {
 				difficulty,
 				pre_hash: *pre_hash,
 				nonce,
 			};
-			let seal = compute.compute();
-	
+			let seal = compute.compute_with(hash);
+
 			// If we solved the PoW then return, otherwise loop again
 			if hash_meets_difficulty(&seal.work, difficulty) {
-				return Ok(Some(seal.encode()))
+				return Ok(Mined { seal: Some(seal.encode()), cursor, hashes })
 			}
 		}
-	
+
 		// Tried the specified number of rounds and never found a solution
-		Ok(None)
+		Ok(Mined { seal: None, cursor, hashes })
+	}
+}
+
+impl<B: BlockT<Hash=H256>> SiipPowAlgorithm<B> for MinimalSha3Algorithm {
+	fn pow_hash(&self, _parent: &BlockId<B>) -> Result<PowHash, Error<B>> {
+		Ok(PowHash::Sha3)
 	}
 }
 
-impl<B: BlockT<Hash=H256>> SiipPowAlgorithm<B> for MinimalSha3Algorithm {}
+impl<B: BlockT<Hash=H256>> SiipPowAlgorithm<B> for MinimalMemoryHardAlgorithm {
+	fn pow_hash(&self, _parent: &BlockId<B>) -> Result<PowHash, Error<B>> {
+		Ok(PowHash::MemoryHard)
+	}
+}
+
+impl<B: BlockT<Hash=H256>, C: HeaderBackend<B>> SiipPowAlgorithm<B> for ScheduledAlgorithm<C> {
+	fn pow_hash(&self, parent: &BlockId<B>) -> Result<PowHash, Error<B>> {
+		self.hash_after(parent)
+	}
+}
 
 // TODO Sha3Algorithm should implement SiipPowAlgorithm as well, so it can be configured in services.rs
 // as a drop-in replacement for MinimalSha3Algorithm, allowing for dynamic difficulty selection.
@@ -0,0 +1,316 @@
+use super::*;
+use proptest::prelude::*;
+use sp_api::ApiRef;
+use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper};
+use std::convert::TryInto;
+use std::str::FromStr;
+
+type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+// Low enough that a seal turns up within a few dozen hashes
+const DIFFICULTY: u64 = 10;
+
+fn mine_from<A: SiipPowAlgorithm<Block>>(algorithm: &A, pre_hash: &H256, cursor: NonceCursor, round: u32) -> Mined {
+	algorithm.mine(&BlockId::Number(1), pre_hash, None, U256::from(DIFFICULTY), cursor, round)
+		.expect("mining doesn't fail")
+}
+
+fn mine<A: SiipPowAlgorithm<Block>>(algorithm: &A, pre_hash: &H256) -> RawSeal {
+	mine_from(algorithm, pre_hash, NonceCursor::seeded(seed_of(pre_hash), 0, 1), 1_000)
+		.seal
+		.expect("a seal is found within 1_000 rounds")
+}
+
+// A seed that differs between pre-hashes, so the property tests don't all mine the same nonces
+fn seed_of(pre_hash: &H256) -> u64 {
+	u64::from_le_bytes(pre_hash[..8].try_into().unwrap())
+}
+
+fn verify<A: SiipPowAlgorithm<Block>>(algorithm: &A, pre_hash: &H256, seal: &RawSeal) -> bool {
+	algorithm.verify(&BlockId::Number(1), pre_hash, None, seal, U256::from(DIFFICULTY))
+		.expect("verifying doesn't fail")
+}
+
+fn mine_with(hash: PowHash, pre_hash: &H256) -> RawSeal {
+	match hash {
+		PowHash::Sha3 => mine(&MinimalSha3Algorithm, pre_hash),
+		PowHash::MemoryHard => mine(&MinimalMemoryHardAlgorithm, pre_hash),
+	}
+}
+
+fn verify_with(hash: PowHash, pre_hash: &H256, seal: &RawSeal) -> bool {
+	match hash {
+		PowHash::Sha3 => verify(&MinimalSha3Algorithm, pre_hash, seal),
+		PowHash::MemoryHard => verify(&MinimalMemoryHardAlgorithm, pre_hash, seal),
+	}
+}
+
+// The hash whose big-endian value is `value`
+fn hash_of(value: U256) -> H256 {
+	let mut bytes = [0u8; 32];
+	value.to_big_endian(&mut bytes);
+	H256::from(bytes)
+}
+
+fn known_compute() -> Compute {
+	Compute {
+		difficulty: U256::from(1_000),
+		pre_hash: H256::repeat_byte(1),
+		nonce: H256::repeat_byte(2),
+	}
+}
+
+#[test]
+fn mining_is_deterministic_given_a_seed() {
+	let pre_hash = H256::repeat_byte(1);
+	let first = mine_from(&MinimalSha3Algorithm, &pre_hash, NonceCursor::seeded(42, 0, 1), 1_000);
+	let second = mine_from(&MinimalSha3Algorithm, &pre_hash, NonceCursor::seeded(42, 0, 1), 1_000);
+	assert!(first.seal.is_some());
+	assert_eq!(first, second);
+
+	//The cursor points just past the nonce that was found
+	let seal = Seal::decode(&mut &first.seal.unwrap()[..]).unwrap();
+	let mut found = NonceCursor::seeded(42, 0, 1);
+	found.next += U256::from(first.hashes - 1);
+	assert_eq!(seal.nonce, found.nonce());
+	assert_eq!(first.cursor.next, found.next + 1);
+}
+
+#[test]
+fn mining_resumes_from_its_cursor() {
+	let pre_hash = H256::repeat_byte(1);
+	let at_once = mine_from(&MinimalSha3Algorithm, &pre_hash, NonceCursor::seeded(42, 0, 1), 1_000);
+
+	//One nonce at a time finds the same seal after the same number of hashes
+	let mut cursor = NonceCursor::seeded(42, 0, 1);
+	let mut hashes = 0;
+	let seal = loop {
+		let mined = mine_from(&MinimalSha3Algorithm, &pre_hash, cursor, 1);
+		assert_eq!(mined.hashes, 1);
+		hashes += mined.hashes;
+		cursor = mined.cursor;
+		if let Some(seal) = mined.seal {
+			break seal;
+		}
+	};
+	assert_eq!(Some(seal), at_once.seal);
+	assert_eq!(hashes, at_once.hashes);
+	assert_eq!(cursor, at_once.cursor);
+}
+
+#[test]
+fn mining_counts_every_hash_tried() {
+	//Nothing meets the highest difficulty, so every round is tried
+	let mined = MinimalSha3Algorithm.mine(
+		&BlockId::<Block>::Number(1), &H256::repeat_byte(1), None, U256::MAX, NonceCursor::seeded(42, 0, 1), 50,
+	).unwrap();
+	assert_eq!(mined.seal, None);
+	assert_eq!(mined.hashes, 50);
+	assert_eq!(mined.cursor.next, NonceCursor::seeded(42, 0, 1).next + 50);
+}
+
+#[test]
+fn workers_of_a_seed_split_the_nonces() {
+	let mut workers: Vec<_> = (0..3).map(|worker| NonceCursor::seeded(42, worker, 3)).collect();
+	let mut nonces = Vec::new();
+	for _ in 0..4 {
+		for cursor in workers.iter_mut() {
+			nonces.push(cursor.next);
+			cursor.advance();
+		}
+	}
+	//Between them, the workers try consecutive nonces, each exactly once
+	let start = NonceCursor::seeded(42, 0, 1).next;
+	nonces.sort();
+	assert_eq!(nonces, (0..12u64).map(|i| start + i).collect::<Vec<_>>());
+}
+
+#[test]
+fn cursors_wrap_around() {
+	let mut cursor = NonceCursor::new(U256::MAX, U256::from(2));
+	assert_eq!(cursor.nonce(), H256::repeat_byte(0xff));
+	cursor.advance();
+	assert_eq!(cursor.nonce(), H256::from_low_u64_be(1));
+}
+
+#[test]
+fn hash_meets_difficulty_up_to_the_overflow() {
+	assert!(hash_meets_difficulty(&H256::zero(), U256::MAX));
+	assert!(hash_meets_difficulty(&hash_of(U256::MAX), U256::one()));
+	assert!(!hash_meets_difficulty(&hash_of(U256::MAX), U256::from(2)));
+
+	//The highest hash that meets a difficulty is the one whose product with it doesn't overflow
+	let difficulty = U256::from(1_000);
+	let highest = U256::MAX / difficulty;
+	assert!(hash_meets_difficulty(&hash_of(highest), difficulty));
+	assert!(!hash_meets_difficulty(&hash_of(highest + 1), difficulty));
+}
+
+#[test]
+fn hashes_match_known_answers() {
+	//The Sha3-256 test vector for the empty message
+	assert_eq!(
+		PowHash::Sha3.hash(b""),
+		H256::from_str("a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a").unwrap(),
+	);
+
+	let compute = known_compute();
+	assert_eq!(compute.encode().len(), 96);
+	assert_eq!(
+		compute.clone().compute().work,
+		H256::from_str("67ebe6cc3d85267b2b55c32e4de409065980b7679a0eb85782d1dbb2fe7134d1").unwrap(),
+	);
+	assert_eq!(
+		compute.compute_with(PowHash::MemoryHard).work,
+		H256::from_str("bba4fc975b121288e346e204e907c22780742618bfd714a9da6ace96242887fd").unwrap(),
+	);
+}
+
+#[test]
+fn compute_keeps_difficulty_and_nonce() {
+	let seal = known_compute().compute();
+	assert_eq!(seal.difficulty, U256::from(1_000));
+	assert_eq!(seal.nonce, H256::repeat_byte(2));
+}
+
+#[test]
+fn seals_decode_from_their_encoding_only() {
+	let seal = known_compute().compute();
+	let encoded = seal.encode();
+	assert_eq!(encoded.len(), 96);
+	assert_eq!(Seal::decode(&mut &encoded[..]).unwrap(), seal);
+	assert!(Seal::decode(&mut &encoded[..95]).is_err());
+	assert!(Seal::decode(&mut &[0u8; 0][..]).is_err());
+}
+
+// The seal verification checks that every algorithm must pass
+fn check_seals<A: SiipPowAlgorithm<Block>>(algorithm: A) {
+	let pre_hash = H256::repeat_byte(1);
+	let raw_seal = mine(&algorithm, &pre_hash);
+	assert!(verify(&algorithm, &pre_hash, &raw_seal));
+
+	//A seal only seals the block it was mined for
+	assert!(!verify(&algorithm, &H256::repeat_byte(2), &raw_seal));
+
+	//Nor can any of its fields be changed
+	let seal = Seal::decode(&mut &raw_seal[..]).unwrap();
+	let tampered = [
+		Seal { nonce: H256::repeat_byte(3), ..seal.clone() },
+		Seal { work: H256::zero(), ..seal.clone() },
+		Seal { difficulty: U256::from(DIFFICULTY + 1), ..seal.clone() },
+	];
+	for seal in tampered.iter() {
+		assert!(!verify(&algorithm, &pre_hash, &seal.encode()));
+	}
+
+	//And garbage isn't a seal
+	assert!(!verify(&algorithm, &pre_hash, &vec![1, 2, 3]));
+}
+
+#[test]
+fn sha3_seals() {
+	check_seals(MinimalSha3Algorithm);
+}
+
+#[test]
+fn memory_hard_seals() {
+	check_seals(MinimalMemoryHardAlgorithm);
+}
+
+#[test]
+fn seals_only_verify_with_their_own_hash() {
+	let pre_hash = H256::repeat_byte(1);
+	let sha3_seal = mine(&MinimalSha3Algorithm, &pre_hash);
+	let memory_hard_seal = mine(&MinimalMemoryHardAlgorithm, &pre_hash);
+	assert!(!verify(&MinimalMemoryHardAlgorithm, &pre_hash, &sha3_seal));
+	assert!(!verify(&MinimalSha3Algorithm, &pre_hash, &memory_hard_seal));
+}
+
+#[test]
+fn memory_hard_hash_is_deterministic() {
+	assert_eq!(PowHash::MemoryHard.hash(b"siip"), PowHash::MemoryHard.hash(b"siip"));
+	assert_ne!(PowHash::MemoryHard.hash(b"siip"), PowHash::MemoryHard.hash(b"siiq"));
+	assert_ne!(PowHash::MemoryHard.hash(b"siip"), PowHash::Sha3.hash(b"siip"));
+}
+
+#[test]
+fn schedule_switches_at_its_height() {
+	let never = PowSchedule::default();
+	assert_eq!(never.hash_at(0), PowHash::Sha3);
+	assert_eq!(never.hash_at(u64::MAX), PowHash::Sha3);
+
+	let at_100 = PowSchedule { memory_hard_from: Some(100) };
+	assert_eq!(at_100.hash_at(1), PowHash::Sha3);
+	assert_eq!(at_100.hash_at(99), PowHash::Sha3);
+	assert_eq!(at_100.hash_at(100), PowHash::MemoryHard);
+	assert_eq!(at_100.hash_at(101), PowHash::MemoryHard);
+
+	let from_genesis = PowSchedule { memory_hard_from: Some(0) };
+	assert_eq!(from_genesis.hash_at(1), PowHash::MemoryHard);
+}
+
+proptest! {
+	// Each case mines a seal, and memory-hard hashes are slow in debug builds
+	#![proptest_config(ProptestConfig::with_cases(16))]
+
+	#[test]
+	fn mined_seals_always_verify(pre_hash in any::<[u8; 32]>(), memory_hard in any::<bool>()) {
+		let hash = if memory_hard { PowHash::MemoryHard } else { PowHash::Sha3 };
+		let pre_hash = H256::from(pre_hash);
+		let seal = mine_with(hash, &pre_hash);
+		prop_assert!(verify_with(hash, &pre_hash, &seal));
+	}
+
+	#[test]
+	fn tampered_seals_never_verify(
+		pre_hash in any::<[u8; 32]>(),
+		memory_hard in any::<bool>(),
+		index in 0..96usize,
+		flip in 1..=255u8,
+	) {
+		let hash = if memory_hard { PowHash::MemoryHard } else { PowHash::Sha3 };
+		let pre_hash = H256::from(pre_hash);
+		let mut seal = mine_with(hash, &pre_hash);
+		seal[index] ^= flip;
+		prop_assert!(!verify_with(hash, &pre_hash, &seal));
+	}
+}
+
+// A runtime that asks for a fixed difficulty
+#[derive(Clone)]
+struct TestClient {
+	difficulty: U256,
+}
+
+struct TestApi {
+	difficulty: U256,
+}
+
+sp_api::mock_impl_runtime_apis! {
+	impl DifficultyApi<Block, U256> for TestApi {
+		fn difficulty(&self) -> U256 {
+			self.difficulty
+		}
+	}
+}
+
+impl ProvideRuntimeApi<Block> for TestClient {
+	type Api = TestApi;
+
+	fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+		TestApi { difficulty: self.difficulty }.into()
+	}
+}
+
+#[test]
+fn sha3_algorithm_takes_difficulty_from_the_runtime() {
+	let algorithm = Sha3Algorithm::new(Arc::new(TestClient { difficulty: U256::from(DIFFICULTY) }));
+	let difficulty = <Sha3Algorithm<TestClient> as PowAlgorithm<Block>>::difficulty(&algorithm, H256::zero()).unwrap();
+	assert_eq!(difficulty, U256::from(DIFFICULTY));
+
+	//It accepts the seals MinimalSha3Algorithm mines, at the runtime's difficulty only
+	let pre_hash = H256::repeat_byte(1);
+	let seal = mine(&MinimalSha3Algorithm, &pre_hash);
+	assert!(algorithm.verify(&BlockId::<Block>::Number(1), &pre_hash, None, &seal, difficulty).unwrap());
+	assert!(!algorithm.verify(&BlockId::<Block>::Number(1), &pre_hash, None, &seal, difficulty + 1).unwrap());
+}
@@ -0,0 +1,89 @@
+//! SCALE-decoding a `Certificate`, as the client and RPCs do with what they read from storage,
+//! never panics and agrees with reading its fields by hand: a little-endian `i32` version, a
+//! 32-byte owner, then five byte strings, each a compact length followed by that many bytes.
+
+#![no_main]
+use std::convert::TryInto;
+use libfuzzer_sys::fuzz_target;
+use pallet_siip::Certificate;
+use parity_scale_codec::{Decode, Encode};
+use sp_core::crypto::AccountId32;
+
+/// Takes the next `len` bytes off `data`.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+	if data.len() < len {
+		return None;
+	}
+	let (taken, rest) = data.split_at(len);
+	*data = rest;
+	Some(taken)
+}
+
+/// Reads a compact-encoded length. The two low bits of the first byte give the mode: the rest of
+/// that byte, of two bytes, or of four bytes, or a byte of zeros followed by four more bytes. Each
+/// mode only holds the lengths too large for the one before it.
+fn compact_len(data: &mut &[u8]) -> Option<usize> {
+	let prefix = take(data, 1)?[0];
+	let len = match prefix & 0b11 {
+		0 => u32::from(prefix >> 2),
+		1 => {
+			let rest = take(data, 1)?[0];
+			let len = u32::from(u16::from_le_bytes([prefix, rest]) >> 2);
+			if len < 1 << 6 {
+				return None;
+			}
+			len
+		},
+		2 => {
+			let rest = take(data, 3)?;
+			let len = u32::from_le_bytes([prefix, rest[0], rest[1], rest[2]]) >> 2;
+			if len < 1 << 14 {
+				return None;
+			}
+			len
+		},
+		_ => {
+			if prefix >> 2 != 0 {
+				return None;
+			}
+			let len = u32::from_le_bytes(take(data, 4)?.try_into().unwrap());
+			if len < 1 << 30 {
+				return None;
+			}
+			len
+		},
+	};
+	Some(len as usize)
+}
+
+fn bytes(data: &mut &[u8]) -> Option<Vec<u8>> {
+	let len = compact_len(data)?;
+	take(data, len).map(<[u8]>::to_vec)
+}
+
+/// The certificate at the start of `data`, and how many bytes it takes up.
+fn reference(data: &[u8]) -> Option<(Certificate<AccountId32>, usize)> {
+	let mut rest = data;
+	let version_number = i32::from_le_bytes(take(&mut rest, 4)?.try_into().unwrap());
+	let owner: [u8; 32] = take(&mut rest, 32)?.try_into().unwrap();
+	let certificate = Certificate {
+		version_number,
+		owner_id: AccountId32::from(owner),
+		name: bytes(&mut rest)?,
+		info: bytes(&mut rest)?,
+		key: bytes(&mut rest)?,
+		ip_addr: bytes(&mut rest)?,
+		domain: bytes(&mut rest)?,
+	};
+	Some((certificate, data.len() - rest.len()))
+}
+
+fuzz_target!(|data: &[u8]| {
+	let mut input = data;
+	let certificate = Certificate::<AccountId32>::decode(&mut input).ok();
+	let read = data.len() - input.len();
+	assert_eq!(certificate.clone().map(|certificate| (certificate, read)), reference(data));
+	if let Some(certificate) = certificate {
+		assert_eq!(certificate.encode(), &data[..read]);
+	}
+});
@@ -0,0 +1,30 @@
+//! `check_domain` agrees with the rules it documents, written out independently: under 64
+//! characters, none of the forbidden symbols, no uppercase, and a 2-63 character TLD after a
+//! non-empty subdomain.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pallet_siip::check_domain;
+use siip_fuzz::accepted;
+
+const FORBIDDEN: [char; 12] = ['_', ' ', '!', '@', '#', '$', '^', '&', '*', '(', ')', '\n'];
+
+fn reference(domain: &[u8]) -> bool {
+	let domain = match std::str::from_utf8(domain) {
+		Ok(domain) => domain,
+		Err(_) => return false,
+	};
+	let (subdomain, tld) = match domain.rsplit_once('.') {
+		Some(parts) => parts,
+		None => return false,
+	};
+	domain.chars().count() < 64
+		&& !domain.contains(&FORBIDDEN[..])
+		&& !domain.chars().any(char::is_uppercase)
+		&& (2..=63).contains(&tld.chars().count())
+		&& !subdomain.is_empty()
+}
+
+fuzz_target!(|data: &[u8]| {
+	assert_eq!(accepted(&check_domain(data)), reference(data), "domain {:?}", String::from_utf8_lossy(data));
+});
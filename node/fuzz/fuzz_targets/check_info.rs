@@ -0,0 +1,15 @@
+//! `check_info` accepts exactly the JSON documents within the size cap, parsed straight from
+//! the bytes rather than through a `str`.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pallet_siip::{check_info, MAX_INFO_LENGTH};
+use siip_fuzz::accepted;
+
+fn reference(info: &[u8]) -> bool {
+	info.len() <= MAX_INFO_LENGTH && serde_json::from_slice::<serde_json::Value>(info).is_ok()
+}
+
+fuzz_target!(|data: &[u8]| {
+	assert_eq!(accepted(&check_info(data)), reference(data), "info {:?}", String::from_utf8_lossy(data));
+});
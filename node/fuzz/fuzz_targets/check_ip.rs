@@ -0,0 +1,15 @@
+//! `check_ip` accepts exactly the addresses the standard library parses as IPv4.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pallet_siip::check_ip;
+use siip_fuzz::accepted;
+use std::net::Ipv4Addr;
+
+fn reference(ip: &[u8]) -> bool {
+	std::str::from_utf8(ip).map_or(false, |ip| ip.parse::<Ipv4Addr>().is_ok())
+}
+
+fuzz_target!(|data: &[u8]| {
+	assert_eq!(accepted(&check_ip(data)), reference(data), "ip {:?}", String::from_utf8_lossy(data));
+});
@@ -0,0 +1,20 @@
+//! `check_key` accepts exactly the keys `siip-dane` decodes to DER and writes back unchanged:
+//! colon-separated pairs of uppercase hexadecimal digits, within the size cap. And whatever DER
+//! the input is, written as a key it must be accepted, so no real public key is ever refused.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pallet_siip::{check_key, MAX_KEY_LENGTH};
+use siip_dane::{der_to_key, key_to_der};
+use siip_fuzz::accepted;
+
+fn reference(key: &[u8]) -> bool {
+	key.len() <= MAX_KEY_LENGTH && key_to_der(key).map_or(false, |der| der_to_key(&der).as_bytes() == key)
+}
+
+fuzz_target!(|data: &[u8]| {
+	assert_eq!(accepted(&check_key(data)), reference(data), "key {:?}", String::from_utf8_lossy(data));
+
+	let key = der_to_key(data);
+	assert_eq!(accepted(&check_key(key.as_bytes())), !data.is_empty() && key.len() <= MAX_KEY_LENGTH, "key {:?}", key);
+});
@@ -0,0 +1,14 @@
+//! `check_name` accepts exactly the non-empty UTF-8 names within the size cap.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pallet_siip::{check_name, MAX_NAME_LENGTH};
+use siip_fuzz::accepted;
+
+fn reference(name: &[u8]) -> bool {
+	name.len() <= MAX_NAME_LENGTH && std::str::from_utf8(name).map_or(false, |name| !name.is_empty())
+}
+
+fuzz_target!(|data: &[u8]| {
+	assert_eq!(accepted(&check_name(data)), reference(data), "name {:?}", String::from_utf8_lossy(data));
+});
@@ -0,0 +1,28 @@
+//! Decoding a `sha3pow::Seal`, which every imported block's seal goes through, never panics and
+//! agrees with reading its three fixed-width fields by hand: a little-endian difficulty, then the
+//! work and nonce hashes.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use parity_scale_codec::{Decode, Encode};
+use sha3pow::Seal;
+use sp_core::{H256, U256};
+
+fn reference(data: &[u8]) -> Option<Seal> {
+	if data.len() < 96 {
+		return None;
+	}
+	Some(Seal {
+		difficulty: U256::from_little_endian(&data[..32]),
+		work: H256::from_slice(&data[32..64]),
+		nonce: H256::from_slice(&data[64..96]),
+	})
+}
+
+fuzz_target!(|data: &[u8]| {
+	let seal = Seal::decode(&mut &data[..]).ok();
+	assert_eq!(seal, reference(data));
+	if let Some(seal) = seal {
+		assert_eq!(seal.encode(), &data[..96]);
+	}
+});
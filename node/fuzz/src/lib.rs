@@ -0,0 +1,7 @@
+//! Shared by the fuzz targets. Each target runs a validator or decoder on arbitrary bytes and
+//! checks that it doesn't panic and that it agrees with an independent reference.
+
+/// Whether a `check_*` function passed its input: none of the criteria it reports is an `Err`.
+pub fn accepted(criteria: &[u8]) -> bool {
+	!String::from_utf8_lossy(criteria).lines().any(|line| line.starts_with("Err:"))
+}
@@ -11,6 +11,10 @@ import substrateinterface
 from substrateinterface import SubstrateInterface
 from substrateinterface import Keypair
 
+# Registration commits to a hash of the domain before revealing it; client/register.py computes it
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), '..', 'client'))
+from register import commitment, MIN_COMMITMENT_AGE
+
 ##########################################################################################
 ############################## Configuration Constants ##################################
 ##########################################################################################
@@ -109,10 +113,40 @@ def siip_node_connect(port = 9933):
 # register the given domain, and block until it's successfully added to the blockchain.
 # It's up to the user to make sure the domain does not already exist
 def register_and_wait(substrate, domain):
+    # Registration commits to a salted hash of the domain first...
+    salt = os.urandom(16).hex()
+    hash = '0x' + commitment(domain, salt, CHARLIE_KEY.public_key)
+    call = substrate.compose_call(
+        call_module='SiipModule',
+        call_function='commit_registration',
+        call_params={'commitment': hash}
+    )
+    # This test setup uses Charlie to register all domains
+    extrinsic = substrate.create_signed_extrinsic(call=call, keypair=CHARLIE_KEY)
+    substrate.submit_extrinsic(extrinsic)
+
+    # Note that I think with a websocket connection, you could use 
+    # substrate.submit_extrinsic(extrinsic, wait_for_inclusion = True)
+    # to block until the extrinsic is included, and eliminate the below code.
+    # But websocket support in substrate-interface appears to be unstable, so that's a no go for now
+    with Timer(BLOCK_TIMEOUT * 2) as timer: # * 2 is because sometimes it can take up to two blocks for a transaction to get added
+        committed_at = substrate.query('SiipModule', 'Commitments', params=[CHARLIE_KEY.ss58_address, hash])
+        while committed_at is None or committed_at.value is None:
+            timer.check()
+            time.sleep(1)
+            committed_at = substrate.query('SiipModule', 'Commitments', params=[CHARLIE_KEY.ss58_address, hash])
+
+    # ...then reveals it, once the commitment is MIN_COMMITMENT_AGE blocks old
+    with Timer(BLOCK_TIMEOUT * MIN_COMMITMENT_AGE) as timer:
+        while substrate.get_block_number(substrate.get_chain_head()) < committed_at.value + MIN_COMMITMENT_AGE - 1:
+            timer.check()
+            time.sleep(1)
+
     call = substrate.compose_call(
         call_module='SiipModule',
-        call_function='register_certificate',
+        call_function='reveal_registration',
         call_params={
+            'salt': salt,
             'name': 'John Smith',
             'domain': domain,
             'ip_addr': '10.0.0.1',
@@ -120,15 +154,10 @@ def register_and_wait(substrate, domain):
             'key': '01:23:45:67:89:AB:CD:EF',
         }
     )
-    # This test setup uses Charlie to register all domains
     extrinsic = substrate.create_signed_extrinsic(call=call, keypair=CHARLIE_KEY)
     substrate.submit_extrinsic(extrinsic)
-    
-    # Note that I think with a websocket connection, you could use 
-    # substrate.submit_extrinsic(extrinsic, wait_for_inclusion = True)
-    # to block until the extrinsic is included, and eliminate the below code.
-    # But websocket support in substrate-interface appears to be unstable, so that's a no go for now
-    with Timer(BLOCK_TIMEOUT * 2) as timer: # * 2 is because sometimes it can take up to two blocks for a transaction to get added
+
+    with Timer(BLOCK_TIMEOUT * 2) as timer:
         certificate = substrate.query('SiipModule', 'CertificateMap', params=[domain])
         while certificate is None:
             timer.check()
@@ -203,18 +232,17 @@ while not alice_has_mined or not bob_has_mined:
     alice_new_balance = alice.query('System', 'Account', params=[ALICE_ACCOUNT_ID]).value['data']['free']
     bob_new_balance = alice.query('System', 'Account', params=[BOB_ACCOUNT_ID]).value['data']['free']
 
-    # either Alice or Bob should've mined the transaction, and their balance should increase as a result
-    assert alice_balance == alice_new_balance or bob_balance == bob_new_balance
+    # Registering takes several blocks, committing, waiting and revealing, and whoever mined
+    # one of them has been rewarded for it
     assert alice_balance != alice_new_balance or bob_balance != bob_new_balance
 
     if alice_balance != alice_new_balance:
         alice_has_mined = True
         alice_balance = alice_new_balance
-        print('Mined by Alice')
-    else:
+    if bob_balance != bob_new_balance:
         bob_has_mined = True
         bob_balance = bob_new_balance
-        print('Mined by Bob')
+    print('Mined by ' + ' and '.join(name for name, mined in [('Alice', alice_has_mined), ('Bob', bob_has_mined)] if mined) + ' so far')
 
     domains.append(domain)
     assert_consensus(alice, bob, domains)
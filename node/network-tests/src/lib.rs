@@ -0,0 +1,238 @@
+//! Runs local networks of `siip-node`s for integration tests. Each node is a child process on
+//! localhost, with its own ports and base path, mining a copy of the local testnet whose
+//! difficulty is low enough for blocks every second or so. Nodes only connect to the peers they
+//! are told to, so a test can partition the network and heal it again.
+//!
+//! The nodes run the binary in `SIIP_NODE`, or else `target/release/siip-node`; build it first.
+
+use std::env;
+use std::fs::{self, File};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use siip_client::Client;
+use siip_node_runtime::{BlockNumber, Hash};
+
+/// The difficulty the nodes mine at: a block every second or so per node, on a release build.
+pub const DIFFICULTY: u64 = 1_000_000;
+
+/// How long a network has to reach the state a test waits for.
+pub const TIMEOUT: Duration = Duration::from_secs(180);
+
+/// How often to poll the nodes while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The development accounts the nodes mine as, one per node.
+const NAMES: [&str; 6] = ["alice", "bob", "charlie", "dave", "eve", "ferdie"];
+
+/// One running node.
+pub struct Node {
+	pub name: &'static str,
+	pub client: Client,
+	/// The node's libp2p peer ID.
+	pub peer_id: String,
+	/// The multiaddress other nodes reach it at.
+	pub address: String,
+	process: Child,
+}
+
+/// Several nodes on one chain, and the directory holding their spec, databases and logs. The
+/// nodes are killed, and the directory removed, when it is dropped.
+pub struct Network {
+	dir: PathBuf,
+	nodes: Vec<Node>,
+}
+
+/// A port nothing is listening on right now.
+fn free_port() -> u16 {
+	TcpListener::bind("127.0.0.1:0")
+		.and_then(|listener| listener.local_addr())
+		.expect("a local port is free")
+		.port()
+}
+
+fn node_binary() -> PathBuf {
+	match env::var_os("SIIP_NODE") {
+		Some(path) => path.into(),
+		None => Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/release/siip-node"),
+	}
+}
+
+/// Writes the local testnet's spec, at the test difficulty and sealed with Sha3 throughout, to
+/// `dir/spec.json`.
+fn write_spec(dir: &Path) -> PathBuf {
+	let output = Command::new(node_binary())
+		.args(&["build-spec", "--chain", "local", "--disable-default-bootnode"])
+		.stderr(Stdio::null())
+		.output()
+		.expect("siip-node runs; build it, or point SIIP_NODE at it");
+	assert!(output.status.success(), "build-spec failed");
+
+	let mut spec: Value = serde_json::from_slice(&output.stdout).expect("build-spec prints JSON");
+	spec["properties"] = json!({
+		"powDifficulty": DIFFICULTY,
+		"targetBlockTime": 2,
+		"proposeTimeout": 1,
+	});
+	let path = dir.join("spec.json");
+	fs::write(&path, spec.to_string()).expect("the spec can be written");
+	path
+}
+
+/// Calls `poll` until it returns something, and panics with `what` if that takes longer than
+/// `TIMEOUT`.
+pub async fn wait_for<T, F, Fut>(what: &str, mut poll: F) -> T
+where
+	F: FnMut() -> Fut,
+	Fut: std::future::Future<Output = Option<T>>,
+{
+	let deadline = Instant::now() + TIMEOUT;
+	loop {
+		if let Some(value) = poll().await {
+			return value;
+		}
+		assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+		tokio::time::sleep(POLL_INTERVAL).await;
+	}
+}
+
+impl Network {
+	/// Starts `count` nodes, connected to no one, and waits for their RPC servers.
+	pub async fn start(count: usize) -> Network {
+		assert!(count <= NAMES.len(), "at most {} nodes", NAMES.len());
+		let dir = env::temp_dir().join(format!("siip-network-{}-{}", std::process::id(), free_port()));
+		fs::create_dir_all(&dir).expect("the network directory can be created");
+		let spec = write_spec(&dir);
+
+		let mut network = Network { dir, nodes: Vec::new() };
+		for name in NAMES.iter().take(count) {
+			let node = network.spawn(name, &spec).await;
+			network.nodes.push(node);
+		}
+		network
+	}
+
+	async fn spawn(&self, name: &'static str, spec: &Path) -> Node {
+		let (p2p_port, rpc_port) = (free_port(), free_port());
+		let log = File::create(self.dir.join(format!("{}.log", name))).expect("the log can be created");
+		let process = Command::new(node_binary())
+			.arg("--chain").arg(spec)
+			.arg("--base-path").arg(self.dir.join(name))
+			.arg(format!("--{}", name))
+			.args(&["--port", &p2p_port.to_string()])
+			.args(&["--rpc-port", &rpc_port.to_string()])
+			.args(&["--ws-port", &free_port().to_string()])
+			.args(&["--rpc-methods", "Unsafe", "--reserved-only", "--no-mdns", "--no-prometheus", "--no-telemetry"])
+			.stdout(Stdio::null())
+			.stderr(log)
+			.spawn()
+			.expect("siip-node runs; build it, or point SIIP_NODE at it");
+
+		let client = Client::new(format!("http://127.0.0.1:{}", rpc_port), Duration::from_secs(10));
+		let rpc = &client;
+		let peer_id = wait_for(&format!("{}'s RPC server", name), move || async move {
+			rpc.request("system_localPeerId", json!([])).await.ok()?.as_str().map(String::from)
+		}).await;
+		let address = format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", p2p_port, peer_id);
+		Node { name, client, peer_id, address, process }
+	}
+
+	pub fn node(&self, index: usize) -> &Node {
+		&self.nodes[index]
+	}
+
+	pub fn len(&self) -> usize {
+		self.nodes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.nodes.is_empty()
+	}
+
+	/// Where node `index` writes its log, for a test to point at when it fails.
+	pub fn log(&self, index: usize) -> PathBuf {
+		self.dir.join(format!("{}.log", self.nodes[index].name))
+	}
+
+	/// Lets nodes `a` and `b` connect to each other.
+	pub async fn connect(&self, a: usize, b: usize) {
+		for (from, to) in [(a, b), (b, a)].iter() {
+			self.nodes[*from].client.request("system_addReservedPeer", json!([self.nodes[*to].address]))
+				.await
+				.expect("the node accepts the peer");
+		}
+	}
+
+	/// Disconnects nodes `a` and `b`, and keeps them apart.
+	pub async fn disconnect(&self, a: usize, b: usize) {
+		for (from, to) in [(a, b), (b, a)].iter() {
+			self.nodes[*from].client.request("system_removeReservedPeer", json!([self.nodes[*to].peer_id]))
+				.await
+				.expect("the node drops the peer");
+		}
+	}
+
+	/// Connects every node in `group` to every other.
+	pub async fn connect_group(&self, group: &[usize]) {
+		for (i, a) in group.iter().enumerate() {
+			for b in &group[i + 1..] {
+				self.connect(*a, *b).await;
+			}
+		}
+	}
+
+	pub async fn connect_all(&self) {
+		self.connect_group(&(0..self.len()).collect::<Vec<_>>()).await;
+	}
+
+	/// The number and hash of node `index`'s best block.
+	pub async fn best(&self, index: usize) -> Option<(BlockNumber, Hash)> {
+		let client = &self.nodes[index].client;
+		let number = client.best_number().await.ok()?;
+		Some((number, client.block_hash(number).await.ok()??))
+	}
+
+	/// Whether the nodes in `group` agree on a chain of at least `height` blocks: the shortest of
+	/// their best chains must be part of all the others. Returns the tip of that chain.
+	pub async fn agreed(&self, group: &[usize], height: BlockNumber) -> Option<(BlockNumber, Hash)> {
+		let mut shortest: Option<(BlockNumber, Hash)> = None;
+		for index in group {
+			let best = self.best(*index).await?;
+			match shortest {
+				Some((number, _)) if number <= best.0 => {},
+				_ => shortest = Some(best),
+			}
+		}
+		let (number, hash) = shortest.filter(|(number, _)| *number >= height)?;
+		for index in group {
+			if self.nodes[*index].client.block_hash(number).await.ok()? != Some(hash) {
+				return None;
+			}
+		}
+		Some((number, hash))
+	}
+
+	/// Waits until the nodes in `group` agree on a chain of at least `height` blocks, and
+	/// returns its tip.
+	pub async fn converge(&self, group: &[usize], height: BlockNumber) -> (BlockNumber, Hash) {
+		wait_for(&format!("nodes {:?} to agree on {} blocks", group, height), move || self.agreed(group, height)).await
+	}
+
+	pub async fn converge_all(&self, height: BlockNumber) -> (BlockNumber, Hash) {
+		self.converge(&(0..self.len()).collect::<Vec<_>>(), height).await
+	}
+}
+
+impl Drop for Network {
+	fn drop(&mut self) {
+		for node in self.nodes.iter_mut() {
+			let _ = node.process.kill();
+			let _ = node.process.wait();
+		}
+		if !std::thread::panicking() {
+			let _ = fs::remove_dir_all(&self.dir);
+		}
+	}
+}
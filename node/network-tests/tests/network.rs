@@ -0,0 +1,88 @@
+//! Multi-node tests against real `siip-node`s. They need a release build of the node and take a
+//! few minutes each, so they're ignored by default:
+//!
+//! ```bash
+//! cargo build --release -p siip-node
+//! cargo test -p siip-network-tests -- --ignored --test-threads 1
+//! ```
+
+use siip_client::{CertificateFields, Signer};
+use siip_network_tests::{wait_for, Network};
+use siip_node_runtime::{BlockNumber, Hash, MinCommitmentAge};
+
+fn certificate(domain: &str) -> CertificateFields {
+	CertificateFields {
+		name: "Charlie".into(),
+		domain: domain.into(),
+		ip_addr: "10.0.0.1".into(),
+		info: "{}".into(),
+		key: siip_dane::der_to_key(&[0x30, 0x59, 0x30, 0x13]),
+	}
+}
+
+/// Waits until every node has a certificate for `domain` on its best chain.
+async fn wait_for_certificate(network: &Network, domain: &str) {
+	for index in 0..network.len() {
+		let client = &network.node(index).client;
+		wait_for(&format!("{} to see {} (log: {:?})", network.node(index).name, domain, network.log(index)), move || async move {
+			client.lookup(domain, None).await.ok()?.map(|_| ())
+		}).await;
+	}
+}
+
+/// Whether `block` is still on node `index`'s best chain.
+async fn on_best_chain(network: &Network, index: usize, (number, hash): (BlockNumber, Hash)) -> bool {
+	network.node(index).client.block_hash(number).await.unwrap() == Some(hash)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn certificates_propagate_and_nodes_converge() {
+	let network = Network::start(3).await;
+	network.connect_all().await;
+	network.converge_all(2).await;
+
+	//Registered through one node, the certificate reaches every node
+	let signer = Signer::from_seed("//Charlie").unwrap();
+	let inclusion = network.node(0).client.register(&signer, &certificate("propagate.com")).await.unwrap();
+	wait_for_certificate(&network, "propagate.com").await;
+
+	//And they all build on the same chain past it
+	network.converge_all(inclusion.block_number + 3).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn partitioned_network_reorgs_to_one_chain_when_healed() {
+	let network = Network::start(4).await;
+	let (left, right) = ([0, 1], [2, 3]);
+	network.connect_all().await;
+	let (split, _) = network.converge_all(2).await;
+
+	//Cut the network in two; each half mines its own fork
+	for a in left.iter() {
+		for b in right.iter() {
+			network.disconnect(*a, *b).await;
+		}
+	}
+	let signer = Signer::from_seed("//Charlie").unwrap();
+	network.node(right[0]).client.register(&signer, &certificate("partition.com")).await.unwrap();
+	let left_tip = network.converge(&left, split + 5).await;
+	let right_tip = network.converge(&right, split + 5).await;
+	assert!(!on_best_chain(&network, left[0], right_tip).await, "the halves didn't fork");
+	assert!(!on_best_chain(&network, right[0], left_tip).await, "the halves didn't fork");
+
+	//Once healed, the nodes agree on a chain past both tips, so one half has reorged off its fork
+	network.connect_all().await;
+	let (height, _) = network.converge_all(left_tip.0.max(right_tip.0) + 1).await;
+
+	//If the right half's fork won, the certificate survived. If the left half's did, the
+	//commitment and the reveal went back in the pool together, and the reveal fails for coming
+	//too soon after the re-mined commitment. Once both have settled, register again
+	let (settled, tip) = network.converge_all(height + MinCommitmentAge::get() + 1).await;
+	if network.node(0).client.lookup("partition.com", Some(tip)).await.unwrap().is_none() {
+		network.node(0).client.register(&signer, &certificate("partition.com")).await.unwrap();
+	}
+	wait_for_certificate(&network, "partition.com").await;
+	network.converge_all(settled).await;
+}
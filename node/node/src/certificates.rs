@@ -0,0 +1,230 @@
+//! Certificates registered at genesis, read from a domain list given to `build-spec --certificates`.
+//!
+//! The list is either a JSON array of objects, or CSV whose first row names the columns. Both
+//! take the columns `domain`, `ip` (or `ipAddr`/`ip_addr`) and `key`, plus optionally `owner`
+//! (an SS58 address; the sudo account by default), `name` (the domain by default) and `info`
+//! (`{}` by default). CSV has no quoting, so the last column takes the rest of each line: put
+//! `info` last if it holds commas. Blank lines and lines starting with `#` are skipped.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use sp_core::crypto::Ss58Codec;
+use siip_node_runtime::AccountId;
+use siip_node_runtime::pallet_siip::validation_failures;
+
+/// `(owner, name, domain, ip_addr, info, key)`, as `pallet_siip`'s genesis config lists them.
+pub type GenesisEntry = (AccountId, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// One row of the domain list, checked the way the pallet will check it at genesis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenesisCertificate {
+	/// `None` leaves the certificate to the chain's sudo account.
+	pub owner: Option<AccountId>,
+	pub name: String,
+	pub domain: String,
+	pub ip_addr: String,
+	pub info: String,
+	pub key: String,
+}
+
+/// A row as written in the file, before defaults are filled in.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Row {
+	domain: String,
+	#[serde(alias = "ip", alias = "ip_addr")]
+	ip_addr: String,
+	key: String,
+	owner: Option<String>,
+	name: Option<String>,
+	info: Option<String>,
+}
+
+impl GenesisCertificate {
+	/// The certificate in the form `pallet_siip`'s genesis config takes it.
+	pub fn into_genesis(self, default_owner: &AccountId) -> GenesisEntry {
+		(
+			self.owner.unwrap_or_else(|| default_owner.clone()),
+			self.name.into_bytes(),
+			self.domain.into_bytes(),
+			self.ip_addr.into_bytes(),
+			self.info.into_bytes(),
+			self.key.into_bytes(),
+		)
+	}
+
+	fn from_row(row: Row) -> Result<Self, String> {
+		let Row { domain, ip_addr, key, owner, name, info } = row;
+		let owner = match owner.as_deref().map(str::trim) {
+			None | Some("") => None,
+			Some(owner) => Some(AccountId::from_ss58check(owner)
+				.map_err(|_| format!("owner {} isn't an SS58 address", owner))?),
+		};
+		let cert = GenesisCertificate {
+			owner,
+			name: name.filter(|name| !name.is_empty()).unwrap_or_else(|| domain.clone()),
+			info: info.filter(|info| !info.is_empty()).unwrap_or_else(|| "{}".into()),
+			domain,
+			ip_addr,
+			key,
+		};
+
+		let failures = validation_failures(
+			cert.name.as_bytes(), cert.domain.as_bytes(), cert.ip_addr.as_bytes(), cert.info.as_bytes(), cert.key.as_bytes(),
+		);
+		if failures.is_empty() {
+			Ok(cert)
+		} else {
+			Err(format!("{} would be rejected ({})", cert.domain, failures.join("; ")))
+		}
+	}
+}
+
+/// Reads and checks the domain list at `path`.
+pub fn load(path: &Path) -> Result<Vec<GenesisCertificate>, String> {
+	let contents = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+	parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parses a domain list: JSON if it starts with `[`, CSV otherwise.
+pub fn parse(contents: &str) -> Result<Vec<GenesisCertificate>, String> {
+	let rows = if contents.trim_start().starts_with('[') {
+		parse_json(contents)?
+	} else {
+		parse_csv(contents)?
+	};
+
+	let mut domains = HashSet::new();
+	rows.into_iter()
+		.map(|(location, row)| {
+			let cert = GenesisCertificate::from_row(row).map_err(|e| format!("{}: {}", location, e))?;
+			if !domains.insert(cert.domain.clone()) {
+				return Err(format!("{}: {} is listed more than once", location, cert.domain));
+			}
+			Ok(cert)
+		})
+		.collect()
+}
+
+/// Each entry of a JSON array, with its position for error messages.
+fn parse_json(contents: &str) -> Result<Vec<(String, Row)>, String> {
+	let rows: Vec<Row> = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+	Ok(rows.into_iter().enumerate().map(|(i, row)| (format!("entry {}", i + 1), row)).collect())
+}
+
+/// Each CSV line after the header, with its line number for error messages.
+fn parse_csv(contents: &str) -> Result<Vec<(String, Row)>, String> {
+	let mut lines = contents.lines()
+		.enumerate()
+		.map(|(i, line)| (i + 1, line.trim()))
+		.filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+	let columns: Vec<String> = match lines.next() {
+		Some((_, header)) => header.split(',').map(|column| column.trim().to_ascii_lowercase()).collect(),
+		None => return Ok(Vec::new()),
+	};
+	for required in &["domain", "key"] {
+		if !columns.iter().any(|column| column == required) {
+			return Err(format!("the header has no {} column", required));
+		}
+	}
+	if !columns.iter().any(|column| column == "ip" || column == "ip_addr" || column == "ipaddr") {
+		return Err("the header has no ip column".into());
+	}
+
+	lines
+		.map(|(number, line)| {
+			let values: Vec<&str> = line.splitn(columns.len(), ',').map(str::trim).collect();
+			if values.len() != columns.len() {
+				return Err(format!("line {}: expected {} columns, found {}", number, columns.len(), values.len()));
+			}
+			let mut row = Row::default();
+			for (column, value) in columns.iter().zip(values) {
+				let value = value.to_string();
+				match column.as_str() {
+					"domain" => row.domain = value,
+					"ip" | "ip_addr" | "ipaddr" => row.ip_addr = value,
+					"key" => row.key = value,
+					"owner" => row.owner = Some(value),
+					"name" => row.name = Some(value),
+					"info" => row.info = Some(value),
+					other => return Err(format!("line {}: unknown column {}", number, other)),
+				}
+			}
+			Ok((format!("line {}", number), row))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::sr25519;
+	use crate::chain_spec::get_account_id_from_seed;
+
+	const KEY: &str = "30:59:30:13:06:07:2A:86";
+
+	#[test]
+	fn csv() {
+		let bob = get_account_id_from_seed::<sr25519::Public>("Bob");
+		let certs = parse(&format!(
+			"# Seeded domains\ndomain, ip, key, owner, info\n\n\
+			example.com, 10.0.0.1, {key}, , \n\
+			siip.org, 10.0.0.2, {key}, {bob}, {{\"a\": 1, \"b\": 2}}\n",
+			key = KEY, bob = bob.to_ss58check(),
+		)).unwrap();
+
+		assert_eq!(certs, vec![
+			GenesisCertificate {
+				owner: None,
+				name: "example.com".into(),
+				domain: "example.com".into(),
+				ip_addr: "10.0.0.1".into(),
+				info: "{}".into(),
+				key: KEY.into(),
+			},
+			GenesisCertificate {
+				owner: Some(bob.clone()),
+				name: "siip.org".into(),
+				domain: "siip.org".into(),
+				ip_addr: "10.0.0.2".into(),
+				info: "{\"a\": 1, \"b\": 2}".into(),
+				key: KEY.into(),
+			},
+		]);
+
+		let alice = get_account_id_from_seed::<sr25519::Public>("Alice");
+		let (owner, name, ..) = certs[0].clone().into_genesis(&alice);
+		assert_eq!((owner, name), (alice.clone(), b"example.com".to_vec()));
+		assert_eq!(certs[1].clone().into_genesis(&alice).0, bob);
+	}
+
+	#[test]
+	fn json() {
+		let certs = parse(&format!(
+			r#"[{{"domain": "example.com", "ip": "10.0.0.1", "key": "{key}", "name": "Example"}},
+			{{"domain": "siip.org", "ipAddr": "10.0.0.2", "key": "{key}"}}]"#,
+			key = KEY,
+		)).unwrap();
+		assert_eq!(certs.len(), 2);
+		assert_eq!((certs[0].name.as_str(), certs[0].ip_addr.as_str()), ("Example", "10.0.0.1"));
+		assert_eq!((certs[1].name.as_str(), certs[1].ip_addr.as_str()), ("siip.org", "10.0.0.2"));
+	}
+
+	#[test]
+	fn errors_are_located() {
+		let header = "domain,ip,key\n";
+		assert_eq!(parse("domain,key\n").unwrap_err(), "the header has no ip column");
+		assert_eq!(parse(&format!("{}example.com,10.0.0.1\n", header)).unwrap_err(),
+			"line 2: expected 3 columns, found 2");
+		assert!(parse(&format!("{}example.com,10.0.0.1,{}\nNot-A-Domain,10.0.0.1,{}\n", header, KEY, KEY))
+			.unwrap_err().starts_with("line 3: Not-A-Domain would be rejected (domain: "));
+		assert_eq!(parse(&format!("{}example.com,10.0.0.1,{}\nexample.com,10.0.0.2,{}\n", header, KEY, KEY)).unwrap_err(),
+			"line 3: example.com is listed more than once");
+		assert!(parse(r#"[{"domain": "example.com", "ip": "10.0.0.1", "key": "30:59", "owner": "nobody"}]"#)
+			.unwrap_err().starts_with("entry 1: owner nobody isn't an SS58 address"));
+		assert!(parse(r#"[{"domain": "example.com", "ip": "10.0.0.1", "key": "30:59", "port": 443}]"#).is_err());
+	}
+}
@@ -1,15 +1,20 @@
 use frame_benchmarking::frame_support::pallet_prelude::ValueQuery;
-use sp_core::{Pair, Public, sr25519};
+use sp_core::{Pair, Public, sr25519, U256};
 use sp_runtime::{
 	MultiSignature, traits::{Verify, IdentifyAccount}, 
-	transaction_validity::{TransactionValidity, InvalidTransaction, TransactionValidityError}
+	transaction_validity::TransactionValidity
 };
 use siip_node_runtime::{
 	AccountId, BalancesConfig, GenesisConfig,
-	SudoConfig, SystemConfig, WASM_BINARY, Signature, DemocracyConfig, UncheckedExtrinsic
+	SudoConfig, SystemConfig, WASM_BINARY, Signature, DemocracyConfig,
+	SiipModuleConfig, CheckpointConfig,
 };
+use crate::certificates::GenesisCertificate;
 
-use sc_service::ChainType;
+use sc_service::{ChainType, Properties};
+use sha3pow::PowSchedule;
+use std::convert::TryInto;
+use std::time::Duration;
 
 // The URL for the telemetry server.
 // const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -17,6 +22,67 @@ use sc_service::ChainType;
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig>;
 
+/// The chain spec property naming the first block sealed with the memory-hard proof of work.
+/// Chains without it are sealed with Sha3 forever.
+pub const MEMORY_HARD_POW_FROM: &str = "memoryHardPowFrom";
+/// The chain spec property holding the difficulty every block must meet.
+pub const POW_DIFFICULTY: &str = "powDifficulty";
+/// The chain spec property holding how many seconds a miner works on a block before proposing a
+/// fresh one with the latest transactions.
+pub const TARGET_BLOCK_TIME: &str = "targetBlockTime";
+/// The chain spec property holding how many seconds the proposer may spend building a block.
+pub const PROPOSE_TIMEOUT: &str = "proposeTimeout";
+/// The chain spec property holding how many nonces the miner tries between checks for a new block.
+pub const MINING_ROUNDS: &str = "miningRounds";
+
+/// How the chain's blocks are mined and sealed, read from its spec's properties.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsensusParams {
+	pub schedule: PowSchedule,
+	pub difficulty: U256,
+	pub target_block_time: Duration,
+	pub propose_timeout: Duration,
+	pub mining_rounds: u32,
+}
+
+impl Default for ConsensusParams {
+	fn default() -> Self {
+		ConsensusParams {
+			schedule: PowSchedule::default(),
+			difficulty: U256::from(1_000),
+			target_block_time: Duration::from_secs(10),
+			propose_timeout: Duration::from_secs(10),
+			mining_rounds: 1_000,
+		}
+	}
+}
+
+/// Reads the consensus parameters from a chain spec's properties. Those it doesn't set keep
+/// their defaults; those it sets to anything but a positive integer are an error.
+pub fn consensus_params(properties: &Properties) -> Result<ConsensusParams, String> {
+	let property = |name: &str| -> Result<Option<u64>, String> {
+		match properties.get(name) {
+			None => Ok(None),
+			Some(value) => value.as_u64()
+				.filter(|&value| value > 0 || name == MEMORY_HARD_POW_FROM)
+				.map(Some)
+				.ok_or_else(|| format!("Chain spec property {} must be a positive integer, not {}", name, value)),
+		}
+	};
+
+	let defaults = ConsensusParams::default();
+	Ok(ConsensusParams {
+		schedule: PowSchedule { memory_hard_from: property(MEMORY_HARD_POW_FROM)? },
+		difficulty: property(POW_DIFFICULTY)?.map(U256::from).unwrap_or(defaults.difficulty),
+		target_block_time: property(TARGET_BLOCK_TIME)?.map(Duration::from_secs).unwrap_or(defaults.target_block_time),
+		propose_timeout: property(PROPOSE_TIMEOUT)?.map(Duration::from_secs).unwrap_or(defaults.propose_timeout),
+		mining_rounds: match property(MINING_ROUNDS)? {
+			Some(rounds) => rounds.try_into().map_err(|_| format!("Chain spec property {} is too large", MINING_ROUNDS))?,
+			None => defaults.mining_rounds,
+		},
+	})
+}
+
 /// Generate a crypto pair from seed.
 pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
 	TPublic::Pair::from_string(&format!("//{}", seed), None)
@@ -33,7 +99,8 @@ pub fn get_account_id_from_seed<TPublic: Public>(seed: &str) -> AccountId where
 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
-pub fn development_config() -> Result<ChainSpec, String> {
+/// `certificates` are registered at block 0, owned by the sudo account unless they say otherwise.
+pub fn development_config(certificates: Vec<GenesisCertificate>) -> Result<ChainSpec, String> {
 	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm binary not available".to_string())?;
 
 	Ok(ChainSpec::from_genesis(
@@ -53,6 +120,9 @@ pub fn development_config() -> Result<ChainSpec, String> {
 				get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 				get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
 			],
+			// Checkpoint signers
+			vec![get_account_id_from_seed::<sr25519::Public>("Alice")],
+			certificates.clone(),
 			true,
 		),
 		// Bootnodes
@@ -68,9 +138,14 @@ pub fn development_config() -> Result<ChainSpec, String> {
 	))
 }
 
-pub fn local_testnet_config() -> Result<ChainSpec, String> {
+/// `certificates` are registered at block 0, owned by the sudo account unless they say otherwise.
+pub fn local_testnet_config(certificates: Vec<GenesisCertificate>) -> Result<ChainSpec, String> {
 	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm binary not available".to_string())?;
 
+	// Sha3 for the first blocks, then the memory-hard proof of work
+	let mut properties = Properties::new();
+	properties.insert(MEMORY_HARD_POW_FROM.into(), 100.into());
+
 	Ok(ChainSpec::from_genesis(
 		// Name
 		"Local Testnet",
@@ -96,6 +171,13 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 				get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 				get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
 			],
+			// Checkpoint signers
+			vec![
+				get_account_id_from_seed::<sr25519::Public>("Alice"),
+				get_account_id_from_seed::<sr25519::Public>("Bob"),
+				get_account_id_from_seed::<sr25519::Public>("Charlie"),
+			],
+			certificates.clone(),
 			true,
 		),
 		// Bootnodes
@@ -105,7 +187,7 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 		// Protocol ID
 		None,
 		// Properties
-		None,
+		Some(properties),
 		// Extensions
 		None,
 	))
@@ -116,6 +198,8 @@ fn testnet_genesis(
 	wasm_binary: &[u8],
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
+	checkpoint_signers: Vec<AccountId>,
+	certificates: Vec<GenesisCertificate>,
 	_enable_println: bool,
 ) -> GenesisConfig {
 	GenesisConfig {
@@ -130,135 +214,42 @@ fn testnet_genesis(
 		}),
 		pallet_sudo: Some(SudoConfig {
 			// Assign network admin rights.
-			key: root_key,
+			key: root_key.clone(),
+		}),
+		pallet_democracy: Some(DemocracyConfig::default()),
+		pallet_siip: Some(SiipModuleConfig {
+			certificates: certificates.into_iter().map(|cert| cert.into_genesis(&root_key)).collect(),
+		}),
+		pallet_checkpoint: Some(CheckpointConfig {
+			// A bare majority of the signers makes a checkpoint
+			threshold: checkpoint_signers.len() as u32 / 2 + 1,
+			signers: checkpoint_signers,
 		}),
-		pallet_democracy: Some(DemocracyConfig::default())
 	}
 }
 
-#[cfg(test)]
-use siip_node_runtime::BuildStorage;
-#[cfg(test)]
-use sp_block_builder::runtime_decl_for_BlockBuilder::BlockBuilder;
-#[cfg(test)]
-use sp_api::{runtime_decl_for_Core::Core};
-#[cfg(test)]
-use sp_core::Encode;
-#[cfg(test)]
-fn new_test_ext() -> sp_io::TestExternalities {
-	let wasm_binary = WASM_BINARY.unwrap();
-
-	let gen = testnet_genesis(
-		wasm_binary,
-		// Sudo account
-		get_account_id_from_seed::<sr25519::Public>("Alice"),
-		// Pre-funded accounts
-		vec![
-			get_account_id_from_seed::<sr25519::Public>("Alice"),
-			get_account_id_from_seed::<sr25519::Public>("Bob"),
-			get_account_id_from_seed::<sr25519::Public>("Charlie"),
-			get_account_id_from_seed::<sr25519::Public>("Dave"),
-			get_account_id_from_seed::<sr25519::Public>("Eve"),
-			get_account_id_from_seed::<sr25519::Public>("Ferdie"),
-			get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
-			get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
-			get_account_id_from_seed::<sr25519::Public>("Charlie//stash"),
-			get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
-			get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
-			get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
-		],
-		true,
-	);
-	let storage = gen.build_storage().unwrap();
-	sp_io::TestExternalities::from(storage)
-}
-
-
-#[test]
-fn transaction_fee_subtracted() {
-	new_test_ext().execute_with(|| {
-		let register = siip_node_runtime::pallet_siip::Call::register_certificate(
-			"Alice Smith".chars().map(|c| c as u8).collect(),
-			"abc.com".chars().map(|c| c as u8).collect(),
-			"127.0.0.1".chars().map(|c| c as u8).collect(),
-			"{}".chars().map(|c| c as u8).collect(),
-			"12:12:12".chars().map(|c| c as u8).collect()
-		);
-
-	
-		let alice_bal = siip_node_runtime::pallet_balances::Module::<siip_node_runtime::Runtime>::free_balance(get_account_id_from_seed::<sr25519::Public>("Alice"));
-
-		let r = siip_node_runtime::Call::SiipModule(register);
-		let extras = siip_node_runtime::default_extras(0);
-		let signed = sign("Alice", &r, &extras);
-
-		let extrinsic = siip_node_runtime::UncheckedExtrinsic {
-			function: r.clone(),
-			signature: Some((
-				siip_node_runtime::Address::Id(get_account_id_from_seed::<sr25519::Public>("Alice")),
-				signed,
-				extras
-			))
-		};
-
-		let genesis_hash = siip_node_runtime::test_block_hash(0);
-
-		let b = siip_node_runtime::test_construct_block(1, genesis_hash, vec![extrinsic]);
-	
-		let new_alice_bal = siip_node_runtime::pallet_balances::Module::<siip_node_runtime::Runtime>::free_balance(get_account_id_from_seed::<sr25519::Public>("Alice"));
-
-		assert!(new_alice_bal < alice_bal);
-
-	});
-}
-
 #[test]
-fn insufficient_balance_cant_register_certificate() {
-	new_test_ext().execute_with(|| {
-		let register = siip_node_runtime::pallet_siip::Call::register_certificate(
-			"Alice Smith".chars().map(|c| c as u8).collect(),
-			"abc.com".chars().map(|c| c as u8).collect(),
-			"127.0.0.1".chars().map(|c| c as u8).collect(),
-			"{}".chars().map(|c| c as u8).collect(),
-			"12:12:12".chars().map(|c| c as u8).collect()
-		);
-
-	
-		let broke_bal = siip_node_runtime::pallet_balances::Module::<siip_node_runtime::Runtime>::free_balance(get_account_id_from_seed::<sr25519::Public>("Broke"));
-
-		let r = siip_node_runtime::Call::SiipModule(register);
-		let extras = siip_node_runtime::default_extras(0);
-		let signed = sign("Broke", &r, &extras);
-
-		let extrinsic = siip_node_runtime::UncheckedExtrinsic {
-			function: r.clone(),
-			signature: Some((
-				siip_node_runtime::Address::Id(get_account_id_from_seed::<sr25519::Public>("Broke")),
-				signed,
-				extras
-			))
-		};
-
-		let genesis_hash = siip_node_runtime::test_block_hash(0);
-
-		let b = siip_node_runtime::test_construct_block(1, genesis_hash, vec![extrinsic]);
-		assert_eq!(b, Err(TransactionValidityError::Invalid(InvalidTransaction::Payment)));
-	});
-}
-
-fn print_balances(name: &str) {
-	let free_balance = siip_node_runtime::pallet_balances::Module::<siip_node_runtime::Runtime>::free_balance(get_account_id_from_seed::<sr25519::Public>(name)) / 1_000_000_000_000u128;
-	println!("{} has {:?} free", name, free_balance);
-	let reserved_balance = siip_node_runtime::pallet_balances::Module::<siip_node_runtime::Runtime>::reserved_balance(get_account_id_from_seed::<sr25519::Public>(name)) / 1_000_000_000_000u128;
-	println!("{} has {:?} reserved", name, reserved_balance);
-}
-
-pub fn sign(name: &str, function: &siip_node_runtime::Call, extras: &siip_node_runtime::SignedExtra ) -> siip_node_runtime::Signature {
-	use sp_core::Encode;
-	let raw_payload = siip_node_runtime::SignedPayload::new(function.clone(), extras.clone()).unwrap();
-	let sig = raw_payload.using_encoded(|p| sr25519::Pair::from_string(&format!("//{}", name), None)
-	.expect("static values are valid; qed").sign(p));
-
-	let signature = siip_node_runtime::Signature::Sr25519(sig);
-	signature
+fn consensus_params_from_properties() {
+	assert_eq!(consensus_params(&Properties::new()), Ok(ConsensusParams::default()));
+
+	let properties: Properties = serde_json::from_str(r#"{
+		"memoryHardPowFrom": 0,
+		"powDifficulty": 50,
+		"targetBlockTime": 6,
+		"proposeTimeout": 2,
+		"miningRounds": 100,
+		"tokenSymbol": "SIIP"
+	}"#).unwrap();
+	assert_eq!(consensus_params(&properties), Ok(ConsensusParams {
+		schedule: PowSchedule { memory_hard_from: Some(0) },
+		difficulty: U256::from(50),
+		target_block_time: Duration::from_secs(6),
+		propose_timeout: Duration::from_secs(2),
+		mining_rounds: 100,
+	}));
+
+	for bad in &[r#"{"powDifficulty": 0}"#, r#"{"targetBlockTime": "6"}"#, r#"{"miningRounds": 4294967296}"#, r#"{"memoryHardPowFrom": -1}"#] {
+		let properties: Properties = serde_json::from_str(bad).unwrap();
+		assert!(consensus_params(&properties).is_err(), "{} is accepted", bad);
+	}
 }
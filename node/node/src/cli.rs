@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use structopt::StructOpt;
 use sc_cli::RunCmd;
 
@@ -8,6 +9,11 @@ pub struct Cli {
 
 	#[structopt(flatten)]
 	pub run: RunCmd,
+
+	/// How many blocks below the best block the certificate lookup RPCs read, unless a request
+	/// asks for another depth. A certificate that deep is that much harder to reorg away.
+	#[structopt(long, default_value = "0")]
+	pub min_confirmations: u32,
 }
 
 #[derive(Debug, StructOpt)]
@@ -15,7 +21,7 @@ pub enum Subcommand {
 	/// Key management cli utilities
 	Key(sc_cli::KeySubcommand),
 	/// Build a chain specification.
-	BuildSpec(sc_cli::BuildSpecCmd),
+	BuildSpec(BuildSpecCmd),
 
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
@@ -39,3 +45,14 @@ pub enum Subcommand {
 	#[structopt(name = "benchmark", about = "Benchmark runtime pallets.")]
 	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
 }
+
+#[derive(Debug, StructOpt)]
+pub struct BuildSpecCmd {
+	#[structopt(flatten)]
+	pub base: sc_cli::BuildSpecCmd,
+
+	/// A CSV or JSON list of certificates to register at genesis, with the columns domain, ip,
+	/// key and optionally owner, name and info. Only applies to the built-in dev and local chains.
+	#[structopt(long, parse(from_os_str))]
+	pub certificates: Option<PathBuf>,
+}
@@ -15,7 +15,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{chain_spec, service};
+use crate::{certificates, chain_spec, service};
 use crate::cli::{Cli, Subcommand};
 use sc_cli::{SubstrateCli, RuntimeVersion, Role, ChainSpec};
 use sc_service::PartialComponents;
@@ -47,12 +47,26 @@ impl SubstrateCli for Cli {
 	}
 
 	fn load_spec(&self, id: &str) -> Result<Box<dyn sc_service::ChainSpec>, String> {
+		let certificates_path = match &self.subcommand {
+			Some(Subcommand::BuildSpec(cmd)) => cmd.certificates.as_deref(),
+			_ => None,
+		};
+		let genesis_certificates = || match certificates_path {
+			Some(path) => certificates::load(path),
+			None => Ok(Vec::new()),
+		};
 		Ok(match id {
-			"dev" => Box::new(chain_spec::development_config()?),
-			"" | "local" => Box::new(chain_spec::local_testnet_config()?),
-			path => Box::new(chain_spec::ChainSpec::from_json_file(
-				std::path::PathBuf::from(path),
-			)?),
+			"dev" => Box::new(chain_spec::development_config(genesis_certificates()?)?),
+			"" | "local" => Box::new(chain_spec::local_testnet_config(genesis_certificates()?)?),
+			path => {
+				if certificates_path.is_some() {
+					return Err("--certificates only applies to the built-in dev and local chains; \
+						a chain spec file's genesis is already fixed".into());
+				}
+				Box::new(chain_spec::ChainSpec::from_json_file(
+					std::path::PathBuf::from(path),
+				)?)
+			},
 		})
 	}
 
@@ -68,8 +82,8 @@ pub fn run() -> sc_cli::Result<()> {
 	match &cli.subcommand {
 		Some(Subcommand::Key(cmd)) => cmd.run(&cli),
 		Some(Subcommand::BuildSpec(cmd)) => {
-			let runner = cli.create_runner(cmd)?;
-			runner.sync_run(|config| cmd.run(config.chain_spec, config.network))
+			let runner = cli.create_runner(&cmd.base)?;
+			runner.sync_run(|config| cmd.base.run(config.chain_spec, config.network))
 		},
 		Some(Subcommand::CheckBlock(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
@@ -127,10 +141,11 @@ pub fn run() -> sc_cli::Result<()> {
 		},
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			let min_confirmations = cli.min_confirmations;
 			runner.run_node_until_exit(|config| async move {
 				match config.role {
 					Role::Light => service::new_light(config),
-					_ => service::new_full(config),
+					_ => service::new_full(config, min_confirmations),
 				}.map_err(sc_cli::Error::Service)
 			})
 		}
@@ -5,18 +5,151 @@
 
 #![warn(missing_docs)]
 
+use std::marker::PhantomData;
 use std::sync::Arc;
 
-use siip_node_runtime::{opaque::Block, AccountId, Balance, Index};
+use futures::{StreamExt, TryStreamExt};
+use siip_node_runtime::{opaque::Block, AccountId, Balance, BlockNumber, Event, Index, Hash};
 use sp_api::ProvideRuntimeApi;
+use sp_core::Decode;
+use sp_core::crypto::Ss58Codec;
+use sp_core::hashing::twox_128;
+use sp_core::storage::StorageKey;
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::Header as _;
 use sp_blockchain::{Error as BlockChainError, HeaderMetadata, HeaderBackend};
 use sp_block_builder::BlockBuilder;
 pub use sc_rpc_api::DenyUnsafe;
+use sc_client_api::{BlockchainEvents, BlockImportNotification, StorageProvider};
+use sc_rpc::SubscriptionTaskExecutor;
 use sp_transaction_pool::TransactionPool;
+use jsonrpc_core::futures::{Future, Sink};
 use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, SubscriptionId};
 use sc_rpc_api::system::error::Result as SystemResult;
 use core::str::from_utf8;
-use siip_node_runtime::pallet_siip::{check_name, check_domain, check_ip, check_info, check_key};
+use siip_node_runtime::pallet_siip::{check_name, check_domain, check_ip, check_info, check_key, Certificate, CertificateVersion, KeyRotation, RawEvent};
+use pallet_siip_runtime_api::SiipApi;
+use siip_dane::{Matching, Tlsa};
+use serde::{Deserialize, Serialize};
+
+/// A certificate as returned by the lookup RPCs, with its fields as text and the TLSA records
+/// that pin its key.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateRecord {
+	/// The certificate format version.
+	pub version_number: i32,
+	/// The SS58 address of the owner.
+	pub owner_id: String,
+	/// The owner's name.
+	pub name: String,
+	/// The owner's JSON info.
+	pub info: String,
+	/// The public key, in colon-separated hexadecimal.
+	pub key: String,
+	/// The IPv4 address the domain points at.
+	pub ip_addr: String,
+	/// The domain the certificate is for.
+	pub domain: String,
+	/// The key that will replace `key`, if a rotation is pending. Both keys are valid until then.
+	pub next_key: Option<String>,
+	/// The block in which `next_key` replaces `key`.
+	pub key_activation: Option<BlockNumber>,
+	/// DANE-EE TLSA records for the key and any next key, in presentation format. Keys that
+	/// aren't well-formed SubjectPublicKeyInfos have none.
+	pub tlsa: Vec<String>,
+}
+
+impl CertificateRecord {
+	fn new(cert: Certificate<AccountId>, rotation: Option<KeyRotation<BlockNumber>>) -> Self {
+		let next_key = rotation.as_ref().map(|rotation| &rotation.next_key);
+		let tlsa = Some(&cert.key).into_iter().chain(next_key)
+			.flat_map(|key| vec![Matching::Sha256, Matching::Full].into_iter()
+				.filter_map(move |matching| Tlsa::from_key(key, matching).ok()))
+			.map(|tlsa| tlsa.to_string())
+			.collect();
+		CertificateRecord {
+			version_number: cert.version_number,
+			owner_id: cert.owner_id.to_ss58check(),
+			name: String::from_utf8_lossy(&cert.name).into(),
+			info: String::from_utf8_lossy(&cert.info).into(),
+			key: String::from_utf8_lossy(&cert.key).into(),
+			ip_addr: String::from_utf8_lossy(&cert.ip_addr).into(),
+			domain: String::from_utf8_lossy(&cert.domain).into(),
+			next_key: next_key.map(|key| String::from_utf8_lossy(key).into()),
+			key_activation: rotation.map(|rotation| rotation.activation),
+			tlsa,
+		}
+	}
+}
+
+/// One version of a domain's certificate, as returned by `certificate_history`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateVersionRecord {
+	/// The number of the block the change was made in.
+	pub block_number: BlockNumber,
+	/// The hash of that block on the chain that was read, if the node still knows it.
+	pub block_hash: Option<Hash>,
+	/// The index in that block of the extrinsic that made the change. Null for changes made
+	/// outside an extrinsic: at genesis, or by a scheduled key activation.
+	pub extrinsic_index: Option<u32>,
+	/// The certificate from this change on, or null if the change removed it.
+	pub certificate: Option<CertificateRecord>,
+}
+
+/// An answer, with the block it was read at and how deep that block is.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Confirmed<T> {
+	/// The number of the block the answer was read at.
+	pub block_number: BlockNumber,
+	/// The hash of the block the answer was read at.
+	pub block_hash: Hash,
+	/// How many blocks the best chain has on top of that block: 0 for the best block itself,
+	/// or for a block that isn't on the best chain.
+	pub confirmations: BlockNumber,
+	/// Whether that block is at or below the last checkpoint, so no reorg can change the answer.
+	pub finalized: bool,
+	/// The answer.
+	pub value: T,
+}
+
+/// What a change did to a domain's certificate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+	/// The certificate was registered, by its owner or by governance.
+	Register,
+	/// The certificate was modified by its owner, or a scheduled key replaced its key.
+	Modify,
+	/// The certificate was removed, by its owner or by governance.
+	Remove,
+	/// Governance gave the certificate to a new owner.
+	Transfer,
+}
+
+/// A change to a certificate on the best chain, as pushed to `siip_subscribeCertificate`
+/// subscribers.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateChange {
+	/// What the change did.
+	pub kind: ChangeKind,
+	/// The domain whose certificate changed.
+	pub domain: String,
+	/// The number of the block that made the change.
+	pub block_number: BlockNumber,
+	/// The hash of the block that made the change.
+	pub block_hash: Hash,
+	/// Whether a reorg took the block off the best chain, undoing the change. Retractions come
+	/// before the changes of the blocks that replace it.
+	pub retracted: bool,
+	/// The certificate after the change, or null if it removed it. For a retraction, the
+	/// certificate the domain now holds on the best chain.
+	pub certificate: Option<CertificateRecord>,
+}
 
 #[rpc]
 /// RPCs related to the Siip Pallet
@@ -45,24 +178,165 @@ pub trait SiipRpcTrait {
 	/// Validates the public key provided.
 	/// Returns multiple lines. Each line will contain Ok: message, or Err: message
 	fn validate_key(&self, key: String) -> SystemResult<String>;
+
+	#[rpc(name = "lookup_certificate")]
+	/// Returns the certificate registered for a domain, with any pending key rotation and TLSA
+	/// records for its keys.
+	/// Reads the given block, or else the block `min_confirmations` (by default, the node's
+	/// `--min-confirmations`) below the best, and fails if that block has fewer confirmations.
+	fn lookup_certificate(&self, domain: String, at: Option<Hash>, min_confirmations: Option<BlockNumber>)
+		-> jsonrpc_core::Result<Confirmed<Option<CertificateRecord>>>;
+
+	#[rpc(name = "reverse_lookup")]
+	/// Returns every certificate that points at an IPv4 address, like `lookup_certificate`.
+	/// Reads the same block as `lookup_certificate`.
+	fn reverse_lookup(&self, ip_addr: String, at: Option<Hash>, min_confirmations: Option<BlockNumber>)
+		-> jsonrpc_core::Result<Confirmed<Vec<CertificateRecord>>>;
+
+	#[rpc(name = "certificate_history")]
+	/// Returns the latest versions of a domain's certificate, oldest first, each with the block
+	/// and extrinsic that made it. Reads the same block as `lookup_certificate`.
+	fn certificate_history(&self, domain: String, at: Option<Hash>, min_confirmations: Option<BlockNumber>)
+		-> jsonrpc_core::Result<Confirmed<Vec<CertificateVersionRecord>>>;
+}
+
+#[rpc]
+/// Subscriptions to certificate changes, over WebSocket RPC.
+pub trait SiipSubscriptionTrait {
+	/// RPC metadata
+	type Metadata;
+
+	#[pubsub(subscription = "siip_certificate", subscribe, name = "siip_subscribeCertificate")]
+	/// Pushes a `CertificateChange` for every change to the domain's certificate on the best chain,
+	/// or to every certificate if no domain is given. A reorg retracts the changes it undoes.
+	fn subscribe_certificate(&self, metadata: Self::Metadata, subscriber: Subscriber<CertificateChange>, domain: Option<String>);
+
+	#[pubsub(subscription = "siip_certificate", unsubscribe, name = "siip_unsubscribeCertificate")]
+	/// Stops a `siip_subscribeCertificate` subscription.
+	fn unsubscribe_certificate(&self, metadata: Option<Self::Metadata>, id: SubscriptionId) -> jsonrpc_core::Result<bool>;
 }
 
 /// A completely useless struct
 pub struct SiipRpcStruct<C> {
-    client: Arc<C>
+    client: Arc<C>,
+    min_confirmations: BlockNumber,
 }
 
 /// No idea what this does
 impl<C> SiipRpcStruct<C> {
-	/// What does this do?
-    pub fn new(client: Arc<C>) -> Self {
+	/// Lookups read `min_confirmations` blocks below the best unless asked otherwise.
+    pub fn new(client: Arc<C>, min_confirmations: BlockNumber) -> Self {
         SiipRpcStruct {
-            client
+            client,
+            min_confirmations,
         }
     }
 }
 
-impl<C> SiipRpcTrait for SiipRpcStruct<C> where C: Send + Sync + 'static {
+impl<C> SiipRpcStruct<C> where C: HeaderBackend<Block> {
+	/// The block to answer at: `at` if given, or else the block `min_confirmations` (or the
+	/// node's default) below the best. Fails if it has fewer than `min_confirmations`.
+	fn block(&self, at: Option<Hash>, min_confirmations: Option<BlockNumber>) -> jsonrpc_core::Result<Confirmed<BlockId<Block>>> {
+		let info = self.client.info();
+		let best = info.best_number;
+		let (hash, number, min_confirmations) = match at {
+			Some(hash) => {
+				let number = self.client.number(hash).map_err(runtime_error)?
+					.ok_or_else(|| depth_error(format!("Block {:?} is unknown.", hash)))?;
+				(hash, number, min_confirmations.unwrap_or(0))
+			},
+			None => {
+				let min_confirmations = min_confirmations.unwrap_or(self.min_confirmations);
+				let number = best.checked_sub(min_confirmations).ok_or_else(|| depth_error(format!(
+					"The chain is only {} blocks long; no block has {} confirmations.", best, min_confirmations,
+				)))?;
+				let hash = self.client.hash(number).map_err(runtime_error)?
+					.ok_or_else(|| depth_error(format!("Block #{} is unknown.", number)))?;
+				(hash, number, min_confirmations)
+			},
+		};
+
+		let on_best_chain = self.client.hash(number).map_err(runtime_error)? == Some(hash);
+		let confirmations = if on_best_chain { best.saturating_sub(number) } else { 0 };
+		if confirmations < min_confirmations {
+			return Err(depth_error(format!(
+				"Block {:?} has {} confirmations, fewer than the {} required.", hash, confirmations, min_confirmations,
+			)));
+		}
+		let finalized = on_best_chain && number <= info.finalized_number;
+		Ok(Confirmed { block_number: number, block_hash: hash, confirmations, finalized, value: BlockId::hash(hash) })
+	}
+
+	/// The hash of block `target` on the chain that ends in block `number`, `hash`, which need
+	/// not be the best chain. `None` if the chain's headers down to `target` aren't known.
+	fn ancestor(&self, mut hash: Hash, mut number: BlockNumber, target: BlockNumber) -> jsonrpc_core::Result<Option<Hash>> {
+		if target > number {
+			return Ok(None);
+		}
+		while number > target {
+			hash = match self.client.header(BlockId::hash(hash)).map_err(runtime_error)? {
+				Some(header) => *header.parent_hash(),
+				None => return Ok(None),
+			};
+			number -= 1;
+		}
+		Ok(Some(hash))
+	}
+}
+
+impl<T> Confirmed<T> {
+	/// `value`, read at the same block.
+	fn answer<U>(&self, value: U) -> Confirmed<U> {
+		Confirmed {
+			block_number: self.block_number,
+			block_hash: self.block_hash,
+			confirmations: self.confirmations,
+			finalized: self.finalized,
+			value,
+		}
+	}
+}
+
+impl<C> SiipRpcStruct<C> where
+	C: ProvideRuntimeApi<Block>,
+	C::Api: SiipApi<Block, AccountId, BlockNumber>,
+{
+	fn record(&self, block: &BlockId<Block>, cert: Certificate<AccountId>) -> jsonrpc_core::Result<CertificateRecord> {
+		record(&*self.client, block, cert)
+	}
+}
+
+/// `cert` with its pending key rotation, as of `block`.
+fn record<C>(client: &C, block: &BlockId<Block>, cert: Certificate<AccountId>) -> jsonrpc_core::Result<CertificateRecord> where
+	C: ProvideRuntimeApi<Block>,
+	C::Api: SiipApi<Block, AccountId, BlockNumber>,
+{
+	let rotation = client.runtime_api()
+		.key_rotation(block, cert.domain.clone())
+		.map_err(runtime_error)?;
+	Ok(CertificateRecord::new(cert, rotation))
+}
+
+fn runtime_error(e: impl std::fmt::Debug) -> jsonrpc_core::Error {
+	jsonrpc_core::Error {
+		code: jsonrpc_core::ErrorCode::ServerError(1),
+		message: "Unable to query certificates.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+fn depth_error(message: String) -> jsonrpc_core::Error {
+	jsonrpc_core::Error {
+		code: jsonrpc_core::ErrorCode::ServerError(2),
+		message,
+		data: None,
+	}
+}
+
+impl<C> SiipRpcTrait for SiipRpcStruct<C> where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: SiipApi<Block, AccountId, BlockNumber>,
+{
 	//Contains 'Err:' if invalid
 	fn validate_name(&self, name: String) -> SystemResult<String> {
 		let criteria = check_name(&name.into_bytes());
@@ -84,6 +358,191 @@ impl<C> SiipRpcTrait for SiipRpcStruct<C> where C: Send + Sync + 'static {
 		let criteria = check_key(&key.into_bytes());
 		Ok(from_utf8(&criteria).unwrap().into())
 	}
+	fn lookup_certificate(&self, domain: String, at: Option<Hash>, min_confirmations: Option<BlockNumber>)
+		-> jsonrpc_core::Result<Confirmed<Option<CertificateRecord>>>
+	{
+		let block = self.block(at, min_confirmations)?;
+		let cert = self.client.runtime_api()
+			.certificate(&block.value, domain.into_bytes())
+			.map_err(runtime_error)?;
+		Ok(block.answer(cert.map(|cert| self.record(&block.value, cert)).transpose()?))
+	}
+	fn reverse_lookup(&self, ip_addr: String, at: Option<Hash>, min_confirmations: Option<BlockNumber>)
+		-> jsonrpc_core::Result<Confirmed<Vec<CertificateRecord>>>
+	{
+		let block = self.block(at, min_confirmations)?;
+		let certs = self.client.runtime_api()
+			.certificates_by_ip(&block.value, ip_addr.into_bytes())
+			.map_err(runtime_error)?;
+		Ok(block.answer(certs.into_iter().map(|cert| self.record(&block.value, cert)).collect::<Result<_, _>>()?))
+	}
+	fn certificate_history(&self, domain: String, at: Option<Hash>, min_confirmations: Option<BlockNumber>)
+		-> jsonrpc_core::Result<Confirmed<Vec<CertificateVersionRecord>>>
+	{
+		let block = self.block(at, min_confirmations)?;
+		let history = self.client.runtime_api()
+			.certificate_history(&block.value, domain.into_bytes())
+			.map_err(runtime_error)?;
+		//Each version's block is an ancestor of the one answered at, so walk down its chain
+		//from the newest version, rather than reading the best chain's hashes
+		let mut tip = (block.block_hash, block.block_number);
+		let mut versions = history.into_iter().rev()
+			.map(|version: CertificateVersion<AccountId, BlockNumber>| {
+				let block_hash = self.ancestor(tip.0, tip.1, version.block_number)?;
+				if let Some(hash) = block_hash {
+					tip = (hash, version.block_number);
+				}
+				Ok(CertificateVersionRecord {
+					block_number: version.block_number,
+					block_hash,
+					extrinsic_index: version.extrinsic_index,
+					certificate: version.certificate.map(|cert| CertificateRecord::new(cert, None)),
+				})
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		versions.reverse();
+		Ok(block.answer(versions))
+	}
+}
+
+/// Serves `siip_subscribeCertificate`, reading each new best block's events for certificate changes.
+pub struct SiipSubscriptions<C, B> {
+	client: Arc<C>,
+	subscriptions: SubscriptionManager,
+	_backend: PhantomData<B>,
+}
+
+impl<C, B> SiipSubscriptions<C, B> {
+	/// Runs each subscription on `executor`.
+	pub fn new(client: Arc<C>, executor: SubscriptionTaskExecutor) -> Self {
+		SiipSubscriptions {
+			client,
+			subscriptions: SubscriptionManager::new(Arc::new(executor)),
+			_backend: PhantomData,
+		}
+	}
+}
+
+/// The changes the pallet's events record in `block`, with the certificate each left behind.
+fn block_changes<C, B>(client: &C, block: Hash) -> Result<Vec<(ChangeKind, Certificate<AccountId>)>, String> where
+	C: StorageProvider<Block, B>,
+	B: sc_client_api::Backend<Block>,
+{
+	let mut key = twox_128(b"System").to_vec();
+	key.extend_from_slice(&twox_128(b"Events"));
+	let events = client.storage(&BlockId::hash(block), &StorageKey(key))
+		.map_err(|e| e.to_string())?;
+	let records: Vec<frame_system::EventRecord<Event, Hash>> = match events {
+		Some(events) => Decode::decode(&mut &events.0[..]).map_err(|e| e.to_string())?,
+		None => Vec::new(),
+	};
+	Ok(records.into_iter()
+		.filter_map(|record| match record.event {
+			Event::pallet_siip(event) => Some(event),
+			_ => None,
+		})
+		.filter_map(|event| match event {
+			RawEvent::CertificateRegistered(cert, _) | RawEvent::CertificateForceRegistered(cert) =>
+				Some((ChangeKind::Register, cert)),
+			RawEvent::CertificateModified(cert, _, _) | RawEvent::KeyRotated(cert, _) =>
+				Some((ChangeKind::Modify, cert)),
+			RawEvent::CertificateRemoved(cert, _) | RawEvent::CertificateForceRemoved(cert) =>
+				Some((ChangeKind::Remove, cert)),
+			RawEvent::CertificateForceTransferred(cert, _) =>
+				Some((ChangeKind::Transfer, cert)),
+			_ => None,
+		})
+		.collect())
+}
+
+/// The certificate changes a new best block brings about, for `domain` or every domain: the
+/// retractions of the blocks it displaced, then the changes of the blocks it enacted, in order.
+fn best_chain_changes<C, B>(
+	client: &C,
+	notification: &BlockImportNotification<Block>,
+	domain: Option<&[u8]>,
+) -> Result<Vec<CertificateChange>, String> where
+	C: ProvideRuntimeApi<Block> + StorageProvider<Block, B>,
+	C::Api: SiipApi<Block, AccountId, BlockNumber>,
+	B: sc_client_api::Backend<Block>,
+{
+	let best = BlockId::hash(notification.hash);
+	let (retracted, enacted) = match &notification.tree_route {
+		Some(route) => (route.retracted().to_vec(), route.enacted().to_vec()),
+		None => (Vec::new(), Vec::new()),
+	};
+	let enacted = enacted.into_iter()
+		.map(|block| (block.hash, block.number))
+		.chain(Some((notification.hash, *notification.header.number())));
+	let wanted = |cert: &Certificate<AccountId>| domain.is_none() || domain == Some(&cert.domain[..]);
+
+	let mut changes = Vec::new();
+	for block in retracted {
+		for (kind, cert) in block_changes(client, block.hash)?.into_iter().filter(|(_, cert)| wanted(cert)) {
+			let current = client.runtime_api()
+				.certificate(&best, cert.domain.clone())
+				.map_err(|e| format!("{:?}", e))?
+				.map(|current| record(client, &best, current))
+				.transpose()
+				.map_err(|e| e.message)?;
+			changes.push(CertificateChange {
+				kind,
+				domain: String::from_utf8_lossy(&cert.domain).into(),
+				block_number: block.number,
+				block_hash: block.hash,
+				retracted: true,
+				certificate: current,
+			});
+		}
+	}
+	for (hash, number) in enacted {
+		for (kind, cert) in block_changes(client, hash)?.into_iter().filter(|(_, cert)| wanted(cert)) {
+			let domain = String::from_utf8_lossy(&cert.domain).into();
+			let certificate = match kind {
+				ChangeKind::Remove => None,
+				_ => Some(record(client, &BlockId::hash(hash), cert).map_err(|e| e.message)?),
+			};
+			changes.push(CertificateChange { kind, domain, block_number: number, block_hash: hash, retracted: false, certificate });
+		}
+	}
+	Ok(changes)
+}
+
+impl<C, B> SiipSubscriptionTrait for SiipSubscriptions<C, B> where
+	C: ProvideRuntimeApi<Block> + StorageProvider<Block, B> + BlockchainEvents<Block> + Send + Sync + 'static,
+	C::Api: SiipApi<Block, AccountId, BlockNumber>,
+	B: sc_client_api::Backend<Block> + Send + Sync + 'static,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn subscribe_certificate(&self, _metadata: Self::Metadata, subscriber: Subscriber<CertificateChange>, domain: Option<String>) {
+		let client = self.client.clone();
+		let domain = domain.map(String::into_bytes);
+		let changes = self.client.import_notification_stream()
+			.filter(|notification| futures::future::ready(notification.is_new_best))
+			.flat_map(move |notification| {
+				let changes = best_chain_changes(&*client, &notification, domain.as_deref())
+					.unwrap_or_else(|e| {
+						log::warn!("Unable to read certificate changes in block {}: {}", notification.hash, e);
+						Vec::new()
+					});
+				futures::stream::iter(changes)
+			})
+			.map(|change| Ok::<_, ()>(Ok(change)))
+			.boxed()
+			.compat();
+
+		self.subscriptions.add(subscriber, |sink| {
+			sink.sink_map_err(|e| log::warn!("Error sending certificate changes: {:?}", e))
+				.send_all(changes)
+				// The stream only ends when the client does
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_certificate(&self, _metadata: Option<Self::Metadata>, id: SubscriptionId) -> jsonrpc_core::Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
 }
 
 /// Full client dependencies.
@@ -94,19 +553,25 @@ pub struct FullDeps<C, P> {
 	pub pool: Arc<P>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// Executor for subscription tasks.
+	pub subscription_executor: SubscriptionTaskExecutor,
+	/// How far below the best block lookups read by default.
+	pub min_confirmations: BlockNumber,
 }
 
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(
+pub fn create_full<C, P, B>(
 	deps: FullDeps<C, P>,
 ) -> jsonrpc_core::IoHandler<sc_rpc::Metadata> where
-	C: ProvideRuntimeApi<Block>,
+	C: ProvideRuntimeApi<Block> + StorageProvider<Block, B> + BlockchainEvents<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error=BlockChainError> + 'static,
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: SiipApi<Block, AccountId, BlockNumber>,
 	P: TransactionPool + 'static,
+	B: sc_client_api::Backend<Block> + Send + Sync + 'static,
 {
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
@@ -116,6 +581,8 @@ pub fn create_full<C, P>(
 		client,
 		pool,
 		deny_unsafe,
+		subscription_executor,
+		min_confirmations,
 	} = deps;
 
 	io.extend_with(
@@ -127,9 +594,13 @@ pub fn create_full<C, P>(
 	);
 
     io.extend_with(
-        SiipRpcTrait::to_delegate(SiipRpcStruct::new(client.clone()))
+        SiipRpcTrait::to_delegate(SiipRpcStruct::new(client.clone(), min_confirmations))
     );
 
+	io.extend_with(
+		SiipSubscriptionTrait::to_delegate(SiipSubscriptions::new(client.clone(), subscription_executor))
+	);
+
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
 	// to call into the runtime.
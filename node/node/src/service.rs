@@ -1,7 +1,6 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
 use std::sync::Arc;
-use std::time::Duration;
 use std::thread;
 use sc_client_api::ExecutorProvider;
 use siip_node_runtime::{self, opaque::Block, RuntimeApi};
@@ -13,9 +12,10 @@ use sp_core::sr25519;
 
 use crate::chain_spec;
 
+mod checkpoint;
 mod miner;
 
-use sha3pow::MinimalSha3Algorithm;
+use sha3pow::ScheduledAlgorithm;
 
 // Our native executor instance.
 native_executor_instance!(
@@ -39,9 +39,10 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 			Arc<FullClient>,
 			FullClient,
 			FullSelectChain,
-			MinimalSha3Algorithm,
+			ScheduledAlgorithm<FullClient>,
 			impl sp_consensus::CanAuthorWith<Block>
 		>,
+		ScheduledAlgorithm<FullClient>,
 	)
 >, ServiceError> {
 	if config.keystore_remote.is_some() {
@@ -49,6 +50,8 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 			format!("Remote Keystores are not supported.")))
 	}
 	let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+	let params = chain_spec::consensus_params(&config.chain_spec.properties())
+		.map_err(ServiceError::Other)?;
 
 	let (client, backend, keystore_container, task_manager) =
 		sc_service::new_full_parts::<Block, RuntimeApi, Executor>(&config)?;
@@ -66,10 +69,13 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 
 	let can_author_with = sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
 
+	// Sha3, then the memory-hard hash from the height the chain spec names
+	let algorithm = ScheduledAlgorithm::new(client.clone(), params.schedule, params.difficulty);
+
 	let pow_block_import = sc_consensus_pow::PowBlockImport::new(
 		client.clone(),
 		client.clone(),
-		MinimalSha3Algorithm,
+		algorithm.clone(),
 		0, // check inherents starting at block 0
 		select_chain.clone(),
 		inherent_data_providers.clone(),
@@ -79,7 +85,7 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 	let import_queue = sc_consensus_pow::import_queue(
 		Box::new(pow_block_import.clone()),
 		None,
-		MinimalSha3Algorithm,
+		algorithm.clone(),
 		inherent_data_providers.clone(),
 		&task_manager.spawn_handle(),
 		config.prometheus_registry()
@@ -88,7 +94,7 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 	Ok(sc_service::PartialComponents {
 		client, backend, task_manager, import_queue, keystore_container, select_chain, transaction_pool,
 		inherent_data_providers,
-		other: (pow_block_import,),
+		other: (pow_block_import, algorithm),
 	})
 }
 
@@ -99,13 +105,16 @@ fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
 	Err("Remote Keystore not supported.")
 }
 
-/// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
+/// Builds a new service for a full client. Its lookup RPCs read `min_confirmations` blocks
+/// below the best by default.
+pub fn new_full(config: Configuration, min_confirmations: u32) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client, backend, mut task_manager, import_queue, mut keystore_container, select_chain, transaction_pool,
 		inherent_data_providers,
-		other: (block_import, ),
+		other: (block_import, algorithm),
 	} = new_partial(&config)?;
+	let params = chain_spec::consensus_params(&config.chain_spec.properties())
+		.map_err(ServiceError::Other)?;
 
 	if let Some(url) = &config.keystore_remote {
 		match remote_keystore(url) {
@@ -143,11 +152,13 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
 
-		Box::new(move |deny_unsafe, _| {
+		Box::new(move |deny_unsafe, subscription_executor| {
 			let deps = crate::rpc::FullDeps {
 				client: client.clone(),
 				pool: pool.clone(),
 				deny_unsafe,
+				subscription_executor,
+				min_confirmations,
 			};
 
 			crate::rpc::create_full(deps)
@@ -171,6 +182,8 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		},
 	)?;
 
+	task_manager.spawn_handle().spawn("checkpoint-finality", checkpoint::finalize_checkpoints(client.clone()));
+
 	if role.is_authority() {
 		let proposer = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
@@ -197,19 +210,19 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			Box::new(block_import),
 			client,
 			select_chain,
-			MinimalSha3Algorithm,
+			algorithm.clone(),
 			proposer,
 			network.clone(),
 			Some(public_key.to_vec()),
 			inherent_data_providers,
 			// time to wait for a new block before starting to mine a new one
-			Duration::from_secs(10),
+			params.target_block_time,
 			// how long to take to actually build the block (i.e. executing extrinsics)
-			Duration::from_secs(10),
+			params.propose_timeout,
 			can_author_with);
 
 		thread::spawn(move || {
-			miner::do_mining(worker_data, MinimalSha3Algorithm);
+			miner::do_mining(worker_data, algorithm, params.mining_rounds);
 		});
 
 		task_manager.spawn_essential_handle().spawn_blocking("pow", worker_task);
@@ -0,0 +1,41 @@
+//! Finalizes each checkpoint the chain's signers agree on, so that no reorg can undo it.
+
+use std::sync::Arc;
+use futures::StreamExt;
+use log::{info, warn};
+use sc_client_api::{Backend, BlockchainEvents, Finalizer};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::generic::BlockId;
+use siip_node_runtime::{opaque::Block, BlockNumber, Hash};
+use pallet_checkpoint_runtime_api::CheckpointApi;
+
+/// Watches for new best blocks, and finalizes the last checkpoint each one records if it is
+/// above the last finalized block. Runs until the client stops importing blocks.
+pub async fn finalize_checkpoints<B, C>(client: Arc<C>) where
+	B: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + Finalizer<Block, B> + HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: CheckpointApi<Block, BlockNumber, Hash>,
+{
+	let mut imports = client.import_notification_stream();
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+		let checkpoint = match client.runtime_api().last_checkpoint(&BlockId::hash(notification.hash)) {
+			Ok(Some(checkpoint)) => checkpoint,
+			Ok(None) => continue,
+			Err(e) => {
+				warn!("Unable to read the last checkpoint at {:?}: {:?}", notification.hash, e);
+				continue;
+			},
+		};
+		if checkpoint.number <= client.info().finalized_number {
+			continue;
+		}
+		match client.finalize_block(BlockId::hash(checkpoint.hash), None, true) {
+			Ok(()) => info!("🔒 Finalized checkpoint #{} ({:?})", checkpoint.number, checkpoint.hash),
+			Err(e) => warn!("Unable to finalize checkpoint #{} ({:?}): {:?}", checkpoint.number, checkpoint.hash, e),
+		}
+	}
+}
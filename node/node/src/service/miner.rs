@@ -1,5 +1,5 @@
 
-use std::{sync::Arc, time::Duration};
+use std::{sync::Arc, time::{Duration, Instant}};
 use std::thread;
 use parking_lot::Mutex;
 use log::{info, warn};
@@ -8,11 +8,15 @@ use sp_core::H256;
 use sp_runtime::generic::BlockId;
 use sc_consensus_pow::MiningWorker;
 
-use sha3pow;
+use sha3pow::{self, NonceCursor};
+
+// How often the miner reports its hashrate
+const HASHRATE_INTERVAL: Duration = Duration::from_secs(60);
 
 pub fn do_mining<Block, Algorithm, C>(
     worker_data: Arc<Mutex<MiningWorker<Block, Algorithm, C>>>,
-    algorithm: Algorithm
+    algorithm: Algorithm,
+    rounds: u32
 ) -> ()
 where
     Block: BlockT<Hash=H256>,
@@ -21,6 +25,10 @@ where
     Algorithm::Difficulty: 'static,
 {
     info!("⛏️  Started miner thread");
+    // The block being mined, and how far the search for its nonce has got
+    let mut search: Option<(H256, NonceCursor)> = None;
+    let mut hashes = 0;
+    let mut since = Instant::now();
     loop {
         let mut block_ready = false;
 
@@ -28,14 +36,22 @@ where
             let mut worker_data = worker_data.lock();
             if let Some(metadata) = worker_data.metadata() {
                 block_ready = true;
+                // A new block starts its search from a random nonce; the same block resumes its search
+                let cursor = match search {
+                    Some((pre_hash, cursor)) if pre_hash == metadata.pre_hash => cursor,
+                    _ => NonceCursor::seeded(rand::random(), 0, 1),
+                };
                 match algorithm.mine(
                         &BlockId::hash(metadata.best_hash),
                         &metadata.pre_hash,
                         metadata.pre_runtime.as_ref().map(|v| &v[..]),
                         metadata.difficulty,
-                        1000/*rounds before giving up*/) {
-                    Ok(r) => {
-                        if let Some(seal) = r {
+                        cursor,
+                        rounds/*before giving up*/) {
+                    Ok(mined) => {
+                        hashes += mined.hashes;
+                        search = Some((metadata.pre_hash, mined.cursor));
+                        if let Some(seal) = mined.seal {
                             // Successfully mined block! Now submit it to the chain so it can be imported
                             worker_data.submit(seal);
                         }
@@ -47,6 +63,12 @@ where
             }
         }
         
+        if since.elapsed() >= HASHRATE_INTERVAL {
+            info!("⛏️  Mining at {:.1} H/s", hashes as f64 / since.elapsed().as_secs_f64());
+            hashes = 0;
+            since = Instant::now();
+        }
+
         if !block_ready {
             // no mining build ready - just be patient
             thread::sleep(Duration::from_millis(100));
@@ -0,0 +1,14 @@
+//! Runtime API for reading the latest checkpoint, so that the node can finalize it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+
+pub use pallet_checkpoint::Checkpoint;
+
+sp_api::decl_runtime_apis! {
+	pub trait CheckpointApi<BlockNumber, Hash> where BlockNumber: Codec, Hash: Codec {
+		/// The latest block the checkpoint signers agreed on, if they have agreed on any.
+		fn last_checkpoint() -> Option<Checkpoint<BlockNumber, Hash>>;
+	}
+}
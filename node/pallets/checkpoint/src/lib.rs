@@ -0,0 +1,163 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Deterministic finality for a proof-of-work chain. A signer set, elected by governance, signs
+//! recent blocks with `sign_checkpoint`. Once a majority of the signers has signed the same
+//! block, it becomes the last checkpoint, and the node finalizes it: no reorg can undo it, nor
+//! any certificate change before it.
+
+use frame_support::{decl_module, decl_storage, decl_event, decl_error, ensure};
+use frame_support::codec::{Encode, Decode};
+use frame_support::storage::{IterableStorageMap, StoragePrefixedMap};
+use frame_support::traits::{Get, EnsureOrigin};
+use frame_support::weights::Pays;
+use frame_support::dispatch::{DispatchResult, DispatchResultWithPostInfo};
+use frame_system::ensure_signed;
+use sp_std::prelude::*;
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
+
+/// Configure the pallet by specifying the parameters and types on which it depends.
+pub trait Config: frame_system::Config {
+	/// Because this pallet emits events, it depends on the runtime's definition of an event.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// The origin that may replace the signer set, e.g. a referendum.
+	type SignerOrigin: EnsureOrigin<Self::Origin>;
+	/// The most signers the set may hold.
+	type MaxSigners: Get<u32>;
+}
+
+/// A block, by number and hash.
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Checkpoint<BlockNumber, Hash> {
+	pub number: BlockNumber,
+	pub hash: Hash,
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as CheckpointModule {
+		/// The accounts that sign checkpoints. With none, nothing is ever finalized.
+		pub Signers get(fn signers) config(): Vec<T::AccountId>;
+		/// How many signers must sign the same block to make it a checkpoint. Always a majority.
+		pub Threshold get(fn threshold) config(): u32;
+		/// The latest block the signers agreed on.
+		pub LastCheckpoint get(fn last_checkpoint): Option<Checkpoint<T::BlockNumber, T::Hash>>;
+		/// The block each signer last signed, until a checkpoint at or above it is reached.
+		pub Votes get(fn vote): map hasher(twox_64_concat) T::AccountId => Option<Checkpoint<T::BlockNumber, T::Hash>>;
+	}
+	add_extra_genesis {
+		build(|config: &GenesisConfig<T>| {
+			if let Err(e) = Module::<T>::validate_signers(&config.signers, config.threshold) {
+				panic!("Genesis checkpoint signers are invalid: {:?}", e);
+			}
+		});
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		BlockNumber = <T as frame_system::Config>::BlockNumber,
+		Hash = <T as frame_system::Config>::Hash,
+	{
+		/// A signer signed a block. Returns: [signer, block number, block hash]
+		CheckpointSigned(AccountId, BlockNumber, Hash),
+		/// A majority of the signers signed a block, which is now final. Returns: [block number, block hash]
+		CheckpointReached(BlockNumber, Hash),
+		/// Governance replaced the signer set. Returns: [signers, threshold]
+		SignersChanged(Vec<AccountId>, u32),
+	}
+);
+
+decl_error! {
+	pub enum Error for Module<T: Config> {
+		/// Only members of the signer set may sign checkpoints.
+		NotASigner,
+		/// The block is at or below the last checkpoint.
+		AlreadyFinal,
+		/// The block isn't a recent ancestor of this one: unknown, on another fork, or too old.
+		NotInChain,
+		/// The threshold must be a majority of the signers, or zero with no signers.
+		InvalidThreshold,
+		/// There are more than `MaxSigners` signers.
+		TooManySigners,
+		/// An account is listed as a signer more than once.
+		DuplicateSigner,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Signs the block `number` of this chain, whose hash must be `hash`. Signing is free for
+		/// signers; a signer's later signature replaces its earlier one.
+		#[weight = 1_000_000]
+		pub fn sign_checkpoint(origin, number: T::BlockNumber, hash: T::Hash) -> DispatchResultWithPostInfo {
+			let signer = ensure_signed(origin)?;
+			ensure!(Self::signers().contains(&signer), Error::<T>::NotASigner);
+			if let Some(last) = Self::last_checkpoint() {
+				ensure!(number > last.number, Error::<T>::AlreadyFinal);
+			}
+			//Only past blocks of this fork have their hashes recorded
+			ensure!(number < <frame_system::Module<T>>::block_number(), Error::<T>::NotInChain);
+			ensure!(<frame_system::Module<T>>::block_hash(number) == hash, Error::<T>::NotInChain);
+
+			let checkpoint = Checkpoint { number, hash };
+			Votes::<T>::insert(&signer, checkpoint);
+			Self::deposit_event(RawEvent::CheckpointSigned(signer, number, hash));
+
+			let signatures = Votes::<T>::iter().filter(|(_, vote)| *vote == checkpoint).count();
+			if signatures as u32 >= Self::threshold() {
+				Self::reach(checkpoint);
+			}
+			Ok(Pays::No.into())
+		}
+
+		/// Replaces the signer set, and discards the signatures of the old one.
+		#[weight = 1_000_000]
+		pub fn set_signers(origin, signers: Vec<T::AccountId>, threshold: u32) -> DispatchResult {
+			T::SignerOrigin::ensure_origin(origin)?;
+			Self::validate_signers(&signers, threshold)?;
+
+			Votes::<T>::remove_all();
+			Signers::<T>::put(&signers);
+			Threshold::put(threshold);
+			Self::deposit_event(RawEvent::SignersChanged(signers, threshold));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Checks that `threshold` of `signers` is a majority, so two forks can't both reach a
+	/// checkpoint unless a signer signs both.
+	pub fn validate_signers(signers: &[T::AccountId], threshold: u32) -> DispatchResult {
+		ensure!(signers.len() as u32 <= T::MaxSigners::get(), Error::<T>::TooManySigners);
+		for (i, signer) in signers.iter().enumerate() {
+			ensure!(!signers[..i].contains(signer), Error::<T>::DuplicateSigner);
+		}
+		let majority = signers.len() as u32 / 2 + 1;
+		let valid = if signers.is_empty() { threshold == 0 } else { threshold >= majority && threshold <= signers.len() as u32 };
+		ensure!(valid, Error::<T>::InvalidThreshold);
+		Ok(())
+	}
+
+	/// Makes `checkpoint` the last checkpoint, and drops the signatures it supersedes.
+	fn reach(checkpoint: Checkpoint<T::BlockNumber, T::Hash>) {
+		LastCheckpoint::<T>::put(checkpoint);
+		let stale: Vec<T::AccountId> = Votes::<T>::iter()
+			.filter(|(_, vote)| vote.number <= checkpoint.number)
+			.map(|(signer, _)| signer)
+			.collect();
+		for signer in stale {
+			Votes::<T>::remove(signer);
+		}
+		Self::deposit_event(RawEvent::CheckpointReached(checkpoint.number, checkpoint.hash));
+	}
+}
@@ -0,0 +1,89 @@
+use crate as pallet_checkpoint;
+use sp_core::H256;
+use frame_support::{parameter_types, storage::StorageMap};
+use sp_runtime::{
+	traits::{BlakeTwo256, IdentityLookup}, testing::Header,
+};
+use frame_system as system;
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+// Configure a mock runtime to test the pallet.
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Module, Call, Config, Storage, Event<T>},
+		CheckpointModule: pallet_checkpoint::{Module, Call, Storage, Config<T>, Event<T>},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const SS58Prefix: u8 = 74;
+}
+
+impl system::Config for Test {
+	type BaseCallFilter = ();
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = ();
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = SS58Prefix;
+}
+
+parameter_types! {
+	pub const MaxSigners: u32 = 4;
+}
+
+impl pallet_checkpoint::Config for Test {
+	type Event = Event;
+	type SignerOrigin = frame_system::EnsureRoot<u64>;
+	type MaxSigners = MaxSigners;
+}
+
+// Build genesis storage according to the mock runtime. Accounts 1, 2 and 3 sign checkpoints,
+// and any two of them make one.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_checkpoint::GenesisConfig::<Test> {
+		signers: vec![1, 2, 3],
+		threshold: 2,
+	}.assimilate_storage(&mut storage).unwrap();
+	let mut ext: sp_io::TestExternalities = storage.into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+// The hash the mock gives block `n`.
+pub fn block_hash(n: u64) -> H256 {
+	H256::repeat_byte(n as u8)
+}
+
+// Moves the chain forward to block `n`, recording each block's hash the way `frame_system` does.
+pub fn run_to_block(n: u64) {
+	while System::block_number() < n {
+		let number = System::block_number();
+		frame_system::BlockHash::<Test>::insert(number, block_hash(number));
+		System::set_block_number(number + 1);
+	}
+}
@@ -0,0 +1,87 @@
+use crate::{Error, Checkpoint, mock::*};
+use frame_support::{assert_ok, assert_noop};
+use frame_support::weights::Pays;
+use sp_core::H256;
+use sp_runtime::DispatchError;
+
+#[test]
+fn majority_reaches_checkpoint() {
+	new_test_ext().execute_with(|| {
+		run_to_block(10);
+		let post_info = CheckpointModule::sign_checkpoint(Origin::signed(1), 5, block_hash(5)).unwrap();
+		assert_eq!(post_info.pays_fee, Pays::No);
+		assert_eq!(CheckpointModule::last_checkpoint(), None);
+		assert_eq!(CheckpointModule::vote(1), Some(Checkpoint { number: 5, hash: block_hash(5) }));
+
+		//A different block doesn't count towards the first
+		assert_ok!(CheckpointModule::sign_checkpoint(Origin::signed(2), 6, block_hash(6)));
+		assert_eq!(CheckpointModule::last_checkpoint(), None);
+
+		assert_ok!(CheckpointModule::sign_checkpoint(Origin::signed(3), 6, block_hash(6)));
+		assert_eq!(CheckpointModule::last_checkpoint(), Some(Checkpoint { number: 6, hash: block_hash(6) }));
+		//Signatures at or below the checkpoint are spent
+		assert_eq!(CheckpointModule::vote(1), None);
+		assert_eq!(CheckpointModule::vote(2), None);
+	});
+}
+
+#[test]
+fn sign_checkpoint_errors() {
+	new_test_ext().execute_with(|| {
+		run_to_block(10);
+		assert_noop!(
+			CheckpointModule::sign_checkpoint(Origin::signed(4), 5, block_hash(5)),
+			Error::<Test>::NotASigner
+		);
+		//On another fork
+		assert_noop!(
+			CheckpointModule::sign_checkpoint(Origin::signed(1), 5, H256::repeat_byte(0xff)),
+			Error::<Test>::NotInChain
+		);
+		//The current block's hash isn't known yet
+		assert_noop!(
+			CheckpointModule::sign_checkpoint(Origin::signed(1), 10, block_hash(10)),
+			Error::<Test>::NotInChain
+		);
+
+		assert_ok!(CheckpointModule::sign_checkpoint(Origin::signed(1), 5, block_hash(5)));
+		assert_ok!(CheckpointModule::sign_checkpoint(Origin::signed(2), 5, block_hash(5)));
+		assert_noop!(
+			CheckpointModule::sign_checkpoint(Origin::signed(3), 5, block_hash(5)),
+			Error::<Test>::AlreadyFinal
+		);
+		assert_noop!(
+			CheckpointModule::sign_checkpoint(Origin::signed(3), 4, block_hash(4)),
+			Error::<Test>::AlreadyFinal
+		);
+	});
+}
+
+#[test]
+fn set_signers() {
+	new_test_ext().execute_with(|| {
+		run_to_block(10);
+		assert_ok!(CheckpointModule::sign_checkpoint(Origin::signed(1), 5, block_hash(5)));
+
+		assert_noop!(CheckpointModule::set_signers(Origin::signed(1), vec![1], 1), DispatchError::BadOrigin);
+		assert_noop!(CheckpointModule::set_signers(Origin::root(), vec![1, 2, 3, 4], 2), Error::<Test>::InvalidThreshold);
+		assert_noop!(CheckpointModule::set_signers(Origin::root(), vec![1, 2], 3), Error::<Test>::InvalidThreshold);
+		assert_noop!(CheckpointModule::set_signers(Origin::root(), vec![], 1), Error::<Test>::InvalidThreshold);
+		assert_noop!(CheckpointModule::set_signers(Origin::root(), vec![1, 2, 1], 2), Error::<Test>::DuplicateSigner);
+		assert_noop!(CheckpointModule::set_signers(Origin::root(), vec![1, 2, 3, 4, 5], 3), Error::<Test>::TooManySigners);
+
+		assert_ok!(CheckpointModule::set_signers(Origin::root(), vec![4], 1));
+		assert_eq!((CheckpointModule::signers(), CheckpointModule::threshold()), (vec![4], 1));
+		//The old set's signatures are gone
+		assert_eq!(CheckpointModule::vote(1), None);
+		assert_noop!(
+			CheckpointModule::sign_checkpoint(Origin::signed(1), 5, block_hash(5)),
+			Error::<Test>::NotASigner
+		);
+		assert_ok!(CheckpointModule::sign_checkpoint(Origin::signed(4), 5, block_hash(5)));
+		assert_eq!(CheckpointModule::last_checkpoint(), Some(Checkpoint { number: 5, hash: block_hash(5) }));
+
+		//No signers turns finality off
+		assert_ok!(CheckpointModule::set_signers(Origin::root(), vec![], 0));
+	});
+}
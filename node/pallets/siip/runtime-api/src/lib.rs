@@ -0,0 +1,22 @@
+//! Runtime API for looking up SIIP certificates, so that RPCs can answer from the runtime
+//! rather than from raw storage.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::prelude::*;
+
+pub use pallet_siip::{Certificate, CertificateVersion, KeyRotation};
+
+sp_api::decl_runtime_apis! {
+	pub trait SiipApi<AccountId, BlockNumber> where AccountId: Codec, BlockNumber: Codec {
+		/// The certificate registered for `domain`, if there is one.
+		fn certificate(domain: Vec<u8>) -> Option<Certificate<AccountId>>;
+		/// Every certificate that points at `ip_addr`.
+		fn certificates_by_ip(ip_addr: Vec<u8>) -> Vec<Certificate<AccountId>>;
+		/// The key `domain` is rotating to, if a rotation is pending.
+		fn key_rotation(domain: Vec<u8>) -> Option<KeyRotation<BlockNumber>>;
+		/// The latest versions of `domain`'s certificate, oldest first.
+		fn certificate_history(domain: Vec<u8>) -> Vec<CertificateVersion<AccountId, BlockNumber>>;
+	}
+}
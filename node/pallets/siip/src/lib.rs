@@ -1,12 +1,19 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
 /// Edit this file to define custom logic or remove it if it is not needed.
 /// Learn more about FRAME and the core library of Substrate FRAME pallets:
 /// https://substrate.dev/docs/en/knowledgebase/runtime/frame
 
-use frame_support::{decl_module, decl_storage, decl_event, decl_error, ensure, dispatch};
+use frame_support::{decl_module, decl_storage, decl_event, decl_error, ensure, dispatch, transactional};
 use frame_support::codec::{Encode, Decode};
-use frame_system::ensure_signed;
+use frame_support::{Parameter, weights::GetDispatchInfo};
+use frame_support::traits::{Currency, ReservableCurrency, OnUnbalanced, Get, Filter, IsSubType, IsType, EnsureOrigin};
+use frame_support::traits::schedule::{Named as ScheduleNamed, DispatchTime, HARD_DEADLINE};
+use frame_support::weights::Weight;
+use sp_runtime::traits::{Saturating, Verify, IdentifyAccount, Hash};
+use frame_system::{ensure_signed, ensure_root};
 use sp_std::prelude::*;
 use core::str::from_utf8;
 use serde_json;
@@ -21,25 +28,108 @@ mod tests;
 pub trait Config: frame_system::Config {
 	/// Because this pallet emits events, it depends on the runtime's definition of an event.
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Config>::Event>;
+	/// The currency in which certificate deposits are held.
+	type Currency: ReservableCurrency<Self::AccountId>;
+	/// The flat part of the deposit reserved for every certificate.
+	type CertificateDepositBase: Get<BalanceOf<Self>>;
+	/// The part of the deposit charged for each byte of an encoded certificate.
+	type CertificateDepositPerByte: Get<BalanceOf<Self>>;
+	/// What to do with the deposit of a certificate that is revoked against its owner's will.
+	type Slashed: OnUnbalanced<NegativeImbalanceOf<Self>>;
+	/// The overarching call type, so that certificate calls can be wrapped in `sponsored_call`
+	/// and key activations scheduled.
+	type Call: Parameter + GetDispatchInfo + IsSubType<Call<Self>> + From<Call<Self>>
+		+ IsType<<Self as frame_system::Config>::Call>;
+	/// The signature an owner gives to let someone else pay for its certificate call.
+	type Signature: Parameter + Verify<Signer = Self::Signer>;
+	/// The key behind `Signature`. It must identify the owner's account.
+	type Signer: IdentifyAccount<AccountId = Self::AccountId>;
+	/// How many blocks a registration commitment must wait before it can be revealed.
+	type MinCommitmentAge: Get<Self::BlockNumber>;
+	/// How many blocks a registration commitment can wait before it expires.
+	type MaxCommitmentAge: Get<Self::BlockNumber>;
+	/// The origin that may transfer, remove or register any certificate, e.g. to settle a dispute.
+	type ForceOrigin: EnsureOrigin<Self::Origin>;
+	/// Dispatches `activate_key` when a key rotation's overlap ends.
+	type Scheduler: ScheduleNamed<Self::BlockNumber, <Self as Config>::Call, Self::PalletsOrigin>;
+	/// The origin the scheduler dispatches with. Activations are dispatched as root.
+	type PalletsOrigin: From<frame_system::RawOrigin<Self::AccountId>>;
+	/// The fewest blocks a key rotation's overlap may last, so that cached certificates have
+	/// time to pick up the next key before it replaces the current one.
+	type MinKeyOverlap: Get<Self::BlockNumber>;
+	/// How many versions of each domain's certificate `CertificateHistory` keeps.
+	type MaxHistoryDepth: Get<u32>;
+	/// How many blocks a removed domain's history is kept before it is deleted.
+	type HistoryRetention: Get<Self::BlockNumber>;
 	fn inflationary_reward();
 }
 
+pub type BalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+pub type NegativeImbalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::NegativeImbalance;
+
 pub const CERTIFICATE_VERSION: i32 = 1;
 
+/// Prefixed to every sponsorship payload, so an owner's signature can't be reused elsewhere.
+pub const SPONSORSHIP_CONTEXT: &[u8] = b"siip/sponsor";
+
+/// The longest `name`, in bytes, that a certificate may carry.
+pub const MAX_NAME_LENGTH: usize = 256;
+/// The longest `info`, in bytes, that a certificate may carry.
+pub const MAX_INFO_LENGTH: usize = 4096;
+/// The longest `key`, in bytes, that a certificate may carry. This fits an RSA-8192 public key.
+pub const MAX_KEY_LENGTH: usize = 4096;
+
+/// Prefixed to the domain in the scheduler's name for its key activation.
+pub const KEY_ROTATION_ID: [u8; 8] = *b"siip/key";
+
 #[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
 pub struct Certificate<AccountIdT> {
-	version_number: i32,
-	owner_id: AccountIdT,
-	name: Vec<u8>,
-	info: Vec<u8>,
-	key: Vec<u8>,
-	ip_addr: Vec<u8>,
-	domain: Vec<u8>,
+	pub version_number: i32,
+	pub owner_id: AccountIdT,
+	pub name: Vec<u8>,
+	pub info: Vec<u8>,
+	pub key: Vec<u8>,
+	pub ip_addr: Vec<u8>,
+	pub domain: Vec<u8>,
+}
+
+/// One version of a domain's certificate, and the change that made it.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
+pub struct CertificateVersion<AccountId, BlockNumber> {
+	/// The certificate from this change on, or `None` if the change removed it.
+	pub certificate: Option<Certificate<AccountId>>,
+	/// The block the change was made in.
+	pub block_number: BlockNumber,
+	/// The index of the extrinsic that made the change in its block. `None` for changes made
+	/// outside an extrinsic: at genesis, or by the scheduler.
+	pub extrinsic_index: Option<u32>,
+}
+
+/// A key that will replace a certificate's key at `activation`. Until then, both keys are valid.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
+pub struct KeyRotation<BlockNumber> {
+	pub next_key: Vec<u8>,
+	pub activation: BlockNumber,
+}
+
+//Appends the criterion that a field is at most `max` bytes long, marked `outcome`
+fn length_criterion(criteria: &mut Vec<u8>, outcome: &str, max: usize) {
+	criteria.extend_from_slice(alloc::format!("{}: Must be at most {} bytes long\n", outcome, max).as_bytes());
 }
 
 pub fn check_name(name: &[u8]) -> Vec<u8> {
 	let mut criteria: Vec<u8> = Vec::new();
 
+	//Must fit within the size cap
+	if name.len() <= MAX_NAME_LENGTH {
+		length_criterion(&mut criteria, "Ok", MAX_NAME_LENGTH);
+	} else {
+		length_criterion(&mut criteria, "Err", MAX_NAME_LENGTH);
+		return criteria;
+	}
+
 	//Must be a valid UTF-8 String
 	let name = from_utf8(name);
 	match name {
@@ -105,22 +195,19 @@ pub fn check_domain(domain: &[u8]) -> Vec<u8> {
 	}
 
 	//The top level domain must be a 2-63 character long
-	let parts: Vec<&str> = domain.split('.').collect();
-	let tld = "";
-	if (parts.len() < 2) || (!parts.last().is_some()) {
+	//Without a period there is neither a TLD nor a subdomain
+	let (subdomain, tld) = match domain.rfind('.') {
+		Some(period) => (&domain[..period], &domain[period + 1..]),
+		None => ("", ""),
+	};
+	if (tld.chars().count() < 2) || (tld.chars().count() > 63) {
 		criteria.extend_from_slice("Err: TLD must be between 2 and 63\
 	 	characters in length\n".as_bytes());
 	} else {
-		let tld = parts.last().unwrap();
-		if (tld.chars().count() < 2) || (tld.chars().count() > 63) {
-			criteria.extend_from_slice("Err: TLD must be between 2 and 63\
-	 		characters in length\n".as_bytes());
-		} else {
-			criteria.extend_from_slice("Ok: TLD must be between 2 and 63\
-	 		characters in length\n".as_bytes());
-		}
+		criteria.extend_from_slice("Ok: TLD must be between 2 and 63\
+	 	characters in length\n".as_bytes());
 	}
-	if (domain.chars().count() - tld.chars().count()) > 1 {
+	if !subdomain.is_empty() {
 		criteria.extend_from_slice("Ok: Subdomain must be at least 1 character long\
 			\n".as_bytes());
 	} else {
@@ -163,8 +250,10 @@ pub fn check_ip(ip: &[u8]) -> Vec<u8> {
 		criteria.extend_from_slice("Err: There must be four sections (separated by periods)\n".as_bytes());
 	}
 
-	//Each section must be a valid U8 number
-	if nums.into_iter().all(|str| str.parse::<u8>().is_ok()) {
+	//Each section must be a valid U8 number, in plain decimal: no sign, and no leading zeros
+	if nums.into_iter().all(|str| str.chars().all(|c| c.is_ascii_digit())
+		&& !(str.len() > 1 && str.starts_with('0'))
+		&& str.parse::<u8>().is_ok()) {
 		criteria.extend_from_slice("Ok: Numbers must be between 0 and 255\n".as_bytes());
 	} else {
 		criteria.extend_from_slice("Err: Numbers must be between 0 and 255\n".as_bytes());
@@ -177,6 +266,14 @@ pub fn check_ip(ip: &[u8]) -> Vec<u8> {
 pub fn check_info(info: &[u8]) -> Vec<u8> {
 	let mut criteria: Vec<u8> = Vec::new();
 
+	//Must fit within the size cap. Checked first so we never parse an oversized blob
+	if info.len() <= MAX_INFO_LENGTH {
+		length_criterion(&mut criteria, "Ok", MAX_INFO_LENGTH);
+	} else {
+		length_criterion(&mut criteria, "Err", MAX_INFO_LENGTH);
+		return criteria;
+	}
+
 	//Must be a valid UTF-8 String
 	let info = from_utf8(&info);
 	match info {
@@ -205,6 +302,14 @@ pub fn check_info(info: &[u8]) -> Vec<u8> {
 pub fn check_key(key: &[u8]) -> Vec<u8> {
 	let mut criteria: Vec<u8> = Vec::new();
 
+	//Must fit within the size cap
+	if key.len() <= MAX_KEY_LENGTH {
+		length_criterion(&mut criteria, "Ok", MAX_KEY_LENGTH);
+	} else {
+		length_criterion(&mut criteria, "Err", MAX_KEY_LENGTH);
+		return criteria;
+	}
+
 	//Must be a valid UTF-8 String
 	let key = from_utf8(&key);
 	match key {
@@ -222,7 +327,7 @@ pub fn check_key(key: &[u8]) -> Vec<u8> {
 	let mut i = 0;
 
 	for char in key.chars() {
-		if (i != 0) && ((i + 1) % 3 == 0) {
+		if (i + 1) % 3 == 0 {
 			if char != ':' {
 				valid_separator = false;
 			}
@@ -249,7 +354,9 @@ pub fn check_key(key: &[u8]) -> Vec<u8> {
 		}
 		i += 1;
 	}
-	if key.chars().last() == Some(':') {
+	//Pairs of digits with a ':' between each: 2 characters, then 3 more per pair. This also
+	//rules out an empty key, a trailing ':' and a trailing lone digit
+	if i % 3 != 2 {
 		valid_separator = false;
 	}
 
@@ -272,6 +379,27 @@ pub fn check_key(key: &[u8]) -> Vec<u8> {
 	criteria
 }
 
+/// Runs `check_name`, `check_domain`, `check_ip`, `check_info` and `check_key` over a
+/// certificate's fields, and returns every criterion they failed, prefixed with the field's name.
+#[cfg(feature = "std")]
+pub fn validation_failures(name: &[u8], domain: &[u8], ip_addr: &[u8], info: &[u8], key: &[u8]) -> Vec<String> {
+	let checks = [
+		("name", check_name(name)),
+		("domain", check_domain(domain)),
+		("ip", check_ip(ip_addr)),
+		("info", check_info(info)),
+		("key", check_key(key)),
+	];
+	checks.iter()
+		.flat_map(|(field, criteria)| {
+			String::from_utf8_lossy(criteria).lines()
+				.filter_map(|line| line.strip_prefix("Err: "))
+				.map(|criterion| format!("{}: {}", field, criterion))
+				.collect::<Vec<_>>()
+		})
+		.collect()
+}
+
 
 // The pallet's runtime storage items.
 // https://substrate.dev/docs/en/knowledgebase/runtime/storage
@@ -284,19 +412,79 @@ decl_storage! {
 		// https://substrate.dev/docs/en/knowledgebase/runtime/storage#declaring-storage-items
 		pub CertificateMap get(fn domain_to_certificate): map hasher(blake2_128_concat) Vec<u8> => Certificate<T::AccountId>;
 		pub ReverseMap get(fn ip_to_certificates): map hasher(blake2_128_concat) Vec<u8> => Vec<Certificate<T::AccountId>>;
+		// How many sponsored calls each account has authorized. Part of the signed payload, to prevent replays.
+		pub SponsorshipNonce get(fn sponsorship_nonce): map hasher(blake2_128_concat) T::AccountId => u32;
+		// Registration commitments, by committer, and the block each one was made in.
+		pub Commitments get(fn commitment): double_map hasher(blake2_128_concat) T::AccountId, hasher(identity) T::Hash => Option<T::BlockNumber>;
+		// The commitments that expire in each block, so they can be pruned.
+		CommitmentExpiry: map hasher(twox_64_concat) T::BlockNumber => Vec<(T::AccountId, T::Hash)>;
+		// The account that paid the deposit on each domain, and how much it paid.
+		pub CertificateDeposits get(fn certificate_deposit): map hasher(blake2_128_concat) Vec<u8> => Option<(T::AccountId, BalanceOf<T>)>;
+		// The key each domain is rotating to, and the block it takes over in.
+		pub KeyRotations get(fn key_rotation): map hasher(blake2_128_concat) Vec<u8> => Option<KeyRotation<T::BlockNumber>>;
+		// The latest `MaxHistoryDepth` versions of each domain's certificate, oldest first.
+		pub CertificateHistory get(fn certificate_history): map hasher(blake2_128_concat) Vec<u8> => Vec<CertificateVersion<T::AccountId, T::BlockNumber>>;
+		// The removed domains whose history ages out in each block, so it can be pruned.
+		HistoryExpiry: map hasher(twox_64_concat) T::BlockNumber => Vec<Vec<u8>>;
+	}
+	add_extra_genesis {
+		// Certificates registered at block 0, like `force_register`: without a commitment or a deposit.
+		// Each is `(owner, name, domain, ip_addr, info, key)`.
+		config(certificates): Vec<(T::AccountId, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>)>;
+		build(|config: &GenesisConfig<T>| {
+			for (owner, name, domain, ip_addr, info, key) in &config.certificates {
+				let domain_str = String::from_utf8_lossy(domain);
+				if let Err(e) = Module::<T>::validate_certificate(name, domain, ip_addr, info, key) {
+					panic!("Genesis certificate for {} is invalid: {:?}", domain_str, e);
+				}
+				assert!(!CertificateMap::<T>::contains_key(domain), "Genesis certificate for {} is duplicated", domain_str);
+
+				let cert = Certificate {
+					version_number: CERTIFICATE_VERSION,
+					owner_id: owner.clone(),
+					name: name.clone(),
+					info: info.clone(),
+					key: key.clone(),
+					ip_addr: ip_addr.clone(),
+					domain: domain.clone(),
+				};
+				CertificateMap::<T>::insert(domain, cert.clone());
+				Module::<T>::record_history(domain, Some(&cert));
+				ReverseMap::<T>::append(ip_addr, cert);
+			}
+		});
 	}
 }
 
 // Pallets use events to inform users when important changes are made.
 // https://substrate.dev/docs/en/knowledgebase/runtime/events
 decl_event!(
-	pub enum Event<T> where AccountId = <T as frame_system::Config>::AccountId {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Config>::AccountId,
+		Hash = <T as frame_system::Config>::Hash,
+		BlockNumber = <T as frame_system::Config>::BlockNumber,
+	{
 		/// A certificate was added to the blockchain. Returns: [certificate, person]
 		CertificateRegistered(Certificate<AccountId>, AccountId),
 		/// A certificate in the blockchain was modified. Returns: [certificate, certificate, person]
 		CertificateModified(Certificate<AccountId>, Certificate<AccountId>, AccountId),
 		/// A certificate in the blockchain was removed. Returns (deleted): [certificate, person]
 		CertificateRemoved(Certificate<AccountId>, AccountId),
+		/// A certificate call was paid for by someone other than its owner. Returns: [owner, sponsor]
+		CallSponsored(AccountId, AccountId),
+		/// An account committed to registering a domain it has not disclosed yet. Returns: [commitment, person]
+		RegistrationCommitted(Hash, AccountId),
+		/// Governance gave a certificate to a new owner. Returns: [certificate, previous owner]
+		CertificateForceTransferred(Certificate<AccountId>, AccountId),
+		/// Governance revoked a certificate and slashed its deposit. Returns (deleted): [certificate]
+		CertificateForceRemoved(Certificate<AccountId>),
+		/// Governance registered a certificate, replacing any existing one. Returns: [certificate]
+		CertificateForceRegistered(Certificate<AccountId>),
+		/// A certificate's owner scheduled a new key. Both keys are valid until the activation block.
+		/// Returns: [certificate, next key, activation block]
+		KeyRotationScheduled(Certificate<AccountId>, Vec<u8>, BlockNumber),
+		/// A scheduled key replaced a certificate's key. Returns: [certificate, old certificate]
+		KeyRotated(Certificate<AccountId>, Certificate<AccountId>),
 	}
 );
 
@@ -312,6 +500,35 @@ decl_error! {
 		NonexistentDomain,
 		DifferentOwner,
 		NoModifications,
+		InsufficientDeposit,
+		/// The name is longer than `MAX_NAME_LENGTH` bytes.
+		NameTooLong,
+		/// The info is longer than `MAX_INFO_LENGTH` bytes.
+		InfoTooLong,
+		/// The key is longer than `MAX_KEY_LENGTH` bytes.
+		KeyTooLong,
+		/// Only certificate calls that could be made directly may be sponsored.
+		NotSponsorable,
+		/// The owner's signature doesn't match the sponsored call, sponsor, nonce or expiry.
+		InvalidSponsorSignature,
+		/// The owner only authorized the sponsored call until an earlier block.
+		SponsorshipExpired,
+		/// The same commitment is already pending.
+		CommitmentAlreadyExists,
+		/// No pending commitment matches the revealed domain, salt and owner.
+		NoCommitment,
+		/// The commitment has not yet aged `MinCommitmentAge` blocks.
+		CommitmentTooNew,
+		/// The commitment is older than `MaxCommitmentAge` blocks.
+		CommitmentExpired,
+		/// The certificate already belongs to that account.
+		SameOwner,
+		/// The next key would take over less than `MinKeyOverlap` blocks from now.
+		ActivationTooSoon,
+		/// The scheduler refused the key activation.
+		ActivationNotScheduled,
+		/// The domain has no key rotation that is due.
+		NoKeyRotation,
 	}
 }
 
@@ -326,8 +543,19 @@ decl_module! {
 		// Events must be initialized if they are used by the pallet.
 		fn deposit_event() = default;
 
+		/// The longest name, in bytes, that a certificate may carry.
+		const MaxNameLength: u32 = MAX_NAME_LENGTH as u32;
+		/// The longest info, in bytes, that a certificate may carry.
+		const MaxInfoLength: u32 = MAX_INFO_LENGTH as u32;
+		/// The longest key, in bytes, that a certificate may carry.
+		const MaxKeyLength: u32 = MAX_KEY_LENGTH as u32;
+		/// How many blocks a registration commitment must wait before it can be revealed.
+		const MinCommitmentAge: T::BlockNumber = T::MinCommitmentAge::get();
+		/// How many blocks a registration commitment can wait before it expires.
+		const MaxCommitmentAge: T::BlockNumber = T::MaxCommitmentAge::get();
+
 		#[weight = 1_000_000]
-		pub fn register_certificate(
+		pub fn modify_certificate(
 			origin,
 			name: Vec<u8>,
 			domain: Vec<u8>,
@@ -337,41 +565,38 @@ decl_module! {
 		) -> dispatch::DispatchResult{
 
 			let sender = ensure_signed(origin)?;
+			Self::do_modify(&sender, &sender, name, domain, ip_addr, info, key)
+		}
 
-			//Input validation
-			ensure!(!from_utf8(&check_name(&name)).unwrap().contains("Err:"), Error::<T>::InvalidOwner);
-			ensure!(!from_utf8(&check_domain(&domain)).unwrap().contains("Err:"), Error::<T>::InvalidDomain);
-			ensure!(!from_utf8(&check_ip(&ip_addr)).unwrap().contains("Err:"), Error::<T>::InvalidIP);
-			ensure!(!from_utf8(&check_info(&info)).unwrap().contains("Err:"), Error::<T>::InvalidInfo);
-			ensure!(!from_utf8(&check_key(&key)).unwrap().contains("Err:"), Error::<T>::InvalidKey);
-
-			//Ensures that the domain is available
-			ensure!(!CertificateMap::<T>::contains_key(&domain), Error::<T>::DomainAlreadyTaken);
-
-			let cert = Certificate {
-				version_number: CERTIFICATE_VERSION,
-				owner_id: sender.clone(),
-				name: name.clone(),
-				info: info.clone(),
-				key: key.clone(),
-				ip_addr: ip_addr.clone(),
-				domain: domain.clone(),
-			};
+		#[weight = 1_000_000]
+		pub fn remove_certificate(
+			origin,
+			domain: Vec<u8>,
+		) -> dispatch::DispatchResult{
 
-			CertificateMap::<T>::insert(&domain, cert.clone());
+			let sender = ensure_signed(origin)?;
+			Self::do_remove(&sender, domain)
+		}
 
-			//Adds it to the reverse lookup table
-			let mut certs = ReverseMap::<T>::take(&ip_addr.clone());
-			certs.push(cert.clone());
-			ReverseMap::<T>::insert(&ip_addr, certs);
+		/// First half of a registration: commits to `registration_commitment(domain, salt, sender)`
+		/// without disclosing the domain, so nobody watching the pool can snatch it first.
+		/// There is no single-step registration; every certificate goes through this.
+		#[weight = 1_000_000]
+		pub fn commit_registration(
+			origin,
+			commitment: T::Hash,
+		) -> dispatch::DispatchResult{
 
-			Self::deposit_event(RawEvent::CertificateRegistered(cert, sender));
-			Ok(())
+			let sender = ensure_signed(origin)?;
+			Self::do_commit(&sender, commitment)
 		}
 
+		/// Second half of a registration: reveals the domain behind a commitment that is
+		/// at least `MinCommitmentAge` and at most `MaxCommitmentAge` blocks old.
 		#[weight = 1_000_000]
-		pub fn modify_certificate(
+		pub fn reveal_registration(
 			origin,
+			salt: Vec<u8>,
 			name: Vec<u8>,
 			domain: Vec<u8>,
 			ip_addr: Vec<u8>,
@@ -380,79 +605,589 @@ decl_module! {
 		) -> dispatch::DispatchResult{
 
 			let sender = ensure_signed(origin)?;
+			Self::do_reveal(&sender, &sender, salt, name, domain, ip_addr, info, key)
+		}
 
+		/// Schedules `next_key` to replace the certificate's key at block `activation`, at least
+		/// `MinKeyOverlap` blocks from now. Until then both keys are valid, so clients with a
+		/// cached certificate keep working while the server switches keys. A new rotation
+		/// replaces one that is still pending.
+		#[weight = 1_000_000]
+		pub fn rotate_key(
+			origin,
+			domain: Vec<u8>,
+			next_key: Vec<u8>,
+			activation: T::BlockNumber,
+		) -> dispatch::DispatchResult{
 
-			//Input validation
-			ensure!(!from_utf8(&check_name(&name)).unwrap().contains("Err:"), Error::<T>::InvalidOwner);
-			ensure!(!from_utf8(&check_domain(&domain)).unwrap().contains("Err:"), Error::<T>::InvalidDomain);
-			ensure!(!from_utf8(&check_ip(&ip_addr)).unwrap().contains("Err:"), Error::<T>::InvalidIP);
-			ensure!(!from_utf8(&check_info(&info)).unwrap().contains("Err:"), Error::<T>::InvalidInfo);
-			ensure!(!from_utf8(&check_key(&key)).unwrap().contains("Err:"), Error::<T>::InvalidKey);
+			let sender = ensure_signed(origin)?;
+			Self::do_rotate(&sender, &sender, domain, next_key, activation)
+		}
 
-			//Ensures that the domain already exists
-			ensure!(CertificateMap::<T>::contains_key(&domain), Error::<T>::NonexistentDomain);
+		/// Replaces the certificate's key with its scheduled next key. Dispatched as root by the
+		/// scheduler at the activation block.
+		#[weight = 1_000_000]
+		pub fn activate_key(
+			origin,
+			domain: Vec<u8>,
+		) -> dispatch::DispatchResult{
 
-			let cert = Certificate {
-				version_number: CERTIFICATE_VERSION,
-				owner_id: sender.clone(),
-				name: name.clone(),
-				info: info.clone(),
-				key: key.clone(),
-				ip_addr: ip_addr.clone(),
-				domain: domain.clone(),
-			};
+			ensure_root(origin)?;
+
+			let rotation = KeyRotations::<T>::get(&domain).ok_or(Error::<T>::NoKeyRotation)?;
+			ensure!(rotation.activation <= frame_system::Module::<T>::block_number(), Error::<T>::NoKeyRotation);
+			KeyRotations::<T>::remove(&domain);
 
-			//Ensures that the owner of the domain is the sender
 			let old_cert = CertificateMap::<T>::get(&domain);
-			ensure!(sender == old_cert.owner_id, Error::<T>::DifferentOwner);
+			let cert = Certificate {
+				key: rotation.next_key,
+				..old_cert.clone()
+			};
+			Self::replace_certificate(&old_cert, &cert);
 
-			//Ensures that there is some modification
-			ensure!(cert != old_cert, Error::<T>::NoModifications);
+			//The larger of the two deposits was reserved when the rotation was scheduled,
+			//so this can only return the difference
+			if let Some((holder, _)) = CertificateDeposits::<T>::get(&domain) {
+				let _ = Self::update_deposit(&domain, &holder, Self::deposit_for(&cert));
+			}
 
-			CertificateMap::<T>::take(&domain);
-			CertificateMap::<T>::insert(&domain, cert.clone());
+			Self::deposit_event(RawEvent::KeyRotated(cert, old_cert));
+			Ok(())
+		}
 
-			//Modifies the reverse lookup map
-			let mut certs = ReverseMap::<T>::take(&ip_addr.clone());
-			certs.retain(|x| *x.domain != domain[..]);
-			certs.push(cert.clone());
-			ReverseMap::<T>::insert(&ip_addr, certs);
+		/// Performs a certificate call on behalf of `owner`, with the sender paying the fee
+		/// and any deposit. `signature` is `owner`'s signature over `sponsorship_payload`,
+		/// which ties it to this sponsor and to `owner`'s current sponsorship nonce. It is only
+		/// valid up to block `valid_until`, so a sponsor can't hold on to it and submit it later.
+		#[weight = call.get_dispatch_info().weight.saturating_add(1_000_000)]
+		pub fn sponsored_call(
+			origin,
+			owner: T::AccountId,
+			signature: T::Signature,
+			valid_until: T::BlockNumber,
+			call: Box<<T as Config>::Call>,
+		) -> dispatch::DispatchResult{
 
-			Self::deposit_event(RawEvent::CertificateModified(cert, old_cert, sender));
+			let sponsor = ensure_signed(origin)?;
+
+			//Only certificate calls can be sponsored, and only if they could be made directly
+			let inner = call.is_sub_type().ok_or(Error::<T>::NotSponsorable)?;
+			ensure!(
+				<T as frame_system::Config>::BaseCallFilter::filter(IsType::into_ref(&*call)),
+				Error::<T>::NotSponsorable
+			);
+
+			//Ensures that the owner really asked for this call, and that it isn't a replay
+			let payload = Self::sponsorship_payload(&sponsor, &owner, valid_until, &call);
+			ensure!(signature.verify(&payload[..], &owner), Error::<T>::InvalidSponsorSignature);
+			ensure!(frame_system::Module::<T>::block_number() <= valid_until, Error::<T>::SponsorshipExpired);
+
+			match inner.clone() {
+				Call::modify_certificate(name, domain, ip_addr, info, key) =>
+					Self::do_modify(&owner, &sponsor, name, domain, ip_addr, info, key),
+				Call::remove_certificate(domain) =>
+					Self::do_remove(&owner, domain),
+				Call::commit_registration(commitment) =>
+					Self::do_commit(&owner, commitment),
+				Call::reveal_registration(salt, name, domain, ip_addr, info, key) =>
+					Self::do_reveal(&owner, &sponsor, salt, name, domain, ip_addr, info, key),
+				Call::rotate_key(domain, next_key, activation) =>
+					Self::do_rotate(&owner, &sponsor, domain, next_key, activation),
+				_ => Err(Error::<T>::NotSponsorable.into()),
+			}?;
+
+			SponsorshipNonce::<T>::mutate(&owner, |nonce| *nonce += 1);
+			Self::deposit_event(RawEvent::CallSponsored(owner, sponsor));
 			Ok(())
 		}
 
+		/// Gives `domain` to `new_owner`, e.g. to settle a trademark dispute.
+		/// The deposit stays with whoever paid it, and is returned to them on removal.
 		#[weight = 1_000_000]
-		pub fn remove_certificate(
+		pub fn force_transfer(
 			origin,
 			domain: Vec<u8>,
+			new_owner: T::AccountId,
 		) -> dispatch::DispatchResult{
 
-			let sender = ensure_signed(origin)?;
-
-			//Input validation
-			ensure!(!from_utf8(&check_domain(&domain)).unwrap().contains("Err:"), Error::<T>::InvalidDomain);
+			T::ForceOrigin::ensure_origin(origin)?;
 
 			//Ensures that the domain already exists
 			ensure!(CertificateMap::<T>::contains_key(&domain), Error::<T>::NonexistentDomain);
 
-			//Ensures that the owner of the domain is the sender
 			let old_cert = CertificateMap::<T>::get(&domain);
-			ensure!(sender == old_cert.owner_id, Error::<T>::DifferentOwner);
+			ensure!(new_owner != old_cert.owner_id, Error::<T>::SameOwner);
+
+			let cert = Certificate {
+				owner_id: new_owner,
+				..old_cert.clone()
+			};
+			Self::replace_certificate(&old_cert, &cert);
 
-			CertificateMap::<T>::take(&domain);
+			//The new owner needn't hold the previous owner's next key
+			Self::cancel_rotation(&domain);
+
+			Self::deposit_event(RawEvent::CertificateForceTransferred(cert, old_cert.owner_id));
+			Ok(())
+		}
+
+		/// Revokes `domain` against its owner's will. Its deposit is slashed.
+		#[weight = 1_000_000]
+		pub fn force_remove(
+			origin,
+			domain: Vec<u8>,
+		) -> dispatch::DispatchResult{
+
+			T::ForceOrigin::ensure_origin(origin)?;
+
+			//Ensures that the domain already exists
+			ensure!(CertificateMap::<T>::contains_key(&domain), Error::<T>::NonexistentDomain);
+
+			let old_cert = CertificateMap::<T>::take(&domain);
+			Self::record_history(&domain, None);
+			Self::slash_deposit(&domain);
+			Self::cancel_rotation(&domain);
 
 			//Deletes the certificate from the reverse lookup map
 			let mut certs = ReverseMap::<T>::take(&old_cert.ip_addr);
 			certs.retain(|x| *x.domain != domain[..]);
 			ReverseMap::<T>::insert(&old_cert.ip_addr, certs);
 
-			Self::deposit_event(RawEvent::CertificateRemoved(old_cert, sender));
+			Self::deposit_event(RawEvent::CertificateForceRemoved(old_cert));
+			Ok(())
+		}
+
+		/// Registers a certificate for `owner` without a commitment or a deposit.
+		/// An existing certificate for the domain is replaced, and its deposit slashed.
+		#[weight = 1_000_000]
+		pub fn force_register(
+			origin,
+			owner: T::AccountId,
+			name: Vec<u8>,
+			domain: Vec<u8>,
+			ip_addr: Vec<u8>,
+			info: Vec<u8>,
+			key: Vec<u8>,
+		) -> dispatch::DispatchResult{
+
+			T::ForceOrigin::ensure_origin(origin)?;
+
+			//Input validation
+			Self::validate_certificate(&name, &domain, &ip_addr, &info, &key)?;
+
+			let cert = Certificate {
+				version_number: CERTIFICATE_VERSION,
+				owner_id: owner,
+				name,
+				info,
+				key,
+				ip_addr,
+				domain: domain.clone(),
+			};
+
+			if CertificateMap::<T>::contains_key(&domain) {
+				let old_cert = CertificateMap::<T>::get(&domain);
+				Self::slash_deposit(&domain);
+				Self::cancel_rotation(&domain);
+				Self::replace_certificate(&old_cert, &cert);
+			} else {
+				CertificateMap::<T>::insert(&domain, cert.clone());
+				Self::record_history(&domain, Some(&cert));
+				ReverseMap::<T>::append(&cert.ip_addr, cert.clone());
+			}
+
+			Self::deposit_event(RawEvent::CertificateForceRegistered(cert));
 			Ok(())
 		}
 
+		fn on_initialize(n: T::BlockNumber) -> Weight {
+			//Prunes the commitments that were never revealed
+			let expired = CommitmentExpiry::<T>::take(n);
+			let committed_at = n.saturating_sub(T::MaxCommitmentAge::get()).saturating_sub(1u32.into());
+			for (committer, commitment) in expired.iter() {
+				//The same commitment may have been revealed and made again since
+				if Commitments::<T>::get(committer, commitment) == Some(committed_at) {
+					Commitments::<T>::remove(committer, commitment);
+				}
+			}
+			let mut weight = T::DbWeight::get().reads_writes(1 + expired.len() as Weight, 1 + expired.len() as Weight);
+
+			//Prunes the history of domains that have stayed removed for `HistoryRetention` blocks
+			let aged_out = HistoryExpiry::<T>::take(n);
+			let removed_at = n.saturating_sub(T::HistoryRetention::get());
+			for domain in aged_out.iter() {
+				//The domain may have been registered, or removed again, since
+				let last = CertificateHistory::<T>::get(domain).pop();
+				if let Some(CertificateVersion { certificate: None, block_number, .. }) = last {
+					if block_number == removed_at {
+						CertificateHistory::<T>::remove(domain);
+					}
+				}
+			}
+			weight = weight.saturating_add(
+				T::DbWeight::get().reads_writes(1 + aged_out.len() as Weight, 1 + aged_out.len() as Weight)
+			);
+			weight
+		}
+
 		fn on_finalize(_n: T::BlockNumber) {
 			T::inflationary_reward();
 		}
 	}
 }
+
+impl<T: Config> Module<T> {
+	/// The bytes `owner` must sign to let `sponsor` submit `call` on its behalf, up to block
+	/// `valid_until`.
+	pub fn sponsorship_payload(
+		sponsor: &T::AccountId,
+		owner: &T::AccountId,
+		valid_until: T::BlockNumber,
+		call: &<T as Config>::Call,
+	) -> Vec<u8> {
+		(SPONSORSHIP_CONTEXT, sponsor, SponsorshipNonce::<T>::get(owner), valid_until, call).encode()
+	}
+
+	/// The certificate registered for `domain`, if there is one.
+	pub fn certificate(domain: &[u8]) -> Option<Certificate<T::AccountId>> {
+		if CertificateMap::<T>::contains_key(domain) {
+			Some(CertificateMap::<T>::get(domain))
+		} else {
+			None
+		}
+	}
+
+	/// The keys a server for `domain` may present: the certificate's key and, while a rotation
+	/// is pending, its next key.
+	pub fn valid_keys(domain: &[u8]) -> Vec<Vec<u8>> {
+		let mut keys: Vec<Vec<u8>> = Self::certificate(domain).into_iter().map(|cert| cert.key).collect();
+		keys.extend(KeyRotations::<T>::get(domain).map(|rotation| rotation.next_key));
+		keys
+	}
+
+	/// The commitment that `owner` must make before revealing `domain` with `salt`.
+	pub fn registration_commitment(domain: &[u8], salt: &[u8], owner: &T::AccountId) -> T::Hash {
+		T::Hashing::hash_of(&(domain, salt, owner))
+	}
+
+	fn do_commit(owner: &T::AccountId, commitment: T::Hash) -> dispatch::DispatchResult {
+		ensure!(!Commitments::<T>::contains_key(owner, &commitment), Error::<T>::CommitmentAlreadyExists);
+
+		let now = frame_system::Module::<T>::block_number();
+		let expiry = now.saturating_add(T::MaxCommitmentAge::get()).saturating_add(1u32.into());
+		Commitments::<T>::insert(owner, &commitment, now);
+		CommitmentExpiry::<T>::append(expiry, (owner.clone(), commitment));
+
+		Self::deposit_event(RawEvent::RegistrationCommitted(commitment, owner.clone()));
+		Ok(())
+	}
+
+	fn do_reveal(
+		owner: &T::AccountId,
+		payer: &T::AccountId,
+		salt: Vec<u8>,
+		name: Vec<u8>,
+		domain: Vec<u8>,
+		ip_addr: Vec<u8>,
+		info: Vec<u8>,
+		key: Vec<u8>,
+	) -> dispatch::DispatchResult {
+		//Ensures that the owner committed to this domain, long enough ago but not too long
+		let commitment = Self::registration_commitment(&domain, &salt, owner);
+		let committed_at = Commitments::<T>::get(owner, &commitment).ok_or(Error::<T>::NoCommitment)?;
+		let age = frame_system::Module::<T>::block_number().saturating_sub(committed_at);
+		ensure!(age >= T::MinCommitmentAge::get(), Error::<T>::CommitmentTooNew);
+		ensure!(age <= T::MaxCommitmentAge::get(), Error::<T>::CommitmentExpired);
+
+		Self::do_register(owner, payer, name, domain, ip_addr, info, key)?;
+		Commitments::<T>::remove(owner, &commitment);
+		Ok(())
+	}
+
+	fn do_register(
+		owner: &T::AccountId,
+		payer: &T::AccountId,
+		name: Vec<u8>,
+		domain: Vec<u8>,
+		ip_addr: Vec<u8>,
+		info: Vec<u8>,
+		key: Vec<u8>,
+	) -> dispatch::DispatchResult {
+		//Input validation
+		Self::validate_certificate(&name, &domain, &ip_addr, &info, &key)?;
+
+		//Ensures that the domain is available
+		ensure!(!CertificateMap::<T>::contains_key(&domain), Error::<T>::DomainAlreadyTaken);
+
+		let cert = Certificate {
+			version_number: CERTIFICATE_VERSION,
+			owner_id: owner.clone(),
+			name: name.clone(),
+			info: info.clone(),
+			key: key.clone(),
+			ip_addr: ip_addr.clone(),
+			domain: domain.clone(),
+		};
+
+		//Reserves the deposit before anything is written, so a failure leaves no trace
+		Self::update_deposit(&domain, payer, Self::deposit_for(&cert))?;
+
+		CertificateMap::<T>::insert(&domain, cert.clone());
+		Self::record_history(&domain, Some(&cert));
+
+		//Adds it to the reverse lookup table
+		let mut certs = ReverseMap::<T>::take(&ip_addr.clone());
+		certs.push(cert.clone());
+		ReverseMap::<T>::insert(&ip_addr, certs);
+
+		Self::deposit_event(RawEvent::CertificateRegistered(cert, owner.clone()));
+		Ok(())
+	}
+
+	fn do_modify(
+		owner: &T::AccountId,
+		payer: &T::AccountId,
+		name: Vec<u8>,
+		domain: Vec<u8>,
+		ip_addr: Vec<u8>,
+		info: Vec<u8>,
+		key: Vec<u8>,
+	) -> dispatch::DispatchResult {
+		//Input validation
+		Self::validate_certificate(&name, &domain, &ip_addr, &info, &key)?;
+
+		//Ensures that the domain already exists
+		ensure!(CertificateMap::<T>::contains_key(&domain), Error::<T>::NonexistentDomain);
+
+		let cert = Certificate {
+			version_number: CERTIFICATE_VERSION,
+			owner_id: owner.clone(),
+			name: name.clone(),
+			info: info.clone(),
+			key: key.clone(),
+			ip_addr: ip_addr.clone(),
+			domain: domain.clone(),
+		};
+
+		//Ensures that the owner of the domain is the sender
+		let old_cert = CertificateMap::<T>::get(&domain);
+		ensure!(*owner == old_cert.owner_id, Error::<T>::DifferentOwner);
+
+		//Ensures that there is some modification
+		ensure!(cert != old_cert, Error::<T>::NoModifications);
+
+		//A new key supersedes any pending rotation. Otherwise the rotation carries on, and its
+		//next key still needs a deposit
+		let rotation = KeyRotations::<T>::get(&domain).filter(|_| cert.key == old_cert.key);
+
+		//The deposit follows the size of the new certificate
+		Self::update_deposit(&domain, payer, Self::deposit_with_rotation(&cert, rotation.as_ref()))?;
+		if rotation.is_none() {
+			Self::cancel_rotation(&domain);
+		}
+
+		//Also moves it in the reverse lookup map if the IP changed
+		Self::replace_certificate(&old_cert, &cert);
+
+		Self::deposit_event(RawEvent::CertificateModified(cert, old_cert, owner.clone()));
+		Ok(())
+	}
+
+	#[transactional]
+	fn do_rotate(
+		owner: &T::AccountId,
+		payer: &T::AccountId,
+		domain: Vec<u8>,
+		next_key: Vec<u8>,
+		activation: T::BlockNumber,
+	) -> dispatch::DispatchResult {
+		//Input validation
+		ensure!(next_key.len() <= MAX_KEY_LENGTH, Error::<T>::KeyTooLong);
+		ensure!(!from_utf8(&check_key(&next_key)).unwrap().contains("Err:"), Error::<T>::InvalidKey);
+
+		//Ensures that the domain already exists
+		ensure!(CertificateMap::<T>::contains_key(&domain), Error::<T>::NonexistentDomain);
+
+		//Ensures that the owner of the domain is the sender
+		let cert = CertificateMap::<T>::get(&domain);
+		ensure!(*owner == cert.owner_id, Error::<T>::DifferentOwner);
+		ensure!(next_key != cert.key, Error::<T>::NoModifications);
+
+		//Leaves cached certificates time to learn the next key
+		let earliest = frame_system::Module::<T>::block_number().saturating_add(T::MinKeyOverlap::get());
+		ensure!(activation >= earliest, Error::<T>::ActivationTooSoon);
+
+		//Both keys are held until the activation, so the deposit covers the larger certificate
+		let rotation = KeyRotation { next_key: next_key.clone(), activation };
+		Self::update_deposit(&domain, payer, Self::deposit_with_rotation(&cert, Some(&rotation)))?;
+
+		Self::cancel_rotation(&domain);
+		T::Scheduler::schedule_named(
+			Self::rotation_id(&domain),
+			DispatchTime::At(activation),
+			None,
+			HARD_DEADLINE,
+			frame_system::RawOrigin::Root.into(),
+			Call::<T>::activate_key(domain.clone()).into(),
+		).map_err(|_| Error::<T>::ActivationNotScheduled)?;
+		KeyRotations::<T>::insert(&domain, rotation);
+
+		Self::deposit_event(RawEvent::KeyRotationScheduled(cert, next_key, activation));
+		Ok(())
+	}
+
+	fn do_remove(owner: &T::AccountId, domain: Vec<u8>) -> dispatch::DispatchResult {
+		//Input validation
+		ensure!(!from_utf8(&check_domain(&domain)).unwrap().contains("Err:"), Error::<T>::InvalidDomain);
+
+		//Ensures that the domain already exists
+		ensure!(CertificateMap::<T>::contains_key(&domain), Error::<T>::NonexistentDomain);
+
+		//Ensures that the owner of the domain is the sender
+		let old_cert = CertificateMap::<T>::get(&domain);
+		ensure!(*owner == old_cert.owner_id, Error::<T>::DifferentOwner);
+
+		CertificateMap::<T>::take(&domain);
+		Self::record_history(&domain, None);
+		Self::release_deposit(&domain);
+		Self::cancel_rotation(&domain);
+
+		//Deletes the certificate from the reverse lookup map
+		let mut certs = ReverseMap::<T>::take(&old_cert.ip_addr);
+		certs.retain(|x| *x.domain != domain[..]);
+		ReverseMap::<T>::insert(&old_cert.ip_addr, certs);
+
+		Self::deposit_event(RawEvent::CertificateRemoved(old_cert, owner.clone()));
+		Ok(())
+	}
+
+	/// Runs every field of a certificate through its `check_*` function.
+	/// Oversized fields are reported separately, so the caller learns about the cap.
+	fn validate_certificate(
+		name: &[u8],
+		domain: &[u8],
+		ip_addr: &[u8],
+		info: &[u8],
+		key: &[u8],
+	) -> dispatch::DispatchResult {
+		ensure!(name.len() <= MAX_NAME_LENGTH, Error::<T>::NameTooLong);
+		ensure!(info.len() <= MAX_INFO_LENGTH, Error::<T>::InfoTooLong);
+		ensure!(key.len() <= MAX_KEY_LENGTH, Error::<T>::KeyTooLong);
+
+		ensure!(!from_utf8(&check_name(name)).unwrap().contains("Err:"), Error::<T>::InvalidOwner);
+		ensure!(!from_utf8(&check_domain(domain)).unwrap().contains("Err:"), Error::<T>::InvalidDomain);
+		ensure!(!from_utf8(&check_ip(ip_addr)).unwrap().contains("Err:"), Error::<T>::InvalidIP);
+		ensure!(!from_utf8(&check_info(info)).unwrap().contains("Err:"), Error::<T>::InvalidInfo);
+		ensure!(!from_utf8(&check_key(key)).unwrap().contains("Err:"), Error::<T>::InvalidKey);
+		Ok(())
+	}
+
+	/// Swaps `old_cert` for `cert` in both the forward and the reverse lookup maps.
+	fn replace_certificate(old_cert: &Certificate<T::AccountId>, cert: &Certificate<T::AccountId>) {
+		CertificateMap::<T>::insert(&cert.domain, cert.clone());
+		Self::record_history(&cert.domain, Some(cert));
+
+		let mut certs = ReverseMap::<T>::take(&old_cert.ip_addr);
+		certs.retain(|x| x.domain != cert.domain);
+		ReverseMap::<T>::insert(&old_cert.ip_addr, certs);
+		ReverseMap::<T>::append(&cert.ip_addr, cert.clone());
+	}
+
+	/// Appends the version of `domain` that starts with the current extrinsic to its history,
+	/// dropping the oldest versions beyond `MaxHistoryDepth`. `None` records a removal, and
+	/// schedules the history to be deleted once it has aged out.
+	fn record_history(domain: &[u8], cert: Option<&Certificate<T::AccountId>>) {
+		let now = frame_system::Module::<T>::block_number();
+		let version = CertificateVersion {
+			certificate: cert.cloned(),
+			block_number: now,
+			extrinsic_index: frame_system::Module::<T>::extrinsic_index(),
+		};
+		if cert.is_none() {
+			HistoryExpiry::<T>::append(now.saturating_add(T::HistoryRetention::get()), domain.to_vec());
+		}
+		CertificateHistory::<T>::mutate(domain, |history| {
+			history.push(version);
+			let excess = history.len().saturating_sub(T::MaxHistoryDepth::get() as usize);
+			history.drain(..excess);
+		});
+	}
+
+	/// The deposit that must be reserved to keep `cert` on the chain.
+	/// It grows linearly with the certificate's encoded size.
+	pub fn deposit_for(cert: &Certificate<T::AccountId>) -> BalanceOf<T> {
+		let size = cert.encoded_size() as u32;
+		T::CertificateDepositPerByte::get()
+			.saturating_mul(size.into())
+			.saturating_add(T::CertificateDepositBase::get())
+	}
+
+	/// The deposit for `cert` while `rotation` is pending: enough for whichever of its two keys
+	/// makes the larger certificate.
+	fn deposit_with_rotation(
+		cert: &Certificate<T::AccountId>,
+		rotation: Option<&KeyRotation<T::BlockNumber>>,
+	) -> BalanceOf<T> {
+		let deposit = Self::deposit_for(cert);
+		match rotation {
+			Some(rotation) => {
+				let rotated = Certificate { key: rotation.next_key.clone(), ..cert.clone() };
+				deposit.max(Self::deposit_for(&rotated))
+			},
+			None => deposit,
+		}
+	}
+
+	/// Makes `payer` the holder of a `deposit` on `domain`, returning any deposit that was
+	/// previously held for it. Fails without side effects if `payer` can't afford it.
+	fn update_deposit(domain: &[u8], payer: &T::AccountId, deposit: BalanceOf<T>) -> dispatch::DispatchResult {
+		let old = CertificateDeposits::<T>::get(domain);
+		match &old {
+			// Same payer: only reserve (or release) the difference
+			Some((holder, held)) if holder == payer => {
+				if deposit > *held {
+					T::Currency::reserve(payer, deposit - *held)
+						.map_err(|_| Error::<T>::InsufficientDeposit)?;
+				} else {
+					T::Currency::unreserve(payer, *held - deposit);
+				}
+			},
+			_ => {
+				T::Currency::reserve(payer, deposit).map_err(|_| Error::<T>::InsufficientDeposit)?;
+				if let Some((holder, held)) = old {
+					T::Currency::unreserve(&holder, held);
+				}
+			},
+		}
+		CertificateDeposits::<T>::insert(domain, (payer.clone(), deposit));
+		Ok(())
+	}
+
+	/// Returns the deposit held for `domain` to whoever paid it.
+	fn release_deposit(domain: &[u8]) {
+		if let Some((holder, held)) = CertificateDeposits::<T>::take(domain) {
+			T::Currency::unreserve(&holder, held);
+		}
+	}
+
+	/// The scheduler's name for the key activation of `domain`.
+	fn rotation_id(domain: &[u8]) -> Vec<u8> {
+		(KEY_ROTATION_ID, domain).encode()
+	}
+
+	/// Drops the pending key rotation of `domain`, if it has one, along with its activation.
+	fn cancel_rotation(domain: &[u8]) {
+		if KeyRotations::<T>::take(domain).is_some() {
+			let _ = T::Scheduler::cancel_named(Self::rotation_id(domain));
+		}
+	}
+
+	/// Confiscates the deposit held for `domain` and hands it to `T::Slashed`.
+	/// This is meant for certificates that are revoked against their owner's will.
+	fn slash_deposit(domain: &[u8]) {
+		if let Some((holder, held)) = CertificateDeposits::<T>::take(domain) {
+			let (imbalance, _) = T::Currency::slash_reserved(&holder, held);
+			T::Slashed::on_unbalanced(imbalance);
+		}
+	}
+}
@@ -1,8 +1,8 @@
 use crate as pallet_siip;
 use sp_core::H256;
-use frame_support::parameter_types;
+use frame_support::{parameter_types, weights::Weight};
 use sp_runtime::{
-	traits::{BlakeTwo256, IdentityLookup}, testing::Header,
+	traits::{BlakeTwo256, IdentityLookup}, testing::{Header, TestSignature, UintAuthorityId},
 };
 use frame_system as system;
 
@@ -17,7 +17,9 @@ frame_support::construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic,
 	{
 		System: frame_system::{Module, Call, Config, Storage, Event<T>},
-		SiipModule: pallet_siip::{Module, Call, Storage, Event<T>},
+		Balances: pallet_balances::{Module, Call, Storage, Config<T>, Event<T>},
+		Scheduler: pallet_scheduler::{Module, Call, Storage, Event<T>},
+		SiipModule: pallet_siip::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
@@ -44,15 +46,71 @@ impl system::Config for Test {
 	type BlockHashCount = BlockHashCount;
 	type Version = ();
 	type PalletInfo = PalletInfo;
-	type AccountData = ();
+	type AccountData = pallet_balances::AccountData<u64>;
 	type OnNewAccount = ();
 	type OnKilledAccount = ();
 	type SystemWeightInfo = ();
 	type SS58Prefix = SS58Prefix;
 }
 
+parameter_types! {
+	pub const ExistentialDeposit: u64 = 1;
+	pub const MaxLocks: u32 = 50;
+}
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = MaxLocks;
+	type Balance = u64;
+	type Event = Event;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const MaximumSchedulerWeight: Weight = 1_000_000_000;
+	pub const MaxScheduledPerBlock: u32 = 10;
+}
+
+impl pallet_scheduler::Config for Test {
+	type Event = Event;
+	type Origin = Origin;
+	type PalletsOrigin = OriginCaller;
+	type Call = Call;
+	type MaximumWeight = MaximumSchedulerWeight;
+	type ScheduleOrigin = frame_system::EnsureRoot<u64>;
+	type MaxScheduledPerBlock = MaxScheduledPerBlock;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const CertificateDepositBase: u64 = 100;
+	pub const CertificateDepositPerByte: u64 = 1;
+	pub const MinCommitmentAge: u64 = 2;
+	pub const MaxCommitmentAge: u64 = 10;
+	pub const MinKeyOverlap: u64 = 5;
+	pub const MaxHistoryDepth: u32 = 3;
+	pub const HistoryRetention: u64 = 5;
+}
+
 impl pallet_siip::Config for Test {
 	type Event = Event;
+	type Currency = Balances;
+	type CertificateDepositBase = CertificateDepositBase;
+	type CertificateDepositPerByte = CertificateDepositPerByte;
+	type Slashed = ();
+	type Call = Call;
+	type Signature = TestSignature;
+	type Signer = UintAuthorityId;
+	type MinCommitmentAge = MinCommitmentAge;
+	type MaxCommitmentAge = MaxCommitmentAge;
+	type ForceOrigin = frame_system::EnsureRoot<u64>;
+	type Scheduler = Scheduler;
+	type PalletsOrigin = OriginCaller;
+	type MinKeyOverlap = MinKeyOverlap;
+	type MaxHistoryDepth = MaxHistoryDepth;
+	type HistoryRetention = HistoryRetention;
 	fn inflationary_reward() {
 		
 	}
@@ -60,5 +118,22 @@ impl pallet_siip::Config for Test {
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_balances::GenesisConfig::<Test> {
+		// Account 3 can afford the transaction, but not the deposit
+		balances: vec![(1, 10_000), (2, 10_000), (3, 10)],
+	}.assimilate_storage(&mut storage).unwrap();
+	let mut ext: sp_io::TestExternalities = storage.into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+// Moves the chain forward to block `n`, running the pallet's and the scheduler's hooks along the way.
+pub fn run_to_block(n: u64) {
+	use frame_support::traits::OnInitialize;
+	while System::block_number() < n {
+		System::set_block_number(System::block_number() + 1);
+		Scheduler::on_initialize(System::block_number());
+		SiipModule::on_initialize(System::block_number());
+	}
 }
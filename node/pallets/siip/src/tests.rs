@@ -3,6 +3,7 @@ use frame_support::{assert_ok, assert_noop};
 use crate::Certificate;
 use frame_system::ensure_signed;
 use crate::mock::new_test_ext;
+use sp_runtime::testing::TestSignature;
 
 type String = Vec<u8>;
 
@@ -14,13 +15,29 @@ const DOMAIN: &str = "adrianteigen.com";
 const IP_ADDR: &str = "13.49.70.106";
 const INFO: &str ="{ \"Algorithm\": \"RSA\",   \"Key Size\": \"32\",   \"Exponent\": \"65537\" }";
 const KEY: &str = "B4:02:EE:13";
+const SALT: &str = "pepper";
+
+//Commits `owner` to registering `domain`, and waits until the commitment can be revealed
+fn commit(owner: u64, domain: &[u8]) {
+	let commitment = SiipModule::registration_commitment(domain, SALT.as_bytes(), &owner);
+	assert_ok!(SiipModule::commit_registration(Origin::signed(owner), commitment));
+	run_to_block(System::block_number() + MinCommitmentAge::get());
+}
+
+//Registers the default certificate for `owner`, committing to it first
+fn register_default(owner: u64) {
+	commit(owner, DOMAIN.as_bytes());
+	assert_ok!(reveal(Origin::signed(owner), SALT));
+}
 
 #[test]
 fn register_certificate() {
 	new_test_ext().execute_with(|| {
 		//Registers a certificate
-		assert_ok!(SiipModule::register_certificate(
+		commit(1, DOMAIN.as_bytes());
+		assert_ok!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			DOMAIN.into(),
 			IP_ADDR.into(),
@@ -47,18 +64,13 @@ fn register_certificate() {
 fn already_taken() {
 	new_test_ext().execute_with(|| {
 		//Register the first certificate
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		//Ensure that the second one returns an error
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, DOMAIN.as_bytes());
+		assert_noop!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			DOMAIN.into(),
 			IP_ADDR.into(),
@@ -75,8 +87,10 @@ fn invalid_domain() {
 			"Donaudampfschifffahrtselektrizitätenhauptbetriebswerkbauunterbeamtengesellschaft.de".into();
 
 		//Domain name is too long
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, &new_domain);
+		assert_noop!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			new_domain.into(),
 			IP_ADDR.into(),
@@ -86,8 +100,10 @@ fn invalid_domain() {
 
 		//Domain contains an invalid symbol
 		let new_domain: String = "hans*müller.de".into();
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, &new_domain);
+		assert_noop!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			new_domain.into(),
 			IP_ADDR.into(),
@@ -101,8 +117,9 @@ fn invalid_domain() {
 fn invalid_signature() {
 	new_test_ext().execute_with(|| {
 		//Does not provide a signature
-		assert!(SiipModule::register_certificate(
+		assert!(SiipModule::reveal_registration(
 			Origin::none(),
+			SALT.into(),
 			NAME.into(),
 			DOMAIN.into(),
 			IP_ADDR.into(),
@@ -117,8 +134,10 @@ fn uppercase_domain() {
 	new_test_ext().execute_with(|| {
 		//A domain with a domain containing uppercase characters
 		let new_domain: String = "AdrianTeigen.com".into();
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, &new_domain);
+		assert_noop!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			new_domain.into(),
 			IP_ADDR.into(),
@@ -131,14 +150,7 @@ fn uppercase_domain() {
 #[test]
 fn modify_certificate() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		let other_public_key: String = "01:23:45:67:89:AB:CD:EF".into();
 		assert_ok!(SiipModule::modify_certificate(
@@ -167,14 +179,7 @@ fn modify_certificate() {
 #[test]
 fn modify_nonexistant() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		assert_noop!(SiipModule::modify_certificate(
 			Origin::signed(1),
@@ -190,14 +195,7 @@ fn modify_nonexistant() {
 #[test]
 fn modify_uppercase_domain() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		assert_noop!(SiipModule::modify_certificate(
 			Origin::signed(1),
@@ -213,14 +211,7 @@ fn modify_uppercase_domain() {
 #[test]
 fn modify_invalid_signature() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		//Transaction is not signed
 		assert!(SiipModule::modify_certificate(
@@ -237,14 +228,7 @@ fn modify_invalid_signature() {
 #[test]
 fn delete_certificate() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		assert_ok!(SiipModule::remove_certificate(Origin::signed(1), DOMAIN.into()));
 
@@ -263,14 +247,7 @@ fn delete_nonexistent() {
 #[test]
 fn delete_invalid_signature() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 
 		assert!(SiipModule::remove_certificate(Origin::none(), DOMAIN.into()).is_err());
 	})
@@ -279,8 +256,10 @@ fn delete_invalid_signature() {
 #[test]
 fn invalid_json() {
 	new_test_ext().execute_with(|| {
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, DOMAIN.as_bytes());
+		assert_noop!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			DOMAIN.into(),
 			IP_ADDR.into(),
@@ -295,8 +274,10 @@ fn invalid_ip() {
 	new_test_ext().execute_with(|| {
 		let new_ip: String = "2001:0db8:85a3:0000:0000:8a2e:0370:7334".into();
 
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, DOMAIN.as_bytes());
+		assert_noop!(SiipModule::reveal_registration(
 				Origin::signed(1),
+				SALT.into(),
 				NAME.into(),
 				DOMAIN.into(),
 				new_ip.into(),
@@ -305,8 +286,9 @@ fn invalid_ip() {
 			), Error::<Test>::InvalidIP);
 
 		let new_ip: String = "256.256.256.256".into();
-		assert_noop!(SiipModule::register_certificate(
+		assert_noop!(SiipModule::reveal_registration(
 				Origin::signed(1),
+				SALT.into(),
 				NAME.into(),
 				DOMAIN.into(),
 				new_ip.into(),
@@ -315,8 +297,9 @@ fn invalid_ip() {
 			), Error::<Test>::InvalidIP);
 
 		let new_ip: String = "-1.-1.-1.-1".into();
-		assert_noop!(SiipModule::register_certificate(
+		assert_noop!(SiipModule::reveal_registration(
 				Origin::signed(1),
+				SALT.into(),
 				NAME.into(),
 				DOMAIN.into(),
 				new_ip.into(),
@@ -330,8 +313,10 @@ fn invalid_ip() {
 fn odd_length_key() {
 	new_test_ext().execute_with(|| {
 		let new_key: String = "01:23:4".into();
-		assert_ok!(SiipModule::register_certificate(
+		commit(1, DOMAIN.as_bytes());
+		assert_ok!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			DOMAIN.into(),
 			IP_ADDR.into(),
@@ -349,8 +334,10 @@ fn odd_length_key() {
 fn invalid_key() {
 	new_test_ext().execute_with(|| {
 		let new_key: String = "G".into();
-		assert_noop!(SiipModule::register_certificate(
+		commit(1, DOMAIN.as_bytes());
+		assert_noop!(SiipModule::reveal_registration(
 				Origin::signed(1),
+				SALT.into(),
 				NAME.into(),
 				DOMAIN.into(),
 				IP_ADDR.into(),
@@ -359,8 +346,9 @@ fn invalid_key() {
 			), Error::<Test>::InvalidKey);
 
 		let new_key: String = "01/02/56".into();
-		assert_noop!(SiipModule::register_certificate(
+		assert_noop!(SiipModule::reveal_registration(
 				Origin::signed(1),
+				SALT.into(),
 				NAME.into(),
 				DOMAIN.into(),
 				IP_ADDR.into(),
@@ -369,8 +357,9 @@ fn invalid_key() {
 			), Error::<Test>::InvalidKey);
 
 		let new_key: String = ":::::".into();
-		assert_noop!(SiipModule::register_certificate(
+		assert_noop!(SiipModule::reveal_registration(
 				Origin::signed(1),
+				SALT.into(),
 				NAME.into(),
 				DOMAIN.into(),
 				IP_ADDR.into(),
@@ -384,17 +373,12 @@ fn invalid_key() {
 #[test]
 fn reverse_lookup() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 		let new_domain = "website.com";
-		assert_ok!(SiipModule::register_certificate(
+		commit(1, new_domain.as_bytes());
+		assert_ok!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			new_domain.into(),
 			IP_ADDR.into(),
@@ -429,17 +413,12 @@ fn reverse_lookup() {
 #[test]
 fn reverse_lookup_modify() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
-			Origin::signed(1),
-			NAME.into(),
-			DOMAIN.into(),
-			IP_ADDR.into(),
-			INFO.into(),
-			KEY.into()
-		));
+		register_default(1);
 		let new_domain = "website.com";
-		assert_ok!(SiipModule::register_certificate(
+		commit(1, new_domain.as_bytes());
+		assert_ok!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			new_domain.into(),
 			IP_ADDR.into(),
@@ -482,19 +461,36 @@ fn reverse_lookup_modify() {
 }
 
 #[test]
-fn reverse_lookup_remove() {
+fn reverse_lookup_modify_ip() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(SiipModule::register_certificate(
+		register_default(1);
+
+		let new_ip = "10.0.0.1";
+		assert_ok!(SiipModule::modify_certificate(
 			Origin::signed(1),
 			NAME.into(),
 			DOMAIN.into(),
-			IP_ADDR.into(),
+			new_ip.into(),
 			INFO.into(),
-			KEY.into()
+			KEY.into(),
 		));
+
+		//The certificate moves to its new IP, and is no longer found under the old one
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(new_ip)), vec![cert]);
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), vec![]);
+	})
+}
+
+#[test]
+fn reverse_lookup_remove() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
 		let new_domain = "website.com";
-		assert_ok!(SiipModule::register_certificate(
+		commit(1, new_domain.as_bytes());
+		assert_ok!(SiipModule::reveal_registration(
 			Origin::signed(1),
+			SALT.into(),
 			NAME.into(),
 			new_domain.into(),
 			IP_ADDR.into(),
@@ -520,4 +516,817 @@ fn reverse_lookup_remove() {
 		let expected = vec!(cert_1);
 		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), expected);
 	})
-}
\ No newline at end of file
+}
+#[test]
+fn deposit_reserved() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		let deposit = SiipModule::deposit_for(&cert);
+		assert_eq!(deposit, 100 + codec::Encode::encoded_size(&cert) as u64);
+		assert_eq!(Balances::reserved_balance(1), deposit);
+		assert_eq!(SiipModule::certificate_deposit(Vec::<u8>::from(DOMAIN)), Some((1, deposit)));
+	})
+}
+
+#[test]
+fn deposit_insufficient_balance() {
+	new_test_ext().execute_with(|| {
+		commit(3, DOMAIN.as_bytes());
+		assert_noop!(SiipModule::reveal_registration(
+			Origin::signed(3),
+			SALT.into(),
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			KEY.into()
+		), Error::<Test>::InsufficientDeposit);
+	})
+}
+
+#[test]
+fn deposit_adjusted_on_modify() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		let small_deposit = Balances::reserved_balance(1);
+
+		//A longer key takes up more space, so it costs more
+		assert_ok!(SiipModule::modify_certificate(
+			Origin::signed(1),
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			"B4:02:EE:13:01:23:45:67:89:AB:CD:EF".into()
+		));
+		let large_deposit = Balances::reserved_balance(1);
+		assert_eq!(large_deposit, small_deposit + 24);
+
+		//Going back to the shorter key releases the difference
+		assert_ok!(SiipModule::modify_certificate(
+			Origin::signed(1),
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			KEY.into()
+		));
+		assert_eq!(Balances::reserved_balance(1), small_deposit);
+	})
+}
+
+#[test]
+fn deposit_released_on_remove() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		assert!(Balances::reserved_balance(1) > 0);
+
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(1), DOMAIN.into()));
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), 10_000);
+		assert_eq!(SiipModule::certificate_deposit(Vec::<u8>::from(DOMAIN)), None);
+	})
+}
+
+#[test]
+fn oversized_fields() {
+	new_test_ext().execute_with(|| {
+		let long_name: String = vec![b'a'; crate::MAX_NAME_LENGTH + 1];
+		commit(1, DOMAIN.as_bytes());
+		assert_noop!(SiipModule::reveal_registration(
+			Origin::signed(1),
+			SALT.into(),
+			long_name,
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			KEY.into()
+		), Error::<Test>::NameTooLong);
+
+		//A perfectly valid JSON string, just too big
+		let mut long_info: String = vec![b'"'];
+		long_info.extend(vec![b'a'; crate::MAX_INFO_LENGTH]);
+		long_info.push(b'"');
+		assert_noop!(SiipModule::reveal_registration(
+			Origin::signed(1),
+			SALT.into(),
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			long_info,
+			KEY.into()
+		), Error::<Test>::InfoTooLong);
+
+		let long_key: String = "AB:".repeat(crate::MAX_KEY_LENGTH / 3 + 1).trim_end_matches(':').into();
+		assert_noop!(SiipModule::reveal_registration(
+			Origin::signed(1),
+			SALT.into(),
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			long_key
+		), Error::<Test>::KeyTooLong);
+	})
+}
+
+#[test]
+fn oversized_fields_report_cap() {
+	let long_info = vec![b'1'; crate::MAX_INFO_LENGTH + 1];
+	let criteria = crate::check_info(&long_info);
+	assert_eq!(core::str::from_utf8(&criteria).unwrap(), format!("Err: Must be at most {} bytes long\n", crate::MAX_INFO_LENGTH));
+
+	let criteria = crate::check_name(NAME.as_bytes());
+	assert!(core::str::from_utf8(&criteria).unwrap().contains(&format!("Ok: Must be at most {} bytes long\n", crate::MAX_NAME_LENGTH)));
+}
+
+#[test]
+fn validation_failures_are_named() {
+	let failures = |ip_addr: &str, key: &str| crate::validation_failures(
+		NAME.as_bytes(), DOMAIN.as_bytes(), ip_addr.as_bytes(), INFO.as_bytes(), key.as_bytes(),
+	);
+	assert!(failures(IP_ADDR, KEY).is_empty());
+
+	//Each failed criterion is prefixed with its field, and the fields that pass add nothing
+	let failures = failures("10.0.0", "b4:02:ee:13");
+	assert!(failures.iter().any(|f| f.starts_with("key: ") && f.contains("uppercase")), "{:?}", failures);
+	assert!(failures.iter().any(|f| f.starts_with("ip: ")), "{:?}", failures);
+	assert!(!failures.iter().any(|f| f.starts_with("domain: ")), "{:?}", failures);
+}
+
+//The last block sponsored calls in these tests are signed for
+const VALID_UNTIL: u64 = 20;
+
+//The reveal of a registration that `domain`'s owner has committed to with `SALT`
+fn sponsored_register(domain: &str) -> Box<Call> {
+	Box::new(Call::SiipModule(crate::Call::reveal_registration(
+		SALT.into(),
+		NAME.into(),
+		domain.into(),
+		IP_ADDR.into(),
+		INFO.into(),
+		KEY.into()
+	)))
+}
+
+#[test]
+fn sponsored_registration() {
+	new_test_ext().execute_with(|| {
+		//Account 3 can't afford the deposit, so account 1 pays for it
+		commit(3, DOMAIN.as_bytes());
+		let call = sponsored_register(DOMAIN);
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&1, &3, VALID_UNTIL, &call));
+		assert_ok!(SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call));
+
+		//The certificate belongs to the owner...
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert.owner_id, 3);
+
+		//...but the sponsor holds the deposit
+		assert_eq!(Balances::reserved_balance(1), SiipModule::deposit_for(&cert));
+		assert_eq!(Balances::reserved_balance(3), 0);
+		assert_eq!(SiipModule::sponsorship_nonce(3), 1);
+
+		//The owner can manage the certificate itself, and removing it refunds the sponsor
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(3), DOMAIN.into()));
+		assert_eq!(Balances::reserved_balance(1), 0);
+	})
+}
+
+#[test]
+fn sponsored_replay() {
+	new_test_ext().execute_with(|| {
+		commit(3, DOMAIN.as_bytes());
+		let call = sponsored_register(DOMAIN);
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&1, &3, VALID_UNTIL, &call));
+		assert_ok!(SiipModule::sponsored_call(Origin::signed(1), 3, signature.clone(), VALID_UNTIL, call.clone()));
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(3), DOMAIN.into()));
+
+		//The nonce has moved on, so the same signature can't register the domain again
+		assert_noop!(
+			SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call),
+			Error::<Test>::InvalidSponsorSignature
+		);
+	})
+}
+
+#[test]
+fn sponsored_expiry() {
+	new_test_ext().execute_with(|| {
+		let call = sponsored_register(DOMAIN);
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&1, &3, 4, &call));
+
+		//The sponsor can't extend the signature's validity...
+		assert_noop!(
+			SiipModule::sponsored_call(Origin::signed(1), 3, signature.clone(), VALID_UNTIL, call.clone()),
+			Error::<Test>::InvalidSponsorSignature
+		);
+
+		//...nor use it once it has expired
+		run_to_block(5);
+		assert_noop!(
+			SiipModule::sponsored_call(Origin::signed(1), 3, signature, 4, call),
+			Error::<Test>::SponsorshipExpired
+		);
+	})
+}
+
+#[test]
+fn sponsored_wrong_signer() {
+	new_test_ext().execute_with(|| {
+		let call = sponsored_register(DOMAIN);
+
+		//Signed by someone other than the owner
+		let signature = TestSignature(2, SiipModule::sponsorship_payload(&1, &3, VALID_UNTIL, &call));
+		assert_noop!(
+			SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call.clone()),
+			Error::<Test>::InvalidSponsorSignature
+		);
+
+		//Signed for a different sponsor
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&2, &3, VALID_UNTIL, &call));
+		assert_noop!(
+			SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call),
+			Error::<Test>::InvalidSponsorSignature
+		);
+	})
+}
+
+#[test]
+fn sponsored_non_certificate_call() {
+	new_test_ext().execute_with(|| {
+		let call = Box::new(Call::Balances(pallet_balances::Call::transfer(1, 1_000)));
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&1, &3, VALID_UNTIL, &call));
+		assert_noop!(
+			SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call),
+			Error::<Test>::NotSponsorable
+		);
+	})
+}
+
+fn reveal(origin: Origin, salt: &str) -> frame_support::dispatch::DispatchResult {
+	SiipModule::reveal_registration(
+		origin,
+		salt.into(),
+		NAME.into(),
+		DOMAIN.into(),
+		IP_ADDR.into(),
+		INFO.into(),
+		KEY.into()
+	)
+}
+
+#[test]
+fn commit_reveal_registration() {
+	new_test_ext().execute_with(|| {
+		let commitment = SiipModule::registration_commitment(DOMAIN.as_bytes(), SALT.as_bytes(), &1);
+		assert_ok!(SiipModule::commit_registration(Origin::signed(1), commitment));
+
+		//Revealing right away would let a front-runner react within the same block
+		assert_noop!(reveal(Origin::signed(1), SALT), Error::<Test>::CommitmentTooNew);
+
+		run_to_block(3);
+		assert_ok!(reveal(Origin::signed(1), SALT));
+
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert.owner_id, 1);
+		assert_eq!(SiipModule::commitment(1, commitment), None);
+	})
+}
+
+#[test]
+fn reveal_without_commitment() {
+	new_test_ext().execute_with(|| {
+		let commitment = SiipModule::registration_commitment(DOMAIN.as_bytes(), SALT.as_bytes(), &1);
+		assert_ok!(SiipModule::commit_registration(Origin::signed(1), commitment));
+		run_to_block(3);
+
+		//Wrong salt
+		assert_noop!(reveal(Origin::signed(1), "paprika"), Error::<Test>::NoCommitment);
+
+		//Someone who saw the commitment can't reveal it for themselves
+		assert_noop!(reveal(Origin::signed(2), SALT), Error::<Test>::NoCommitment);
+
+		//...nor take the domain by copying the commitment
+		assert_ok!(SiipModule::commit_registration(Origin::signed(2), commitment));
+		run_to_block(6);
+		assert_noop!(reveal(Origin::signed(2), SALT), Error::<Test>::NoCommitment);
+		assert_ok!(reveal(Origin::signed(1), SALT));
+	})
+}
+
+#[test]
+fn duplicate_commitment() {
+	new_test_ext().execute_with(|| {
+		let commitment = SiipModule::registration_commitment(DOMAIN.as_bytes(), SALT.as_bytes(), &1);
+		assert_ok!(SiipModule::commit_registration(Origin::signed(1), commitment));
+		assert_noop!(
+			SiipModule::commit_registration(Origin::signed(1), commitment),
+			Error::<Test>::CommitmentAlreadyExists
+		);
+	})
+}
+
+#[test]
+fn stale_commitment_expires() {
+	new_test_ext().execute_with(|| {
+		let commitment = SiipModule::registration_commitment(DOMAIN.as_bytes(), SALT.as_bytes(), &1);
+		assert_ok!(SiipModule::commit_registration(Origin::signed(1), commitment));
+
+		//Still valid at exactly the maximum age
+		run_to_block(11);
+		assert_eq!(SiipModule::commitment(1, commitment), Some(1));
+
+		//One block later it is pruned, and can no longer be revealed
+		run_to_block(12);
+		assert_eq!(SiipModule::commitment(1, commitment), None);
+		assert_noop!(reveal(Origin::signed(1), SALT), Error::<Test>::NoCommitment);
+	})
+}
+
+#[test]
+fn sponsored_commit_reveal() {
+	new_test_ext().execute_with(|| {
+		let commitment = SiipModule::registration_commitment(DOMAIN.as_bytes(), SALT.as_bytes(), &3);
+		let call = Box::new(Call::SiipModule(crate::Call::commit_registration(commitment)));
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&1, &3, VALID_UNTIL, &call));
+		assert_ok!(SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call));
+
+		run_to_block(3);
+		let call = Box::new(Call::SiipModule(crate::Call::reveal_registration(
+			SALT.into(),
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			KEY.into()
+		)));
+		let signature = TestSignature(3, SiipModule::sponsorship_payload(&1, &3, VALID_UNTIL, &call));
+		assert_ok!(SiipModule::sponsored_call(Origin::signed(1), 3, signature, VALID_UNTIL, call));
+
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert.owner_id, 3);
+		assert_eq!(Balances::reserved_balance(1), SiipModule::deposit_for(&cert));
+	})
+}
+
+#[test]
+fn force_remove() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		let deposit = Balances::reserved_balance(1);
+		let issuance = Balances::total_issuance();
+
+		//Only governance can revoke someone else's certificate
+		assert!(SiipModule::force_remove(Origin::signed(2), DOMAIN.into()).is_err());
+		assert_ok!(SiipModule::force_remove(Origin::root(), DOMAIN.into()));
+
+		let empty_cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert!(empty_cert.version_number == EMPTY_CERTIFICATE);
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), vec![]);
+
+		//The deposit is slashed, not returned
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), 10_000 - deposit);
+		assert_eq!(Balances::total_issuance(), issuance - deposit);
+	})
+}
+
+#[test]
+fn force_transfer() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+
+		assert!(SiipModule::force_transfer(Origin::signed(2), DOMAIN.into(), 2).is_err());
+		assert_noop!(
+			SiipModule::force_transfer(Origin::root(), DOMAIN.into(), 1),
+			Error::<Test>::SameOwner
+		);
+		assert_ok!(SiipModule::force_transfer(Origin::root(), DOMAIN.into(), 2));
+
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert.owner_id, 2);
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), vec![cert]);
+
+		//The new owner is in control; the old one is not
+		assert_noop!(
+			SiipModule::remove_certificate(Origin::signed(1), DOMAIN.into()),
+			Error::<Test>::DifferentOwner
+		);
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(2), DOMAIN.into()));
+
+		//The deposit goes back to whoever paid it
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), 10_000);
+	})
+}
+
+#[test]
+fn force_register() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+
+		let new_ip = "10.0.0.1";
+		assert!(SiipModule::force_register(
+			Origin::signed(2),
+			2,
+			NAME.into(),
+			DOMAIN.into(),
+			new_ip.into(),
+			INFO.into(),
+			KEY.into()
+		).is_err());
+		assert_ok!(SiipModule::force_register(
+			Origin::root(),
+			2,
+			NAME.into(),
+			DOMAIN.into(),
+			new_ip.into(),
+			INFO.into(),
+			KEY.into()
+		));
+
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert.owner_id, 2);
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), vec![]);
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(new_ip)), vec![cert]);
+
+		//The previous owner's deposit is slashed, and the new owner pays none
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert!(Balances::free_balance(1) < 10_000);
+		assert_eq!(SiipModule::certificate_deposit(Vec::<u8>::from(DOMAIN)), None);
+	})
+}
+
+#[test]
+fn force_calls_emit_events() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(SiipModule::force_register(
+			Origin::root(),
+			1,
+			NAME.into(),
+			DOMAIN.into(),
+			IP_ADDR.into(),
+			INFO.into(),
+			KEY.into()
+		));
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert!(System::events().iter().any(|record| record.event ==
+			Event::pallet_siip(crate::RawEvent::CertificateForceRegistered(cert.clone()))));
+
+		assert_ok!(SiipModule::force_transfer(Origin::root(), DOMAIN.into(), 2));
+		let transferred = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert!(System::events().iter().any(|record| record.event ==
+			Event::pallet_siip(crate::RawEvent::CertificateForceTransferred(transferred.clone(), 1))));
+
+		assert_ok!(SiipModule::force_remove(Origin::root(), DOMAIN.into()));
+		assert!(System::events().iter().any(|record| record.event ==
+			Event::pallet_siip(crate::RawEvent::CertificateForceRemoved(transferred.clone()))));
+	})
+}
+
+fn genesis_ext(certificates: Vec<(u64, &str, &str, &str, &str, &str)>) -> sp_io::TestExternalities {
+	let certificates = certificates.into_iter()
+		.map(|(owner, name, domain, ip_addr, info, key)| (owner, name.into(), domain.into(), ip_addr.into(), info.into(), key.into()))
+		.collect();
+	let mut storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_balances::GenesisConfig::<Test> {
+		balances: vec![(1, 10_000), (2, 10_000)],
+	}.assimilate_storage(&mut storage).unwrap();
+	crate::GenesisConfig::<Test> { certificates }.assimilate_storage(&mut storage).unwrap();
+	storage.into()
+}
+
+#[test]
+fn genesis_certificates() {
+	genesis_ext(vec![
+		(1, NAME, DOMAIN, IP_ADDR, INFO, KEY),
+		(2, "Sam Bove", "sambove.com", IP_ADDR, "{}", "AB:CD"),
+	]).execute_with(|| {
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert, Certificate {
+			version_number: CERTIFICATE_VERSION,
+			owner_id: 1,
+			name: NAME.into(),
+			key: KEY.into(),
+			info: INFO.into(),
+			ip_addr: IP_ADDR.into(),
+			domain: DOMAIN.into(),
+		});
+		let other = SiipModule::domain_to_certificate(Vec::<u8>::from("sambove.com"));
+		assert_eq!(other.owner_id, 2);
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), vec![cert, other]);
+
+		//Genesis certificates hold no deposit, but can be managed like any other
+		assert_eq!(SiipModule::certificate_deposit(Vec::<u8>::from(DOMAIN)), None);
+		assert_ok!(SiipModule::force_remove(Origin::root(), DOMAIN.into()));
+		assert!(!crate::CertificateMap::<Test>::contains_key(Vec::<u8>::from(DOMAIN)));
+	})
+}
+
+#[test]
+#[should_panic(expected = "Genesis certificate for Not-A-Domain is invalid")]
+fn invalid_genesis_certificate() {
+	genesis_ext(vec![(1, NAME, "Not-A-Domain", IP_ADDR, INFO, KEY)]);
+}
+
+#[test]
+#[should_panic(expected = "Genesis certificate for adrianteigen.com is duplicated")]
+fn duplicate_genesis_certificate() {
+	genesis_ext(vec![(1, NAME, DOMAIN, IP_ADDR, INFO, KEY), (2, NAME, DOMAIN, "10.0.0.1", INFO, KEY)]);
+}
+
+const NEXT_KEY: &str = "B4:02:EE:13:9A:7C";
+
+#[test]
+fn rotate_key() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		let old_cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+
+		//Both keys are valid from the moment the rotation is scheduled
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 8));
+		assert_eq!(SiipModule::key_rotation(Vec::<u8>::from(DOMAIN)),
+			Some(crate::KeyRotation { next_key: NEXT_KEY.into(), activation: 8 }));
+		assert_eq!(SiipModule::valid_keys(DOMAIN.as_bytes()), vec![String::from(KEY), String::from(NEXT_KEY)]);
+		assert!(System::events().iter().any(|record| record.event ==
+			Event::pallet_siip(crate::RawEvent::KeyRotationScheduled(old_cert.clone(), NEXT_KEY.into(), 8))));
+
+		run_to_block(7);
+		assert_eq!(SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN)).key, String::from(KEY));
+
+		//The scheduler swaps the keys at the activation block
+		run_to_block(8);
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		assert_eq!(cert, Certificate { key: NEXT_KEY.into(), ..old_cert.clone() });
+		assert_eq!(SiipModule::ip_to_certificates(Vec::<u8>::from(IP_ADDR)), vec![cert.clone()]);
+		assert_eq!(SiipModule::key_rotation(Vec::<u8>::from(DOMAIN)), None);
+		assert_eq!(SiipModule::valid_keys(DOMAIN.as_bytes()), vec![String::from(NEXT_KEY)]);
+		assert!(System::events().iter().any(|record| record.event ==
+			Event::pallet_siip(crate::RawEvent::KeyRotated(cert.clone(), old_cert.clone()))));
+	})
+}
+
+#[test]
+fn rotate_key_errors() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 8),
+			Error::<Test>::NonexistentDomain
+		);
+		register_default(1);
+
+		//The overlap must last at least MinKeyOverlap blocks
+		assert_noop!(
+			SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 7),
+			Error::<Test>::ActivationTooSoon
+		);
+		assert_noop!(
+			SiipModule::rotate_key(Origin::signed(2), DOMAIN.into(), NEXT_KEY.into(), 8),
+			Error::<Test>::DifferentOwner
+		);
+		assert_noop!(
+			SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), KEY.into(), 8),
+			Error::<Test>::NoModifications
+		);
+		assert_noop!(
+			SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), "b4:02".into(), 8),
+			Error::<Test>::InvalidKey
+		);
+
+		//Only the scheduler activates keys, and only once they are due
+		assert_noop!(
+			SiipModule::activate_key(Origin::signed(1), DOMAIN.into()),
+			sp_runtime::DispatchError::BadOrigin
+		);
+		assert_noop!(SiipModule::activate_key(Origin::root(), DOMAIN.into()), Error::<Test>::NoKeyRotation);
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 8));
+		assert_noop!(SiipModule::activate_key(Origin::root(), DOMAIN.into()), Error::<Test>::NoKeyRotation);
+	})
+}
+
+#[test]
+fn rotate_key_rescheduled() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), "AA:BB".into(), 8));
+
+		//A second rotation replaces the first, activation and all
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 10));
+		run_to_block(9);
+		assert_eq!(SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN)).key, String::from(KEY));
+		run_to_block(10);
+		assert_eq!(SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN)).key, String::from(NEXT_KEY));
+	})
+}
+
+#[test]
+fn rotate_key_cancelled() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+
+		//Modifying anything but the key leaves the rotation pending
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 8));
+		assert_ok!(SiipModule::modify_certificate(
+			Origin::signed(1),
+			NAME.into(),
+			DOMAIN.into(),
+			"10.0.0.1".into(),
+			INFO.into(),
+			KEY.into()
+		));
+		assert!(SiipModule::key_rotation(Vec::<u8>::from(DOMAIN)).is_some());
+
+		//A new key supersedes it
+		assert_ok!(SiipModule::modify_certificate(
+			Origin::signed(1),
+			NAME.into(),
+			DOMAIN.into(),
+			"10.0.0.1".into(),
+			INFO.into(),
+			"AA:BB".into()
+		));
+		assert_eq!(SiipModule::key_rotation(Vec::<u8>::from(DOMAIN)), None);
+		run_to_block(8);
+		assert_eq!(SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN)).key, String::from("AA:BB"));
+
+		//So do removals and transfers
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 14));
+		assert_ok!(SiipModule::force_transfer(Origin::root(), DOMAIN.into(), 2));
+		assert_eq!(SiipModule::key_rotation(Vec::<u8>::from(DOMAIN)), None);
+		assert_ok!(SiipModule::rotate_key(Origin::signed(2), DOMAIN.into(), NEXT_KEY.into(), 14));
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(2), DOMAIN.into()));
+		assert_eq!(SiipModule::key_rotation(Vec::<u8>::from(DOMAIN)), None);
+		run_to_block(14);
+		assert!(!crate::CertificateMap::<Test>::contains_key(Vec::<u8>::from(DOMAIN)));
+	})
+}
+
+#[test]
+fn rotate_key_deposit() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		let rotated = Certificate { key: NEXT_KEY.into(), ..cert.clone() };
+		assert!(SiipModule::deposit_for(&rotated) > SiipModule::deposit_for(&cert));
+
+		//The larger key is paid for while both are held
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), NEXT_KEY.into(), 8));
+		assert_eq!(Balances::reserved_balance(1), SiipModule::deposit_for(&rotated));
+		run_to_block(8);
+		assert_eq!(Balances::reserved_balance(1), SiipModule::deposit_for(&rotated));
+
+		//A smaller next key only releases the difference once it takes over
+		assert_ok!(SiipModule::rotate_key(Origin::signed(1), DOMAIN.into(), KEY.into(), 13));
+		assert_eq!(Balances::reserved_balance(1), SiipModule::deposit_for(&rotated));
+		run_to_block(13);
+		assert_eq!(Balances::reserved_balance(1), SiipModule::deposit_for(&cert));
+	})
+}
+
+#[test]
+fn rotate_key_sponsored() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		let call = Call::SiipModule(crate::Call::rotate_key(DOMAIN.into(), NEXT_KEY.into(), 8));
+		let payload = SiipModule::sponsorship_payload(&2, &1, VALID_UNTIL, &call);
+		assert_ok!(SiipModule::sponsored_call(Origin::signed(2), 1, TestSignature(1, payload), VALID_UNTIL, Box::new(call)));
+
+		//The sponsor holds the deposit from now on
+		let cert = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+		let rotated = Certificate { key: NEXT_KEY.into(), ..cert };
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::reserved_balance(2), SiipModule::deposit_for(&rotated));
+		run_to_block(8);
+		assert_eq!(SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN)).key, String::from(NEXT_KEY));
+	})
+}
+
+#[test]
+fn certificate_history() {
+	new_test_ext().execute_with(|| {
+		System::set_extrinsic_index(2);
+		register_default(1);
+		let registered = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+
+		run_to_block(5);
+		System::set_extrinsic_index(0);
+		assert_ok!(SiipModule::modify_certificate(
+			Origin::signed(1),
+			NAME.into(),
+			DOMAIN.into(),
+			"10.0.0.1".into(),
+			INFO.into(),
+			KEY.into()
+		));
+		let modified = SiipModule::domain_to_certificate(Vec::<u8>::from(DOMAIN));
+
+		run_to_block(6);
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(1), DOMAIN.into()));
+
+		//Each version keeps the block and extrinsic that made it, and removals are kept too
+		assert_eq!(SiipModule::certificate_history(Vec::<u8>::from(DOMAIN)), vec![
+			crate::CertificateVersion { certificate: Some(registered), block_number: 3, extrinsic_index: Some(2) },
+			crate::CertificateVersion { certificate: Some(modified), block_number: 5, extrinsic_index: Some(0) },
+			crate::CertificateVersion { certificate: None, block_number: 6, extrinsic_index: Some(0) },
+		]);
+	})
+}
+
+#[test]
+fn certificate_history_is_bounded() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		for ip in &["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+			assert_ok!(SiipModule::modify_certificate(
+				Origin::signed(1),
+				NAME.into(),
+				DOMAIN.into(),
+				(*ip).into(),
+				INFO.into(),
+				KEY.into()
+			));
+		}
+
+		//Only the latest MaxHistoryDepth versions are kept
+		let ips: Vec<String> = SiipModule::certificate_history(Vec::<u8>::from(DOMAIN)).into_iter()
+			.map(|version| version.certificate.unwrap().ip_addr)
+			.collect();
+		assert_eq!(ips, vec![String::from("10.0.0.1"), String::from("10.0.0.2"), String::from("10.0.0.3")]);
+	})
+}
+
+#[test]
+fn certificate_history_outside_extrinsics() {
+	genesis_ext(vec![(1, NAME, DOMAIN, IP_ADDR, INFO, KEY)]).execute_with(|| {
+		let history = SiipModule::certificate_history(Vec::<u8>::from(DOMAIN));
+		assert_eq!(history.len(), 1);
+		assert_eq!((history[0].block_number, history[0].extrinsic_index), (0, None));
+
+		//Governance calls and scheduled key activations are recorded like any other change
+		System::set_block_number(1);
+		assert_ok!(SiipModule::force_transfer(Origin::root(), DOMAIN.into(), 2));
+		assert_ok!(SiipModule::rotate_key(Origin::signed(2), DOMAIN.into(), NEXT_KEY.into(), 6));
+		run_to_block(6);
+		let history = SiipModule::certificate_history(Vec::<u8>::from(DOMAIN));
+		assert_eq!(history.len(), 3);
+		assert_eq!(history[1].certificate.as_ref().unwrap().owner_id, 2);
+		assert_eq!(history[2].certificate.as_ref().unwrap().key, String::from(NEXT_KEY));
+		assert_eq!(history[2].block_number, 6);
+	})
+}
+
+#[test]
+fn removed_domain_history_ages_out() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		run_to_block(4);
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(1), DOMAIN.into()));
+
+		//Kept for HistoryRetention blocks after the removal, then deleted
+		run_to_block(8);
+		assert_eq!(SiipModule::certificate_history(Vec::<u8>::from(DOMAIN)).len(), 2);
+		run_to_block(9);
+		assert!(SiipModule::certificate_history(Vec::<u8>::from(DOMAIN)).is_empty());
+	})
+}
+
+#[test]
+fn reregistered_domain_history_is_kept() {
+	new_test_ext().execute_with(|| {
+		register_default(1);
+		run_to_block(4);
+		assert_ok!(SiipModule::remove_certificate(Origin::signed(1), DOMAIN.into()));
+		run_to_block(5);
+		register_default(2);
+
+		//The domain is in use again, so its history outlives the removal's retention
+		run_to_block(9);
+		let history = SiipModule::certificate_history(Vec::<u8>::from(DOMAIN));
+		assert_eq!(history.len(), 3);
+		assert_eq!(history[2].certificate.as_ref().unwrap().owner_id, 2);
+	})
+}
+
+#[test]
+fn validators_reject_malformed_fields() {
+	let accepted = |criteria: Vec<u8>| !core::str::from_utf8(&criteria).unwrap().contains("Err:");
+
+	//A domain needs something before its TLD
+	assert!(accepted(crate::check_domain(b"a.com")));
+	assert!(!accepted(crate::check_domain(b".com")));
+	assert!(!accepted(crate::check_domain(b"com")));
+
+	//Octets are plain decimal
+	assert!(accepted(crate::check_ip(b"0.10.100.255")));
+	assert!(!accepted(crate::check_ip(b"+1.2.3.4")));
+	assert!(!accepted(crate::check_ip(b"01.2.3.4")));
+
+	//Keys are whole pairs of digits
+	assert!(accepted(crate::check_key(KEY.as_bytes())));
+	assert!(!accepted(crate::check_key(b"")));
+	assert!(!accepted(crate::check_key(b"B4:02:EE:1")));
+	assert!(!accepted(crate::check_key(b"B4:02:EE:")));
+}
@@ -44,9 +44,13 @@ use pallet_transaction_payment::CurrencyAdapter;
 
 mod reward_miner;
 
+#[cfg(test)]
+mod tests;
+
 /// Import the SIIP pallet.
 pub use pallet_siip;
 pub use pallet_balances;
+pub use pallet_checkpoint;
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -103,10 +107,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("siip-node"),
 	impl_name: create_runtime_str!("siip-node"),
 	authoring_version: 1,
-	spec_version: 100,
+	spec_version: 116,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 8,
 };
 
 
@@ -234,7 +238,9 @@ impl pallet_balances::Config for Runtime {
 }
 
 parameter_types! {
-	pub const TransactionByteFee: Balance = 0;
+	// Every byte of an extrinsic is charged on top of the flat fee, so storing a large
+	// certificate costs more than storing a small one. 100 bytes cost about one unit.
+	pub const TransactionByteFee: Balance = 10 * MILLICENTS;
 }
 
 pub struct ConstFee;
@@ -264,9 +270,40 @@ impl pallet_sudo::Config for Runtime {
 
 use frame_support::traits::Currency;
 const MAX_TOKENS: Balance = 1_000_000_000_000_000_000u128; // note that 1_000_000_000_000 = 1 unit
+
+parameter_types! {
+	// Ten units to hold a domain, plus one unit per kilobyte of certificate
+	pub const CertificateDepositBase: Balance = 10 * CENTS;
+	pub const CertificateDepositPerByte: Balance = 1 * MILLICENTS;
+	// Long enough that the commitment can't be front-run in the block that reveals it
+	pub const MinCommitmentAge: BlockNumber = 3;
+	pub const MaxCommitmentAge: BlockNumber = 1 * DAYS;
+	// Longer than clients are expected to cache a certificate
+	pub const MinKeyOverlap: BlockNumber = 1 * HOURS;
+	pub const MaxHistoryDepth: u32 = 16;
+	pub const HistoryRetention: BlockNumber = 7 * DAYS;
+}
+
 /// Configure the SIIP pallet in pallets/siip.
 impl pallet_siip::Config for Runtime {
 	type Event = Event;
+	type Currency = Balances;
+	type CertificateDepositBase = CertificateDepositBase;
+	type CertificateDepositPerByte = CertificateDepositPerByte;
+	// There is no treasury, so slashed deposits are burned
+	type Slashed = ();
+	type Call = Call;
+	type Signature = Signature;
+	type Signer = <Signature as Verify>::Signer;
+	type MinCommitmentAge = MinCommitmentAge;
+	type MaxCommitmentAge = MaxCommitmentAge;
+	// Root is what a passed democracy referendum dispatches as
+	type ForceOrigin = EnsureRoot<AccountId>;
+	type Scheduler = Scheduler;
+	type PalletsOrigin = OriginCaller;
+	type MinKeyOverlap = MinKeyOverlap;
+	type MaxHistoryDepth = MaxHistoryDepth;
+	type HistoryRetention = HistoryRetention;
 	fn inflationary_reward() {
 		reward_miner::get_block_miner::<Runtime>().map(|minerID| {
 			let total_issuance = Balances::total_issuance();
@@ -278,6 +315,18 @@ impl pallet_siip::Config for Runtime {
 	}
 }
 
+parameter_types! {
+	pub const MaxCheckpointSigners: u32 = 32;
+}
+
+/// Configure the checkpoint pallet in pallets/checkpoint.
+impl pallet_checkpoint::Config for Runtime {
+	type Event = Event;
+	// Root is what a passed democracy referendum dispatches as
+	type SignerOrigin = EnsureRoot<AccountId>;
+	type MaxSigners = MaxCheckpointSigners;
+}
+
 const MILLICENTS: Balance = 1_000_000_000;
 const CENTS: Balance = 1_000 * MILLICENTS;    // assume this is worth about a cent.
 const DOLLARS: Balance = 100 * CENTS;
@@ -383,7 +432,8 @@ construct_runtime!(
 		Scheduler: pallet_scheduler::{Module, Call, Storage, Event<T>},
 		Democracy: pallet_democracy::{Module, Call, Storage, Config, Event<T>},
 		// Include the custom logic from the SIIP pallet in the runtime.
-		SiipModule: pallet_siip::{Module, Call, Storage, Event<T>},
+		SiipModule: pallet_siip::{Module, Call, Storage, Config<T>, Event<T>},
+		Checkpoint: pallet_checkpoint::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
@@ -529,6 +579,30 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl pallet_siip_runtime_api::SiipApi<Block, AccountId, BlockNumber> for Runtime {
+		fn certificate(domain: Vec<u8>) -> Option<pallet_siip::Certificate<AccountId>> {
+			SiipModule::certificate(&domain)
+		}
+
+		fn certificates_by_ip(ip_addr: Vec<u8>) -> Vec<pallet_siip::Certificate<AccountId>> {
+			SiipModule::ip_to_certificates(ip_addr)
+		}
+
+		fn key_rotation(domain: Vec<u8>) -> Option<pallet_siip::KeyRotation<BlockNumber>> {
+			SiipModule::key_rotation(domain)
+		}
+
+		fn certificate_history(domain: Vec<u8>) -> Vec<pallet_siip::CertificateVersion<AccountId, BlockNumber>> {
+			SiipModule::certificate_history(domain)
+		}
+	}
+
+	impl pallet_checkpoint_runtime_api::CheckpointApi<Block, BlockNumber, Hash> for Runtime {
+		fn last_checkpoint() -> Option<pallet_checkpoint::Checkpoint<BlockNumber, Hash>> {
+			Checkpoint::last_checkpoint()
+		}
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	impl frame_benchmarking::Benchmark<Block> for Runtime {
 		fn dispatch_benchmark(
@@ -569,59 +643,9 @@ impl_runtime_apis! {
 	}
 }
 
-pub fn test_construct_block(
-	number: BlockNumber,
-	parent_hash: Hash,
-	extrinsics: Vec<UncheckedExtrinsic>
-) -> Result<Block, TransactionValidityError> {
-	
-	let calls: Vec<Call> = extrinsics.clone().into_iter().map(|x| x.function).collect();
-	let extrinsics_root = frame_system::extrinsics_root::<BlakeTwo256, _>(&calls[..]);
-
-	// let extrinsics_root = sp_trie::TrieConfiguration::ordered_trie_root(extrinsics.iter().map(|x| x.function).map(codec::Encode::encode)).to_fixed_bytes().into();
-
-	let header = Header {
-		parent_hash,
-		number,
-		state_root: Default::default(),
-		extrinsics_root,
-		digest: Default::default()
-	};
-
-	// let block = Block {
-	// 	header.clone(),
-	// 	extrinsics
-	// };
-
-	Executive::initialize_block(&header);
-
-
-	for extrinsic in extrinsics.iter() {
-		let res = Executive::apply_extrinsic(extrinsic.clone());
-		match res {
-			Ok(sub_res) => {
-				match sub_res {
-					Ok(_) => {},
-					Err(e) => panic!("Dispatch error: {:?}", e)
-				}
-			},
-			Err(e) => return Err(e)
-		}
-
-	}
-
-	let header = Executive::finalize_block();
-
-	Ok(Block {
-		header,
-		extrinsics
-	})
-}
-
-// #[cfg(test)]
-pub fn test_block_hash(i: u32) -> Hash {
-	// frame_system::Pallet::<Runtime>::block_hash(i)
-	frame_system::Pallet::<Runtime>::parent_hash()
+/// The hash of block `i`, once a later block has been initialized; genesis's is known from the start.
+pub fn test_block_hash(i: BlockNumber) -> Hash {
+	frame_system::Pallet::<Runtime>::block_hash(i)
 }
 
 use sp_runtime::traits::Checkable;
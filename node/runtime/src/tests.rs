@@ -0,0 +1,264 @@
+//! End-to-end tests of the runtime: real signed extrinsics, applied through `Executive` in blocks
+//! carrying a miner's pre-runtime digest, as the node executes them.
+
+use super::*;
+use codec::Encode;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_core::{sr25519, Pair};
+use sp_runtime::{DigestItem, DispatchError, transaction_validity::InvalidTransaction};
+use std::collections::BTreeMap;
+
+const ENDOWMENT: Balance = 1 << 50;
+
+const NAME: &str = "Adrian Teigen";
+const DOMAIN: &str = "adrianteigen.com";
+const IP_ADDR: &str = "13.49.70.106";
+const INFO: &str = "{ \"Algorithm\": \"RSA\",   \"Key Size\": \"32\",   \"Exponent\": \"65537\" }";
+const KEY: &str = "B4:02:EE:13";
+
+fn pair(name: &str) -> sr25519::Pair {
+	sr25519::Pair::from_string(&format!("//{}", name), None).expect("static values are valid; qed")
+}
+
+fn account(name: &str) -> AccountId {
+	<Signature as Verify>::Signer::from(pair(name).public()).into_account()
+}
+
+fn free(name: &str) -> Balance {
+	Balances::free_balance(account(name))
+}
+
+fn reserved(name: &str) -> Balance {
+	Balances::reserved_balance(account(name))
+}
+
+/// Alice, Bob and Charlie hold `ENDOWMENT` each; Broke holds nothing.
+fn new_test_ext() -> sp_io::TestExternalities {
+	GenesisConfig {
+		frame_system: Some(SystemConfig {
+			code: vec![],
+			changes_trie_config: Default::default(),
+		}),
+		pallet_balances: Some(BalancesConfig {
+			balances: ["Alice", "Bob", "Charlie"].iter().map(|name| (account(name), ENDOWMENT)).collect(),
+		}),
+		pallet_sudo: Some(SudoConfig { key: account("Alice") }),
+		pallet_democracy: Some(DemocracyConfig::default()),
+		pallet_siip: Some(SiipModuleConfig { certificates: vec![] }),
+		pallet_checkpoint: Some(CheckpointConfig { signers: vec![], threshold: 0 }),
+	}.build_storage().unwrap().into()
+}
+
+/// What executing a block did.
+struct Executed {
+	/// Each extrinsic's result, in order: `Err` if it couldn't be included at all, otherwise its
+	/// dispatch outcome.
+	outcomes: Vec<ApplyExtrinsicResult>,
+	/// The events of the whole block, from initialization to finalization.
+	events: Vec<Event>,
+}
+
+/// Builds blocks on top of genesis, one after another, and signs extrinsics for them.
+struct Chain {
+	parent_hash: Hash,
+	number: BlockNumber,
+	/// Extrinsics signed for the next block, by signer, so each gets the next nonce.
+	pending: BTreeMap<AccountId, Index>,
+}
+
+impl Chain {
+	fn new() -> Self {
+		Chain { parent_hash: test_block_hash(0), number: 0, pending: BTreeMap::new() }
+	}
+
+	/// `call`, signed by `signer` with the next nonce.
+	fn sign(&mut self, signer: &str, call: impl Into<Call>) -> UncheckedExtrinsic {
+		let call = call.into();
+		let who = account(signer);
+		let pending = self.pending.entry(who.clone()).or_default();
+		let extras = default_extras(System::account_nonce(&who) + *pending);
+		*pending += 1;
+
+		let payload = SignedPayload::new(call.clone(), extras.clone()).unwrap();
+		let signature = payload.using_encoded(|payload| pair(signer).sign(payload));
+		UncheckedExtrinsic::new_signed(call, Address::Id(who), signature.into(), extras)
+	}
+
+	/// Executes the next block, mined by `miner` if there is one.
+	fn block(&mut self, miner: Option<&str>, extrinsics: Vec<UncheckedExtrinsic>) -> Executed {
+		let mut header = Header {
+			parent_hash: self.parent_hash,
+			number: self.number + 1,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		if let Some(miner) = miner {
+			header.digest.push(DigestItem::PreRuntime(POW_ENGINE_ID, account(miner).encode()));
+		}
+
+		Executive::initialize_block(&header);
+		let outcomes = extrinsics.into_iter().map(Executive::apply_extrinsic).collect();
+		let header = Executive::finalize_block();
+		let events = System::events().into_iter().map(|record| record.event).collect();
+
+		self.parent_hash = header.hash();
+		self.number = header.number;
+		self.pending.clear();
+		Executed { outcomes, events }
+	}
+
+	/// Executes empty blocks mined by `miner` until block `number` has been executed.
+	fn run_to(&mut self, miner: &str, number: BlockNumber) {
+		while self.number < number {
+			self.block(Some(miner), vec![]);
+		}
+	}
+}
+
+/// The reward for mining a block that ends with `issuance` tokens in existence.
+fn inflation(issuance: Balance) -> Balance {
+	(MAX_TOKENS - issuance) / 1000
+}
+
+fn commit(domain: &str, owner: &str) -> pallet_siip::Call<Runtime> {
+	pallet_siip::Call::commit_registration(SiipModule::registration_commitment(domain.as_bytes(), b"salt", &account(owner)))
+}
+
+fn reveal(domain: &str) -> pallet_siip::Call<Runtime> {
+	pallet_siip::Call::reveal_registration(
+		b"salt".to_vec(), NAME.into(), domain.into(), IP_ADDR.into(), INFO.into(), KEY.into(),
+	)
+}
+
+#[test]
+fn block_hashes_are_recorded() {
+	new_test_ext().execute_with(|| {
+		let mut chain = Chain::new();
+		let genesis = chain.parent_hash;
+		chain.block(Some("Charlie"), vec![]);
+		let first = chain.parent_hash;
+		chain.block(Some("Charlie"), vec![]);
+
+		assert_eq!(test_block_hash(0), genesis);
+		assert_eq!(test_block_hash(1), first);
+		assert_ne!(first, genesis);
+	});
+}
+
+#[test]
+fn fees_go_to_the_miner() {
+	new_test_ext().execute_with(|| {
+		let mut chain = Chain::new();
+		let issuance = Balances::total_issuance();
+		let commitment = chain.sign("Alice", commit(DOMAIN, "Alice"));
+		let executed = chain.block(Some("Charlie"), vec![commitment]);
+		assert_eq!(executed.outcomes, vec![Ok(Ok(()))]);
+
+		//Alice paid a fee, and the miner got it on top of the block reward
+		let fee = ENDOWMENT - free("Alice");
+		assert!(fee > 0);
+		assert_eq!(free("Charlie"), ENDOWMENT + fee + inflation(issuance));
+		assert_eq!(Balances::total_issuance(), issuance + inflation(issuance));
+	});
+}
+
+#[test]
+fn fees_are_burned_without_a_miner() {
+	new_test_ext().execute_with(|| {
+		let mut chain = Chain::new();
+		let issuance = Balances::total_issuance();
+		let commitment = chain.sign("Alice", commit(DOMAIN, "Alice"));
+		chain.block(None, vec![commitment]);
+
+		let fee = ENDOWMENT - free("Alice");
+		assert!(fee > 0);
+		assert_eq!(Balances::total_issuance(), issuance - fee);
+	});
+}
+
+#[test]
+fn miner_reward_shrinks_geometrically() {
+	new_test_ext().execute_with(|| {
+		let mut chain = Chain::new();
+		let mut expected = ENDOWMENT;
+		let mut last_reward = Balance::max_value();
+		for _ in 0..3 {
+			//Each reward is a thousandth of the tokens still to be issued
+			let reward = inflation(Balances::total_issuance());
+			assert!(reward < last_reward);
+			chain.block(Some("Bob"), vec![]);
+			expected += reward;
+			assert_eq!(free("Bob"), expected);
+			last_reward = reward;
+		}
+
+		//Without a miner to pay, nothing is issued
+		let issuance = Balances::total_issuance();
+		chain.block(None, vec![]);
+		assert_eq!(Balances::total_issuance(), issuance);
+	});
+}
+
+#[test]
+fn insufficient_balance_cant_pay_fees() {
+	new_test_ext().execute_with(|| {
+		let mut chain = Chain::new();
+		let commitment = chain.sign("Broke", commit(DOMAIN, "Broke"));
+		let executed = chain.block(Some("Charlie"), vec![commitment]);
+		assert_eq!(executed.outcomes, vec![Err(TransactionValidityError::Invalid(InvalidTransaction::Payment))]);
+		assert!(SiipModule::commitment(account("Broke"), SiipModule::registration_commitment(DOMAIN.as_bytes(), b"salt", &account("Broke"))).is_none());
+	});
+}
+
+#[test]
+fn certificate_lifecycle() {
+	new_test_ext().execute_with(|| {
+		let mut chain = Chain::new();
+		let commitment = chain.sign("Alice", commit(DOMAIN, "Alice"));
+		let executed = chain.block(Some("Charlie"), vec![commitment]);
+		let committed_at = chain.number;
+		assert!(executed.events.iter().any(|event| matches!(event,
+			Event::pallet_siip(pallet_siip::RawEvent::RegistrationCommitted(..))
+		)));
+
+		//The commitment isn't old enough to reveal in the next block
+		let early = chain.sign("Alice", reveal(DOMAIN));
+		let executed = chain.block(Some("Charlie"), vec![early]);
+		assert!(matches!(executed.outcomes[..], [Ok(Err(DispatchError::Module { .. }))]));
+		assert_eq!(reserved("Alice"), 0);
+
+		//Once it is, the certificate is registered and its deposit reserved
+		chain.run_to("Charlie", committed_at + MinCommitmentAge::get() - 1);
+		let revealed = chain.sign("Alice", reveal(DOMAIN));
+		let executed = chain.block(Some("Charlie"), vec![revealed]);
+		assert_eq!(executed.outcomes, vec![Ok(Ok(()))]);
+		assert!(executed.events.iter().any(|event| matches!(event,
+			Event::pallet_siip(pallet_siip::RawEvent::CertificateRegistered(..))
+		)));
+		assert_eq!(SiipModule::domain_to_certificate(DOMAIN.as_bytes().to_vec()).domain, DOMAIN.as_bytes().to_vec());
+		let deposit = reserved("Alice");
+		assert!(deposit >= CertificateDepositBase::get());
+
+		//Modified and removed over the next blocks, by its owner only
+		let modified = chain.sign("Alice", pallet_siip::Call::modify_certificate(
+			NAME.into(), DOMAIN.into(), "13.49.70.107".into(), INFO.into(), KEY.into(),
+		));
+		let stolen = chain.sign("Bob", pallet_siip::Call::remove_certificate(DOMAIN.into()));
+		let executed = chain.block(Some("Charlie"), vec![modified, stolen]);
+		assert!(matches!(executed.outcomes[..], [Ok(Ok(())), Ok(Err(_))]));
+		assert!(executed.events.iter().any(|event| matches!(event,
+			Event::pallet_siip(pallet_siip::RawEvent::CertificateModified(..))
+		)));
+		assert_eq!(SiipModule::domain_to_certificate(DOMAIN.as_bytes().to_vec()).ip_addr, b"13.49.70.107".to_vec());
+
+		let removed = chain.sign("Alice", pallet_siip::Call::remove_certificate(DOMAIN.into()));
+		let executed = chain.block(Some("Charlie"), vec![removed]);
+		assert_eq!(executed.outcomes, vec![Ok(Ok(()))]);
+		assert!(executed.events.iter().any(|event| matches!(event,
+			Event::pallet_siip(pallet_siip::RawEvent::CertificateRemoved(..))
+		)));
+		assert_eq!(reserved("Alice"), 0);
+		assert_eq!(SiipModule::domain_to_certificate(DOMAIN.as_bytes().to_vec()).domain, Vec::<u8>::new());
+	});
+}
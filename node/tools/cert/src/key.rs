@@ -0,0 +1,106 @@
+//! Where a certificate's key comes from: a file, or the server that will present it.
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use webpki::DNSNameRef;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeySource {
+	/// A PEM or DER certificate, signing request, public key or unencrypted private key.
+	File(PathBuf),
+	/// A TLS server, whose certificate for the domain is read during a handshake.
+	Server(SocketAddr),
+}
+
+/// Reads the key from `source`, formatted as `check_key` expects it.
+pub async fn read_key(source: &KeySource, domain: &str) -> Result<String, String> {
+	let spki = match source {
+		KeySource::File(path) => {
+			let contents = fs::read(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+			siip_dane::spki_from_file(&contents).map_err(|e| format!("{}: {}", path.display(), e))?
+		},
+		KeySource::Server(addr) => {
+			let cert = server_certificate(*addr, domain).await
+				.map_err(|e| format!("can't get a certificate from {}: {}", addr, e))?;
+			siip_dane::der::spki_from_certificate(&cert)
+				.map_err(|e| format!("{} sent an unusable certificate: {}", addr, e))?
+				.to_vec()
+		},
+	};
+	Ok(siip_dane::der_to_key(&spki))
+}
+
+/// Accepts whatever certificate the server presents, and keeps it. Nothing is sent over the
+/// connection: the certificate is only read so that its owner can register it.
+struct Capture(Mutex<Option<Certificate>>);
+
+impl ServerCertVerifier for Capture {
+	fn verify_server_cert(
+		&self,
+		_roots: &RootCertStore,
+		presented_certs: &[Certificate],
+		_dns_name: DNSNameRef,
+		_ocsp_response: &[u8],
+	) -> Result<ServerCertVerified, TLSError> {
+		let end_entity = presented_certs.first().ok_or(TLSError::NoCertificatesPresented)?;
+		*self.0.lock().expect("no panics while holding the lock; qed") = Some(end_entity.clone());
+		Ok(ServerCertVerified::assertion())
+	}
+}
+
+/// The DER certificate `addr` presents for `domain`.
+async fn server_certificate(addr: SocketAddr, domain: &str) -> io::Result<Vec<u8>> {
+	let dns_name = DNSNameRef::try_from_ascii_str(domain)
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the domain isn't a valid server name"))?;
+	let capture = Arc::new(Capture(Mutex::new(None)));
+	let mut config = ClientConfig::new();
+	config.dangerous().set_certificate_verifier(capture.clone());
+
+	let tcp = TcpStream::connect(addr).await?;
+	TlsConnector::from(Arc::new(config)).connect(dns_name, tcp).await?;
+	let cert = capture.0.lock().expect("no panics while holding the lock; qed").take();
+	cert.map(|cert| cert.0).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no certificate presented"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::Path;
+
+	#[tokio::test]
+	async fn keys_from_files() {
+		let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("../dane/testdata");
+		let public = read_key(&KeySource::File(testdata.join("ec-public.pem")), "example.com").await.unwrap();
+		assert!(public.starts_with("30:59:30:13:06:07:2A:86:48:CE:3D:02:01"));
+		for file in &["ec-cert.pem", "ec-csr.pem", "ec-pkcs8.pem", "ec-sec1.pem"] {
+			assert_eq!(read_key(&KeySource::File(testdata.join(file)), "example.com").await, Ok(public.clone()));
+		}
+		assert!(read_key(&KeySource::File(testdata.join("missing.pem")), "example.com").await.is_err());
+	}
+
+	#[tokio::test]
+	async fn key_from_server() {
+		let cert = rcgen::generate_simple_self_signed(vec!["example.com".to_string()]).unwrap();
+		let key = siip_dane::der_to_key(&cert.get_key_pair().public_key_der());
+		let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+		config.set_single_cert(
+			vec![Certificate(cert.serialize_der().unwrap())],
+			rustls::PrivateKey(cert.serialize_private_key_der()),
+		).unwrap();
+		let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			let (stream, _) = listener.accept().await.unwrap();
+			let _ = acceptor.accept(stream).await;
+		});
+		assert_eq!(read_key(&KeySource::Server(addr), "example.com").await, Ok(key));
+	}
+}
@@ -0,0 +1,164 @@
+//! Registers or modifies a SIIP certificate from a certificate, key or CSR that already exists,
+//! or from the server that will present it.
+//!
+//! `siip-cert register --domain example.com --ip 10.0.0.1 --name Alice --key-from cert.pem
+//! --suri //Alice` takes the public key out of `cert.pem`, formats it as `check_key` expects, runs the
+//! pallet's checks over every field, and submits the registration. `--key-from-server
+//! 127.0.0.1:443` reads the key from a running server's certificate instead.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use sp_core::crypto::Ss58Codec;
+use sp_core::sr25519;
+use structopt::StructOpt;
+use siip_client::{CertificateFields, Client, Signer};
+use siip_node_runtime::pallet_siip::validation_failures;
+
+mod key;
+
+use key::KeySource;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "siip-cert", about = "Registers and modifies SIIP certificates from existing keys")]
+enum Command {
+	/// Register a new certificate. This commits to the registration, then reveals it a few
+	/// blocks later.
+	Register(Opt),
+	/// Replace the certificate the account already holds for a domain.
+	Modify(Opt),
+}
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+	/// The domain the certificate is for.
+	#[structopt(long)]
+	domain: String,
+
+	/// The IPv4 address the domain points at. Defaults to the server's with `--key-from-server`.
+	#[structopt(long)]
+	ip: Option<String>,
+
+	/// The owner's name.
+	#[structopt(long)]
+	name: String,
+
+	/// The owner's JSON info.
+	#[structopt(long, default_value = "{}")]
+	info: String,
+
+	/// A PEM or DER certificate, CSR, public key or unencrypted private key holding the key.
+	#[structopt(long, parse(from_os_str), required_unless = "key_from_server")]
+	key_from: Option<PathBuf>,
+
+	/// A running TLS server whose certificate holds the key, e.g. `127.0.0.1:443`.
+	#[structopt(long, conflicts_with = "key_from")]
+	key_from_server: Option<SocketAddr>,
+
+	/// The secret URI of the account to sign with, e.g. `//Alice` or a mnemonic.
+	#[structopt(long, required_unless = "account")]
+	suri: Option<String>,
+
+	/// The SS58 address of an account in `--keystore` to sign with.
+	#[structopt(long, requires = "keystore", conflicts_with = "suri")]
+	account: Option<String>,
+
+	/// The node keystore holding `--account`.
+	#[structopt(long, parse(from_os_str))]
+	keystore: Option<PathBuf>,
+
+	/// Check and print the certificate without submitting it.
+	#[structopt(long)]
+	dry_run: bool,
+
+	/// HTTP RPC endpoint of the node to submit to.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	node: String,
+
+	/// How long to wait for each extrinsic to be mined, in seconds.
+	#[structopt(long, default_value = "300")]
+	timeout: u64,
+}
+
+impl Opt {
+	fn source(&self) -> KeySource {
+		match (&self.key_from, self.key_from_server) {
+			(Some(path), _) => KeySource::File(path.clone()),
+			(None, Some(addr)) => KeySource::Server(addr),
+			(None, None) => unreachable!("structopt requires one of them; qed"),
+		}
+	}
+
+	/// `--ip`, or the address of `--key-from-server` if it is IPv4.
+	fn ip_addr(&self) -> Result<String, String> {
+		match (&self.ip, self.key_from_server.map(|addr| addr.ip())) {
+			(Some(ip), _) => Ok(ip.clone()),
+			(None, Some(IpAddr::V4(ip))) => Ok(ip.to_string()),
+			_ => Err("--ip is required".into()),
+		}
+	}
+
+	fn signer(&self) -> Result<Signer, String> {
+		if let Some(suri) = &self.suri {
+			return Signer::from_seed(suri).map_err(|e| e.to_string());
+		}
+		let account = self.account.as_deref().unwrap_or_default();
+		let public = sr25519::Public::from_ss58check(account)
+			.map_err(|_| format!("{} isn't an SS58 address", account))?;
+		let keystore = self.keystore.as_ref().expect("structopt requires --keystore with --account; qed");
+		Signer::from_keystore(keystore, &public).map_err(|e| e.to_string())
+	}
+}
+
+#[tokio::main]
+async fn main() {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	if let Err(e) = run(Command::from_args()).await {
+		eprintln!("siip-cert: {}", e);
+		process::exit(1);
+	}
+}
+
+async fn run(command: Command) -> Result<(), String> {
+	let (opt, register) = match command {
+		Command::Register(opt) => (opt, true),
+		Command::Modify(opt) => (opt, false),
+	};
+
+	let fields = CertificateFields {
+		name: opt.name.clone(),
+		domain: opt.domain.clone(),
+		ip_addr: opt.ip_addr()?,
+		info: opt.info.clone(),
+		key: key::read_key(&opt.source(), &opt.domain).await?,
+	};
+	println!("domain: {}\nip:     {}\nname:   {}\ninfo:   {}\nkey:    {}",
+		fields.domain, fields.ip_addr, fields.name, fields.info, fields.key);
+
+	let failures = validation_failures(
+		fields.name.as_bytes(), fields.domain.as_bytes(), fields.ip_addr.as_bytes(), fields.info.as_bytes(), fields.key.as_bytes(),
+	);
+	if !failures.is_empty() {
+		return Err(format!("the certificate would be rejected:\n  {}", failures.join("\n  ")));
+	}
+	if opt.dry_run {
+		return Ok(());
+	}
+
+	let signer = opt.signer()?;
+	let client = Client::new(opt.node.clone(), Duration::from_secs(10))
+		.inclusion_timeout(Duration::from_secs(opt.timeout));
+	let inclusion = if register {
+		client.register(&signer, &fields).await
+	} else {
+		client.modify(&signer, &fields).await
+	}.map_err(|e| e.to_string())?;
+
+	println!(
+		"{} {} in block #{} ({:?})",
+		if register { "Registered" } else { "Modified" },
+		fields.domain, inclusion.block_number, inclusion.block_hash,
+	);
+	Ok(())
+}
@@ -0,0 +1,117 @@
+//! Signs checkpoints as one of the chain's checkpoint signers. Once a majority of the signers has
+//! signed the same block, the node finalizes it.
+//!
+//! `siip-checkpoint --suri //Alice --depth 20` signs the block 20 below the best every minute,
+//! unless the last checkpoint is already that high. The depth should be enough that honest
+//! signers see the same block there, or their signatures won't add up.
+
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use log::{info, warn};
+use sp_core::crypto::Ss58Codec;
+use sp_core::sr25519;
+use structopt::StructOpt;
+use siip_client::{Client, Signer};
+use siip_node_runtime::BlockNumber;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "siip-checkpoint", about = "Signs SIIP checkpoints, so that the chain can finalize them")]
+struct Opt {
+	/// How many blocks below the best block to sign.
+	#[structopt(long, default_value = "20")]
+	depth: BlockNumber,
+
+	/// How often to sign, in seconds.
+	#[structopt(long, default_value = "60")]
+	interval: u64,
+
+	/// The secret URI of the signer's account, e.g. `//Alice` or a mnemonic.
+	#[structopt(long, required_unless = "account")]
+	suri: Option<String>,
+
+	/// The SS58 address of an account in `--keystore` to sign with.
+	#[structopt(long, requires = "keystore", conflicts_with = "suri")]
+	account: Option<String>,
+
+	/// The node keystore holding `--account`.
+	#[structopt(long, parse(from_os_str))]
+	keystore: Option<PathBuf>,
+
+	/// HTTP RPC endpoint of the node to submit to.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	node: String,
+}
+
+impl Opt {
+	fn signer(&self) -> Result<Signer, String> {
+		if let Some(suri) = &self.suri {
+			return Signer::from_seed(suri).map_err(|e| e.to_string());
+		}
+		let account = self.account.as_deref().unwrap_or_default();
+		let public = sr25519::Public::from_ss58check(account)
+			.map_err(|_| format!("{} isn't an SS58 address", account))?;
+		let keystore = self.keystore.as_ref().expect("structopt requires --keystore with --account; qed");
+		Signer::from_keystore(keystore, &public).map_err(|e| e.to_string())
+	}
+}
+
+/// The block to sign: `depth` below `best`, if that is above the last checkpoint.
+fn target(best: BlockNumber, depth: BlockNumber, last_checkpoint: Option<BlockNumber>) -> Option<BlockNumber> {
+	let target = best.checked_sub(depth).filter(|target| *target > 0)?;
+	match last_checkpoint {
+		Some(last) if last >= target => None,
+		_ => Some(target),
+	}
+}
+
+#[tokio::main]
+async fn main() {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	let opt = Opt::from_args();
+	let signer = match opt.signer() {
+		Ok(signer) => signer,
+		Err(e) => {
+			eprintln!("siip-checkpoint: {}", e);
+			process::exit(1);
+		},
+	};
+	let client = Client::new(opt.node.clone(), Duration::from_secs(10));
+
+	loop {
+		if let Err(e) = sign(&client, &signer, opt.depth).await {
+			warn!("Not signed: {}", e);
+		}
+		tokio::time::sleep(Duration::from_secs(opt.interval)).await;
+	}
+}
+
+/// Signs the block `depth` below the best, if it isn't final yet.
+async fn sign(client: &Client, signer: &Signer, depth: BlockNumber) -> Result<(), String> {
+	let best = client.best_number().await.map_err(|e| e.to_string())?;
+	let last = client.last_checkpoint(None).await.map_err(|e| e.to_string())?;
+	let number = match target(best, depth, last.map(|checkpoint| checkpoint.number)) {
+		Some(number) => number,
+		None => return Ok(()),
+	};
+	let hash = client.block_hash(number).await.map_err(|e| e.to_string())?
+		.ok_or_else(|| format!("the node has no block #{}", number))?;
+	client.sign_checkpoint(signer, number, hash).await.map_err(|e| e.to_string())?;
+	info!("Signed block #{} ({:?})", number, hash);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn targets() {
+		assert_eq!(target(100, 20, None), Some(80));
+		assert_eq!(target(100, 20, Some(79)), Some(80));
+		assert_eq!(target(100, 20, Some(80)), None);
+		//Too short a chain to have a block that deep
+		assert_eq!(target(20, 20, None), None);
+		assert_eq!(target(5, 20, None), None);
+	}
+}
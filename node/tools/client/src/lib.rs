@@ -0,0 +1,420 @@
+//! A client for SIIP nodes, built on the runtime's own types.
+//!
+//! Lookups read `CertificateMap` and `ReverseMap` straight from storage and decode them as
+//! `Certificate`s. Certificate management builds, signs and submits `Call`s, keeping track of
+//! each account's nonce, and waits until the extrinsic has been mined to report whether it
+//! succeeded.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use codec::{Decode, Encode};
+use log::debug;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use sp_core::crypto::Ss58Codec;
+use sp_core::hashing::blake2_256;
+use sp_runtime::generic::Era;
+use siip_node_runtime::{
+	pallet_checkpoint, pallet_siip, Address, Balance, BalancesCall, BlockNumber, Call, Event, Hash, Index,
+	MinCommitmentAge, SignedExtra, SignedPayload, SiipModule, UncheckedExtrinsic,
+};
+
+mod signer;
+pub mod storage;
+
+pub use siip_node_runtime::AccountId;
+pub use signer::Signer;
+
+/// A certificate as stored by the runtime.
+pub type Certificate = pallet_siip::Certificate<AccountId>;
+/// A key a certificate will switch to, as stored by the runtime.
+pub type KeyRotation = pallet_siip::KeyRotation<BlockNumber>;
+/// A block the checkpoint signers agreed on, as stored by the runtime.
+pub type Checkpoint = pallet_checkpoint::Checkpoint<BlockNumber, Hash>;
+/// One version of a domain's certificate, as kept by the runtime.
+pub type CertificateVersion = pallet_siip::CertificateVersion<AccountId, BlockNumber>;
+
+/// How long to wait for an extrinsic to be mined, unless told otherwise.
+const DEFAULT_INCLUSION_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often to look for new blocks while waiting.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum Error {
+	/// The node couldn't be reached, or didn't answer in time.
+	Transport(reqwest::Error),
+	/// The node answered with a JSON-RPC error.
+	Rpc(Value),
+	/// The node answered with something that couldn't be decoded.
+	Decode(String),
+	/// The signing key couldn't be loaded.
+	Signer(String),
+	/// The extrinsic was mined, but failed.
+	Dispatch(String),
+	/// The extrinsic wasn't mined before the timeout.
+	Timeout(Hash),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Transport(e) => write!(f, "node unreachable: {}", e),
+			Error::Rpc(e) => write!(f, "node returned an error: {}", e),
+			Error::Decode(e) => write!(f, "malformed response: {}", e),
+			Error::Signer(e) => write!(f, "{}", e),
+			Error::Dispatch(e) => write!(f, "extrinsic failed: {}", e),
+			Error::Timeout(hash) => write!(f, "extrinsic {:?} wasn't mined in time", hash),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+	fn from(e: reqwest::Error) -> Self {
+		Error::Transport(e)
+	}
+}
+
+/// The fields of a certificate that its owner chooses.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CertificateFields {
+	pub name: String,
+	pub domain: String,
+	pub ip_addr: String,
+	pub info: String,
+	pub key: String,
+}
+
+/// Where a successful extrinsic was mined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inclusion {
+	pub block_hash: Hash,
+	pub block_number: BlockNumber,
+	/// The extrinsic's index within the block.
+	pub index: u32,
+	/// The events the extrinsic deposited.
+	pub events: Vec<Event>,
+}
+
+/// A connection to one trusted node.
+pub struct Client {
+	http: reqwest::Client,
+	url: String,
+	inclusion_timeout: Duration,
+	/// The next nonce of each account that has submitted through this client. Lets several
+	/// extrinsics be submitted before the first is mined.
+	nonces: Mutex<HashMap<AccountId, Index>>,
+}
+
+impl Client {
+	/// Every request gives up after `timeout`, so an unreachable node can't hang the caller.
+	pub fn new(url: impl Into<String>, timeout: Duration) -> Self {
+		let http = reqwest::Client::builder()
+			.timeout(timeout)
+			.build()
+			.expect("static client configuration is valid; qed");
+		Client {
+			http,
+			url: url.into(),
+			inclusion_timeout: DEFAULT_INCLUSION_TIMEOUT,
+			nonces: Default::default(),
+		}
+	}
+
+	/// How long certificate management calls wait for their extrinsics to be mined.
+	pub fn inclusion_timeout(mut self, timeout: Duration) -> Self {
+		self.inclusion_timeout = timeout;
+		self
+	}
+
+	/// Sends one JSON-RPC request, and returns its result.
+	pub async fn request(&self, method: &str, params: Value) -> Result<Value, Error> {
+		let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+		let mut response: Value = self.http.post(&self.url).json(&body).send().await?.json().await?;
+		match response.get_mut("error") {
+			Some(error) => Err(Error::Rpc(error.take())),
+			None => Ok(response["result"].take()),
+		}
+	}
+
+	/// Reads and decodes a storage item, at the best block unless `at` says otherwise.
+	pub async fn storage<T: Decode>(&self, key: &[u8], at: Option<Hash>) -> Result<Option<T>, Error> {
+		let key = format!("0x{}", hex::encode(key));
+		match self.request("state_getStorage", json!([key, at])).await? {
+			Value::String(data) => decode(&data).map(Some),
+			_ => Ok(None),
+		}
+	}
+
+	/// The certificate for `domain`, at the best block unless `at` says otherwise.
+	pub async fn lookup(&self, domain: &str, at: Option<Hash>) -> Result<Option<Certificate>, Error> {
+		self.storage(&storage::certificate_key(domain), at).await
+	}
+
+	/// Every certificate pointing at `ip_addr`, at the best block unless `at` says otherwise.
+	pub async fn reverse_lookup(&self, ip_addr: &str, at: Option<Hash>) -> Result<Vec<Certificate>, Error> {
+		Ok(self.storage(&storage::reverse_key(ip_addr), at).await?.unwrap_or_default())
+	}
+
+	/// The key `domain` is rotating to, if any, at the best block unless `at` says otherwise.
+	pub async fn key_rotation(&self, domain: &str, at: Option<Hash>) -> Result<Option<KeyRotation>, Error> {
+		self.storage(&storage::key_rotation_key(domain), at).await
+	}
+
+	/// The latest versions of `domain`'s certificate, oldest first, at the best block unless `at`
+	/// says otherwise.
+	pub async fn history(&self, domain: &str, at: Option<Hash>) -> Result<Vec<CertificateVersion>, Error> {
+		Ok(self.storage(&storage::history_key(domain), at).await?.unwrap_or_default())
+	}
+
+	/// The latest block the checkpoint signers agreed on, as of the best block unless `at`
+	/// says otherwise. The node finalizes it.
+	pub async fn last_checkpoint(&self, at: Option<Hash>) -> Result<Option<Checkpoint>, Error> {
+		self.storage(&storage::last_checkpoint_key(), at).await
+	}
+
+	/// Registers a certificate for `signer`. Registrations must be committed to before they are
+	/// revealed, so this submits two extrinsics and takes a few blocks.
+	pub async fn register(&self, signer: &Signer, fields: &CertificateFields) -> Result<Inclusion, Error> {
+		let salt = rand::random::<[u8; 32]>().to_vec();
+		let commitment = SiipModule::registration_commitment(fields.domain.as_bytes(), &salt, &signer.account_id());
+		let committed = self.submit_and_wait(signer, Call::SiipModule(pallet_siip::Call::commit_registration(commitment))).await?;
+
+		// The reveal must be mined at least `MinCommitmentAge` blocks after the commitment
+		let reveal_after = committed.block_number + MinCommitmentAge::get() - 1;
+		self.wait_for_block(reveal_after).await?;
+
+		let reveal = pallet_siip::Call::reveal_registration(
+			salt,
+			fields.name.clone().into_bytes(),
+			fields.domain.clone().into_bytes(),
+			fields.ip_addr.clone().into_bytes(),
+			fields.info.clone().into_bytes(),
+			fields.key.clone().into_bytes(),
+		);
+		self.submit_and_wait(signer, Call::SiipModule(reveal)).await
+	}
+
+	/// Replaces the certificate `signer` holds for `fields.domain`.
+	pub async fn modify(&self, signer: &Signer, fields: &CertificateFields) -> Result<Inclusion, Error> {
+		let call = pallet_siip::Call::modify_certificate(
+			fields.name.clone().into_bytes(),
+			fields.domain.clone().into_bytes(),
+			fields.ip_addr.clone().into_bytes(),
+			fields.info.clone().into_bytes(),
+			fields.key.clone().into_bytes(),
+		);
+		self.submit_and_wait(signer, Call::SiipModule(call)).await
+	}
+
+	/// Schedules `next_key` to replace the key of the certificate `signer` holds for `domain` at
+	/// block `activation`. Both keys are valid until then.
+	pub async fn rotate_key(&self, signer: &Signer, domain: &str, next_key: &str, activation: BlockNumber) -> Result<Inclusion, Error> {
+		let call = pallet_siip::Call::rotate_key(domain.as_bytes().to_vec(), next_key.as_bytes().to_vec(), activation);
+		self.submit_and_wait(signer, Call::SiipModule(call)).await
+	}
+
+	/// Signs block `number`, whose hash is `hash`, as one of the checkpoint signers.
+	pub async fn sign_checkpoint(&self, signer: &Signer, number: BlockNumber, hash: Hash) -> Result<Inclusion, Error> {
+		let call = pallet_checkpoint::Call::sign_checkpoint(number, hash);
+		self.submit_and_wait(signer, Call::Checkpoint(call)).await
+	}
+
+	/// Removes the certificate `signer` holds for `domain`, releasing its deposit.
+	pub async fn remove(&self, signer: &Signer, domain: &str) -> Result<Inclusion, Error> {
+		let call = pallet_siip::Call::remove_certificate(domain.as_bytes().to_vec());
+		self.submit_and_wait(signer, Call::SiipModule(call)).await
+	}
+
+	/// Sends `amount` from `signer` to `dest`.
+	pub async fn transfer(&self, signer: &Signer, dest: &AccountId, amount: Balance) -> Result<Inclusion, Error> {
+		let call = BalancesCall::transfer(Address::Id(dest.clone()), amount);
+		self.submit_and_wait(signer, Call::Balances(call)).await
+	}
+
+	/// Submits `call`, and waits for it to be mined.
+	pub async fn submit_and_wait(&self, signer: &Signer, call: Call) -> Result<Inclusion, Error> {
+		let from = self.best_number().await?;
+		let hash = self.submit(signer, call).await?;
+		self.wait_for_inclusion(hash, from).await
+	}
+
+	/// Signs and submits `call`, and returns the extrinsic's hash without waiting for it.
+	pub async fn submit(&self, signer: &Signer, call: Call) -> Result<Hash, Error> {
+		let account = signer.account_id();
+		let genesis: Hash = serde_json::from_value(self.request("chain_getBlockHash", json!([0])).await?)
+			.map_err(|e| Error::Decode(e.to_string()))?;
+		let version = self.request("state_getRuntimeVersion", json!([])).await?;
+		let spec_version = version["specVersion"].as_u64().ok_or_else(|| Error::Decode("no specVersion".into()))?;
+		let tx_version = version["transactionVersion"].as_u64().ok_or_else(|| Error::Decode("no transactionVersion".into()))?;
+
+		let nonce = self.next_nonce(&account).await?;
+		let extrinsic = sign(signer, call, nonce, genesis, spec_version as u32, tx_version as u32);
+		let encoded = format!("0x{}", hex::encode(extrinsic.encode()));
+		match self.request("author_submitExtrinsic", json!([encoded])).await {
+			Ok(_) => Ok(Hash::from(blake2_256(&extrinsic.encode()))),
+			Err(e) => {
+				// The nonce wasn't used, so start again from the chain's view of it
+				self.nonces.lock().remove(&account);
+				Err(e)
+			},
+		}
+	}
+
+	/// The nonce to sign the next extrinsic from `account` with: the chain's (which counts the
+	/// transaction pool), unless this client has already used it.
+	pub async fn next_nonce(&self, account: &AccountId) -> Result<Index, Error> {
+		let chain: Index = serde_json::from_value(
+			self.request("system_accountNextIndex", json!([account.to_ss58check()])).await?
+		).map_err(|e| Error::Decode(e.to_string()))?;
+
+		let mut nonces = self.nonces.lock();
+		let nonce = nonces.get(account).copied().unwrap_or(0).max(chain);
+		nonces.insert(account.clone(), nonce + 1);
+		Ok(nonce)
+	}
+
+	/// The number of the node's best block.
+	pub async fn best_number(&self) -> Result<BlockNumber, Error> {
+		let header = self.request("chain_getHeader", json!([])).await?;
+		parse_number(&header["number"])
+	}
+
+	/// The hash of block `number` on the node's best chain, if it has one.
+	pub async fn block_hash(&self, number: BlockNumber) -> Result<Option<Hash>, Error> {
+		serde_json::from_value(self.request("chain_getBlockHash", json!([number])).await?)
+			.map_err(|e| Error::Decode(e.to_string()))
+	}
+
+	/// Waits until the best block is at least `number`.
+	pub async fn wait_for_block(&self, number: BlockNumber) -> Result<(), Error> {
+		while self.best_number().await? < number {
+			tokio::time::sleep(POLL_INTERVAL).await;
+		}
+		Ok(())
+	}
+
+	/// Looks for the extrinsic `hash` in each block after `from` as it is mined, and reports
+	/// whether it succeeded.
+	pub async fn wait_for_inclusion(&self, hash: Hash, from: BlockNumber) -> Result<Inclusion, Error> {
+		let deadline = Instant::now() + self.inclusion_timeout;
+		let mut next = from + 1;
+		while Instant::now() < deadline {
+			let best = self.best_number().await?;
+			while next <= best {
+				if let Some(inclusion) = self.find_extrinsic(hash, next).await? {
+					debug!("Extrinsic {:?} mined in block {}", hash, next);
+					return inclusion.into_result();
+				}
+				next += 1;
+			}
+			tokio::time::sleep(POLL_INTERVAL).await;
+		}
+		Err(Error::Timeout(hash))
+	}
+
+	async fn find_extrinsic(&self, hash: Hash, number: BlockNumber) -> Result<Option<Inclusion>, Error> {
+		let block_hash: Hash = serde_json::from_value(self.request("chain_getBlockHash", json!([number])).await?)
+			.map_err(|e| Error::Decode(e.to_string()))?;
+		let block = self.request("chain_getBlock", json!([block_hash])).await?;
+		let extrinsics = block["block"]["extrinsics"].as_array().cloned().unwrap_or_default();
+
+		for (index, extrinsic) in extrinsics.iter().enumerate() {
+			let bytes = hex::decode(extrinsic.as_str().unwrap_or_default().trim_start_matches("0x"))
+				.map_err(|e| Error::Decode(e.to_string()))?;
+			if blake2_256(&bytes) != hash.0 {
+				continue;
+			}
+
+			let index = index as u32;
+			let records: Vec<frame_system::EventRecord<Event, Hash>> =
+				self.storage(&storage::events_key(), Some(block_hash)).await?.unwrap_or_default();
+			let events = records.into_iter()
+				.filter(|record| record.phase == frame_system::Phase::ApplyExtrinsic(index))
+				.map(|record| record.event)
+				.collect();
+			return Ok(Some(Inclusion { block_hash, block_number: number, index, events }));
+		}
+		Ok(None)
+	}
+}
+
+impl Inclusion {
+	/// Fails if the extrinsic's events say it did.
+	fn into_result(self) -> Result<Self, Error> {
+		for event in &self.events {
+			if let Event::frame_system(frame_system::Event::ExtrinsicFailed(error, _)) = event {
+				return Err(Error::Dispatch(format!("{:?}", error)));
+			}
+		}
+		Ok(self)
+	}
+}
+
+/// Signs `call` as `signer`. The extrinsic is immortal, so it stays valid however long it takes
+/// to be mined; the nonce alone prevents it from being replayed.
+pub fn sign(
+	signer: &Signer,
+	call: Call,
+	nonce: Index,
+	genesis: Hash,
+	spec_version: u32,
+	tx_version: u32,
+) -> UncheckedExtrinsic {
+	let extra: SignedExtra = (
+		frame_system::CheckSpecVersion::new(),
+		frame_system::CheckTxVersion::new(),
+		frame_system::CheckGenesis::new(),
+		frame_system::CheckEra::from(Era::Immortal),
+		frame_system::CheckNonce::from(nonce),
+		frame_system::CheckWeight::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::from(0),
+	);
+	// What each extension adds to the signed payload: an immortal era is anchored at genesis
+	let additional = (spec_version, tx_version, genesis, genesis, (), (), ());
+	let payload = SignedPayload::from_raw(call, extra, additional);
+	let signature = payload.using_encoded(|payload| signer.sign(payload));
+	let (call, extra, _) = payload.deconstruct();
+	UncheckedExtrinsic::new_signed(call, Address::Id(signer.account_id()), signature, extra)
+}
+
+fn decode<T: Decode>(data: &str) -> Result<T, Error> {
+	let bytes = hex::decode(data.trim_start_matches("0x")).map_err(|e| Error::Decode(e.to_string()))?;
+	T::decode(&mut &bytes[..]).map_err(|e| Error::Decode(e.to_string()))
+}
+
+fn parse_number(number: &Value) -> Result<BlockNumber, Error> {
+	let number = number.as_str().ok_or_else(|| Error::Decode("block number isn't a string".into()))?;
+	BlockNumber::from_str_radix(number.trim_start_matches("0x"), 16).map_err(|e| Error::Decode(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::Verify;
+
+	#[test]
+	fn signed_extrinsic_verifies() {
+		let alice = Signer::from_seed("//Alice").unwrap();
+		let genesis = Hash::repeat_byte(7);
+		let call = Call::SiipModule(pallet_siip::Call::remove_certificate(b"example.com".to_vec()));
+		let extrinsic = sign(&alice, call.clone(), 5, genesis, 100, 1);
+
+		let decoded = UncheckedExtrinsic::decode(&mut &extrinsic.encode()[..]).unwrap();
+		assert_eq!(decoded.function, call);
+		let (address, signature, extra) = decoded.signature.unwrap();
+		assert_eq!(address, Address::Id(alice.account_id()));
+
+		// The node rebuilds the payload from the extra and its own view of the chain
+		let payload = SignedPayload::from_raw(call, extra, (100u32, 1u32, genesis, genesis, (), (), ()));
+		assert!(payload.using_encoded(|payload| signature.verify(payload, &alice.account_id())));
+	}
+
+	#[test]
+	fn block_numbers_are_hex() {
+		assert_eq!(parse_number(&json!("0x1a")).unwrap(), 26);
+		assert!(parse_number(&json!(26)).is_err());
+	}
+}
@@ -0,0 +1,82 @@
+//! The accounts that sign extrinsics.
+
+use std::fs;
+use std::path::Path;
+use sp_core::crypto::{key_types, Pair as _};
+use sp_core::sr25519;
+use sp_runtime::traits::IdentifyAccount;
+use sp_runtime::MultiSigner;
+use siip_node_runtime::{AccountId, Signature};
+
+use crate::Error;
+
+/// An sr25519 key pair, the kind the Python clients and the front end use.
+pub struct Signer {
+	pair: sr25519::Pair,
+}
+
+impl Signer {
+	/// A key from a secret URI: a mnemonic, a hex seed, or a dev account such as `//Alice`.
+	pub fn from_seed(suri: &str) -> Result<Self, Error> {
+		sr25519::Pair::from_string(suri, None)
+			.map(|pair| Signer { pair })
+			.map_err(|e| Error::Signer(format!("invalid secret URI: {:?}", e)))
+	}
+
+	/// The account key for `public` in a node's keystore directory, as written by
+	/// `siip-node key insert --key-type acco`.
+	pub fn from_keystore(dir: &Path, public: &sr25519::Public) -> Result<Self, Error> {
+		let file = dir.join(format!("{}{}", hex::encode(key_types::ACCOUNT.0), hex::encode(public)));
+		let contents = fs::read_to_string(&file)
+			.map_err(|e| Error::Signer(format!("can't read {}: {}", file.display(), e)))?;
+		// The keystore stores each secret URI as a JSON string
+		let suri: String = serde_json::from_str(&contents)
+			.map_err(|e| Error::Signer(format!("malformed key file {}: {}", file.display(), e)))?;
+
+		let signer = Self::from_seed(&suri)?;
+		if signer.pair.public() != *public {
+			return Err(Error::Signer(format!("{} holds a different key", file.display())));
+		}
+		Ok(signer)
+	}
+
+	pub fn account_id(&self) -> AccountId {
+		MultiSigner::from(self.pair.public()).into_account()
+	}
+
+	pub fn sign(&self, payload: &[u8]) -> Signature {
+		self.pair.sign(payload).into()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::Ss58Codec;
+	use sp_runtime::traits::Verify;
+
+	#[test]
+	fn dev_account() {
+		let alice = Signer::from_seed("//Alice").unwrap();
+		assert_eq!(alice.account_id().to_ss58check(), "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY");
+		assert!(alice.sign(b"payload").verify(&b"payload"[..], &alice.account_id()));
+		assert!(Signer::from_seed("not a seed").is_err());
+	}
+
+	#[test]
+	fn keystore() {
+		let dir = std::env::temp_dir().join(format!("siip-client-keystore-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let bob = sr25519::Pair::from_string("//Bob", None).unwrap().public();
+		let file = dir.join(format!("{}{}", hex::encode(b"acco"), hex::encode(&bob)));
+
+		fs::write(&file, "\"//Bob\"").unwrap();
+		let signer = Signer::from_keystore(&dir, &bob).unwrap();
+		assert_eq!(signer.account_id(), Signer::from_seed("//Bob").unwrap().account_id());
+
+		fs::write(&file, "\"//Alice\"").unwrap();
+		assert!(Signer::from_keystore(&dir, &bob).is_err());
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
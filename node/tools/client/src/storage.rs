@@ -0,0 +1,74 @@
+//! Storage keys of the runtime items the client reads directly.
+
+use codec::Encode;
+use sp_core::hashing::{blake2_128, twox_128};
+
+/// The storage key of a value (or the prefix of a map) declared in `decl_storage!`.
+pub fn storage_prefix(module: &[u8], item: &[u8]) -> Vec<u8> {
+	let mut key = twox_128(module).to_vec();
+	key.extend_from_slice(&twox_128(item));
+	key
+}
+
+/// The key of `key` in a `blake2_128_concat` map: the encoded key follows its hash.
+fn blake2_128_concat(mut prefix: Vec<u8>, key: impl Encode) -> Vec<u8> {
+	let encoded = key.encode();
+	prefix.extend_from_slice(&blake2_128(&encoded));
+	prefix.extend(encoded);
+	prefix
+}
+
+/// The storage key under which `CertificateMap` keeps the certificate for `domain`.
+pub fn certificate_key(domain: &str) -> Vec<u8> {
+	blake2_128_concat(storage_prefix(b"SiipModule", b"CertificateMap"), domain.as_bytes().to_vec())
+}
+
+/// The storage key under which `ReverseMap` keeps the certificates pointing at `ip_addr`.
+pub fn reverse_key(ip_addr: &str) -> Vec<u8> {
+	blake2_128_concat(storage_prefix(b"SiipModule", b"ReverseMap"), ip_addr.as_bytes().to_vec())
+}
+
+/// The storage key under which `KeyRotations` keeps the pending key rotation of `domain`.
+pub fn key_rotation_key(domain: &str) -> Vec<u8> {
+	blake2_128_concat(storage_prefix(b"SiipModule", b"KeyRotations"), domain.as_bytes().to_vec())
+}
+
+/// The storage key under which `CertificateHistory` keeps the latest versions of `domain`'s
+/// certificate.
+pub fn history_key(domain: &str) -> Vec<u8> {
+	blake2_128_concat(storage_prefix(b"SiipModule", b"CertificateHistory"), domain.as_bytes().to_vec())
+}
+
+/// The storage key of `LastCheckpoint`, the latest block the checkpoint signers agreed on.
+pub fn last_checkpoint_key() -> Vec<u8> {
+	storage_prefix(b"CheckpointModule", b"LastCheckpoint")
+}
+
+/// The storage key of the events deposited in a block.
+pub fn events_key() -> Vec<u8> {
+	storage_prefix(b"System", b"Events")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn storage_prefix_matches_runtime() {
+		// The `System::Events` key whitelisted in the runtime's benchmarks
+		assert_eq!(
+			hex::encode(events_key()),
+			"26aa394eea5630e07c48ae0c9558cef780d41e5e16056765bc8461851072c9d7"
+		);
+	}
+
+	#[test]
+	fn certificate_key_ends_with_domain() {
+		let key = certificate_key("example.com");
+		let encoded = b"example.com".to_vec().encode();
+		assert_eq!(key.len(), 32 + 16 + encoded.len());
+		assert!(key.ends_with(&encoded));
+		assert_eq!(&key[32..48], &blake2_128(&encoded)[..]);
+		assert_eq!(&key[..32], &storage_prefix(b"SiipModule", b"CertificateMap")[..]);
+	}
+}
@@ -0,0 +1,141 @@
+//! Just enough HTTP/1.1 for one request per connection.
+
+use std::io::Write;
+
+/// The longest response head read before giving up on the server.
+const MAX_HEADERS: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Request {
+	pub method: String,
+	/// The host and, if it isn't the default, the port, as sent in `Host`.
+	pub host: String,
+	/// The path and query.
+	pub target: String,
+	/// Extra `Name: value` header lines.
+	pub headers: Vec<String>,
+	pub body: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+	pub status: u16,
+	/// The status line and headers, exactly as received.
+	pub head: Vec<u8>,
+	/// The body, with any chunked transfer coding removed.
+	pub body: Vec<u8>,
+}
+
+impl Request {
+	/// The request on the wire. It asks the server to close the connection afterwards, so the
+	/// response ends where the stream does.
+	pub fn encode(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		let _ = write!(out, "{} {} HTTP/1.1\r\nHost: {}\r\n", self.method, self.target, self.host);
+		let has = |name: &str| self.headers.iter()
+			.any(|header| header.split(':').next().unwrap_or_default().trim().eq_ignore_ascii_case(name));
+		if !has("user-agent") {
+			let _ = write!(out, "User-Agent: siip-curl/{}\r\n", env!("CARGO_PKG_VERSION"));
+		}
+		if !has("accept") {
+			out.extend_from_slice(b"Accept: */*\r\n");
+		}
+		for header in &self.headers {
+			let _ = write!(out, "{}\r\n", header);
+		}
+		if let Some(body) = &self.body {
+			let _ = write!(out, "Content-Length: {}\r\n", body.len());
+		}
+		out.extend_from_slice(b"Connection: close\r\n\r\n");
+		if let Some(body) = &self.body {
+			out.extend_from_slice(body);
+		}
+		out
+	}
+}
+
+impl Response {
+	/// Parses everything the server sent before closing the connection.
+	pub fn parse(data: &[u8]) -> Result<Self, String> {
+		let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+		let mut response = httparse::Response::new(&mut headers);
+		let head_len = match response.parse(data) {
+			Ok(httparse::Status::Complete(len)) => len,
+			Ok(httparse::Status::Partial) => return Err("the response ended in its headers".into()),
+			Err(e) => return Err(format!("malformed response: {}", e)),
+		};
+		let status = response.code.ok_or("the response has no status")?;
+
+		let header = |name: &str| response.headers.iter()
+			.find(|header| header.name.eq_ignore_ascii_case(name))
+			.map(|header| String::from_utf8_lossy(header.value).trim().to_ascii_lowercase());
+		let rest = &data[head_len..];
+		let body = if header("transfer-encoding").unwrap_or_default().ends_with("chunked") {
+			dechunk(rest)?
+		} else if let Some(length) = header("content-length") {
+			let length: usize = length.parse().map_err(|_| "malformed Content-Length")?;
+			rest.get(..length).ok_or("the response ended in its body")?.to_vec()
+		} else {
+			rest.to_vec()
+		};
+		Ok(Response { status, head: data[..head_len].to_vec(), body })
+	}
+}
+
+/// Removes the chunked transfer coding. Trailers are dropped.
+fn dechunk(mut data: &[u8]) -> Result<Vec<u8>, String> {
+	let mut body = Vec::new();
+	loop {
+		let line_end = data.windows(2).position(|w| w == b"\r\n").ok_or("the response ended in a chunk size")?;
+		let line = String::from_utf8_lossy(&data[..line_end]);
+		// Chunk extensions follow a semicolon, and mean nothing to us
+		let size = line.split(';').next().unwrap_or_default().trim();
+		let size = usize::from_str_radix(size, 16).map_err(|_| format!("malformed chunk size {:?}", size))?;
+		data = &data[line_end + 2..];
+		if size == 0 {
+			return Ok(body);
+		}
+		body.extend_from_slice(data.get(..size).ok_or("the response ended in a chunk")?);
+		data = data.get(size + 2..).ok_or("the response ended in a chunk")?;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn requests() {
+		let request = Request {
+			method: "POST".into(),
+			host: "example.com:8443".into(),
+			target: "/submit?x=1".into(),
+			headers: vec!["Accept: text/plain".into()],
+			body: Some(b"hello".to_vec()),
+		};
+		assert_eq!(String::from_utf8(request.encode()).unwrap(), format!(
+			"POST /submit?x=1 HTTP/1.1\r\nHost: example.com:8443\r\nUser-Agent: siip-curl/{}\r\n\
+			Accept: text/plain\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+			env!("CARGO_PKG_VERSION"),
+		));
+	}
+
+	#[test]
+	fn responses() {
+		let response = Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello, and more").unwrap();
+		assert_eq!((response.status, &response.body[..]), (200, &b"hello"[..]));
+		assert_eq!(response.head, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n");
+
+		let response = Response::parse(b"HTTP/1.1 404 Not Found\r\n\r\nuntil the end").unwrap();
+		assert_eq!((response.status, &response.body[..]), (404, &b"until the end"[..]));
+
+		let response = Response::parse(
+			b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: x\r\n\r\n",
+		).unwrap();
+		assert_eq!(response.body, b"hello, world");
+
+		assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Le").is_err());
+		assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nhello").is_err());
+		assert!(Response::parse(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n").is_err());
+	}
+}
@@ -0,0 +1,202 @@
+//! Fetches HTTPS URLs from servers that hold the key in their SIIP certificate.
+//!
+//! The host is looked up in `CertificateMap` instead of DNS, the connection goes to the
+//! certificate's IP address, and the TLS handshake completes only if the server presents the
+//! certificate's key. Certificate authorities play no part: a server with a CA-signed
+//! certificate for another key is refused, and one with a self-signed certificate for the
+//! right key is trusted.
+
+use std::fmt;
+use std::io;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use rustls::{ClientConfig, TLSError};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::{client::TlsStream, TlsConnector};
+use url::Url;
+use webpki::DNSNameRef;
+
+pub mod http;
+
+use siip_client::{Certificate, Client};
+use siip_dane::PinnedSpki;
+
+/// Why a URL couldn't be fetched. Each class exits with its own status, numbered as in curl
+/// where curl has an equivalent.
+#[derive(Debug)]
+pub enum Error {
+	/// The URL is malformed, or isn't `https`.
+	Url(String),
+	/// The node couldn't be asked about the host.
+	Node(siip_client::Error),
+	/// The host has no SIIP certificate.
+	MissingDomain(String),
+	/// The host's certificate can't be used to connect: its address or key is malformed.
+	BadCertificate(String),
+	/// The server couldn't be reached, or the connection broke.
+	ConnectionFailure(io::Error),
+	/// The server didn't present the key in its certificate, or couldn't prove it holds it.
+	KeyMismatch(String),
+	/// The server's response isn't HTTP.
+	Http(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Url(e) => write!(f, "bad URL: {}", e),
+			Error::Node(e) => write!(f, "{}", e),
+			Error::MissingDomain(host) => write!(f, "{} has no SIIP certificate", host),
+			Error::BadCertificate(e) => write!(f, "unusable SIIP certificate: {}", e),
+			Error::ConnectionFailure(e) => write!(f, "connection failed: {}", e),
+			Error::KeyMismatch(e) => write!(f, "key mismatch: {}", e),
+			Error::Http(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			Error::Url(_) => 3,
+			Error::Node(_) => 5,
+			Error::MissingDomain(_) => 6,
+			Error::ConnectionFailure(_) => 7,
+			Error::Http(_) => 8,
+			Error::BadCertificate(_) | Error::KeyMismatch(_) => 60,
+		}
+	}
+
+	/// Tells a handshake the verifier refused apart from one that broke.
+	fn from_handshake(e: io::Error) -> Self {
+		match e.get_ref().and_then(|inner| inner.downcast_ref::<TLSError>()) {
+			Some(tls @ TLSError::General(_))
+			| Some(tls @ TLSError::NoCertificatesPresented)
+			| Some(tls @ TLSError::WebPKIError(_)) => Error::KeyMismatch(tls.to_string()),
+			_ => Error::ConnectionFailure(e),
+		}
+	}
+}
+
+/// The parts of an `https` URL needed to fetch it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+	pub domain: String,
+	pub port: u16,
+	/// The path and query.
+	pub path: String,
+}
+
+impl Target {
+	pub fn parse(url: &str) -> Result<Self, Error> {
+		let url = Url::parse(url).map_err(|e| Error::Url(e.to_string()))?;
+		if url.scheme() != "https" {
+			return Err(Error::Url(format!("{} isn't https, so there's no key to pin", url.scheme())));
+		}
+		let domain = match url.host() {
+			Some(url::Host::Domain(domain)) => domain.to_ascii_lowercase(),
+			_ => return Err(Error::Url("the host isn't a domain name".into())),
+		};
+		let path = match url.query() {
+			Some(query) => format!("{}?{}", url.path(), query),
+			None => url.path().to_string(),
+		};
+		Ok(Target { domain, port: url.port().unwrap_or(443), path })
+	}
+
+	/// The `Host` header: the port is left out when it's the default.
+	pub fn host(&self) -> String {
+		match self.port {
+			443 => self.domain.clone(),
+			port => format!("{}:{}", self.domain, port),
+		}
+	}
+}
+
+pub struct Curl {
+	pub client: Client,
+}
+
+impl Curl {
+	/// Looks `domain` up on the chain, and opens a TLS connection pinned to its certificate's
+	/// key, or to the key it is rotating to: until the rotation activates, the server may
+	/// present either.
+	pub async fn connect(&self, domain: &str, port: u16) -> Result<(TlsStream<TcpStream>, Certificate), Error> {
+		let cert = self.client.lookup(domain, None).await
+			.map_err(Error::Node)?
+			.ok_or_else(|| Error::MissingDomain(domain.to_string()))?;
+		let ip_addr = String::from_utf8_lossy(&cert.ip_addr);
+		let ip: Ipv4Addr = ip_addr.parse()
+			.map_err(|_| Error::BadCertificate(format!("{} isn't an IPv4 address", ip_addr)))?;
+
+		// A malformed next key pins nothing, but doesn't stop the current key from working
+		let next_key = self.client.key_rotation(domain, None).await
+			.map_err(Error::Node)?
+			.map(|rotation| rotation.next_key)
+			.filter(|key| PinnedSpki::from_key(key).is_ok());
+		let keys: Vec<&[u8]> = Some(&cert.key).into_iter().chain(next_key.as_ref()).map(|key| &key[..]).collect();
+		let stream = connect_pinned(domain, ip, port, &keys).await?;
+		Ok((stream, cert))
+	}
+
+	/// Sends `request` to `target`, and reads the whole response.
+	pub async fn fetch(&self, target: &Target, request: &http::Request) -> Result<(http::Response, Certificate), Error> {
+		let (mut stream, cert) = self.connect(&target.domain, target.port).await?;
+		let response = exchange(&mut stream, request).await?;
+		Ok((response, cert))
+	}
+}
+
+/// Connects to `ip:port` and completes a TLS handshake for `domain` only if the server
+/// presents one of `keys`, in the form a SIIP certificate holds them.
+pub async fn connect_pinned(domain: &str, ip: Ipv4Addr, port: u16, keys: &[&[u8]]) -> Result<TlsStream<TcpStream>, Error> {
+	let dns_name = DNSNameRef::try_from_ascii_str(domain)
+		.map_err(|_| Error::Url(format!("{} isn't a domain name", domain)))?;
+	let verifier = PinnedSpki::from_keys(keys).map_err(|e| Error::BadCertificate(e.to_string()))?;
+
+	let mut config = ClientConfig::new();
+	config.set_protocols(&[b"http/1.1".to_vec()]);
+	config.dangerous().set_certificate_verifier(Arc::new(verifier));
+
+	let tcp = TcpStream::connect((ip, port)).await.map_err(Error::ConnectionFailure)?;
+	TlsConnector::from(Arc::new(config))
+		.connect(dns_name, tcp)
+		.await
+		.map_err(Error::from_handshake)
+}
+
+/// Sends `request` over `stream`, and reads the response until the server closes it.
+pub async fn exchange(stream: &mut TlsStream<TcpStream>, request: &http::Request) -> Result<http::Response, Error> {
+	stream.write_all(&request.encode()).await.map_err(Error::ConnectionFailure)?;
+	let mut data = Vec::new();
+	if let Err(e) = stream.read_to_end(&mut data).await {
+		// Plenty of servers close without a TLS close_notify. What arrived is still
+		// authenticated, and the response's own framing says whether it's complete.
+		if e.kind() != io::ErrorKind::UnexpectedEof {
+			return Err(Error::ConnectionFailure(e));
+		}
+	}
+	http::Response::parse(&data).map_err(Error::Http)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn targets() {
+		let target = Target::parse("https://Example.com:8443/a/b?c=d").unwrap();
+		assert_eq!(target, Target { domain: "example.com".into(), port: 8443, path: "/a/b?c=d".into() });
+		assert_eq!(target.host(), "example.com:8443");
+
+		let target = Target::parse("https://example.com").unwrap();
+		assert_eq!((target.port, target.path.as_str(), target.host()), (443, "/", "example.com".into()));
+
+		assert!(matches!(Target::parse("http://example.com/"), Err(Error::Url(_))));
+		assert!(matches!(Target::parse("https://10.0.0.1/"), Err(Error::Url(_))));
+		assert!(matches!(Target::parse("example.com"), Err(Error::Url(_))));
+	}
+}
@@ -0,0 +1,116 @@
+//! A curl-like HTTPS client that trusts servers by their SIIP certificates.
+//!
+//! Exit statuses follow curl where it has an equivalent: 3 for a bad URL, 6 for a host with
+//! no SIIP certificate, 7 for a connection failure, 60 for a key mismatch and 22 for an HTTP
+//! error with `--fail`. 5 means the node couldn't be asked.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use sp_core::crypto::Ss58Codec;
+use structopt::StructOpt;
+
+use siip_curl::{http, Curl, Error, Target};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "siip-curl", about = "Fetches an HTTPS URL from a server holding its SIIP certificate's key")]
+struct Opt {
+	/// The `https` URL to fetch.
+	url: String,
+
+	/// The request method. Defaults to GET, or POST with `--data`.
+	#[structopt(short = "X", long = "request")]
+	method: Option<String>,
+
+	/// An extra `Name: value` header. Can be repeated.
+	#[structopt(short = "H", long = "header", number_of_values = 1)]
+	headers: Vec<String>,
+
+	/// A request body.
+	#[structopt(short, long)]
+	data: Option<String>,
+
+	/// Print the response headers before the body.
+	#[structopt(short, long)]
+	include: bool,
+
+	/// Describe the lookup and the handshake on stderr.
+	#[structopt(short, long)]
+	verbose: bool,
+
+	/// Write the body to a file instead of stdout.
+	#[structopt(short, long, parse(from_os_str))]
+	output: Option<PathBuf>,
+
+	/// Fail with status 22, printing nothing, if the server answers with an HTTP error.
+	#[structopt(short, long)]
+	fail: bool,
+
+	/// HTTP RPC endpoint of a trusted SIIP node.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	node: String,
+
+	/// How long to wait for the node before giving up, in milliseconds.
+	#[structopt(long, default_value = "5000")]
+	node_timeout: u64,
+}
+
+#[tokio::main]
+async fn main() {
+	let opt = Opt::from_args();
+	if let Err(e) = run(opt).await {
+		eprintln!("siip-curl: ({}) {}", e.exit_code(), e);
+		process::exit(e.exit_code());
+	}
+}
+
+async fn run(opt: Opt) -> Result<(), Error> {
+	let target = Target::parse(&opt.url)?;
+	let body = opt.data.map(String::into_bytes);
+	let request = http::Request {
+		method: opt.method.unwrap_or_else(|| if body.is_some() { "POST" } else { "GET" }.to_string()),
+		host: target.host(),
+		target: target.path.clone(),
+		headers: opt.headers,
+		body,
+	};
+
+	let curl = Curl { client: siip_client::Client::new(opt.node, Duration::from_millis(opt.node_timeout)) };
+	let (mut stream, cert) = curl.connect(&target.domain, target.port).await?;
+	if opt.verbose {
+		eprintln!(
+			"* {} is at {} according to its SIIP certificate (owner {})",
+			target.domain, String::from_utf8_lossy(&cert.ip_addr), cert.owner_id.to_ss58check(),
+		);
+		eprintln!("* The server presented the certificate's key {}", String::from_utf8_lossy(&cert.key));
+		let encoded = request.encode();
+		let head = String::from_utf8_lossy(&encoded);
+		for line in head.split("\r\n\r\n").next().unwrap_or_default().lines() {
+			eprintln!("> {}", line);
+		}
+	}
+	let response = siip_curl::exchange(&mut stream, &request).await?;
+	if opt.verbose {
+		for line in String::from_utf8_lossy(&response.head).lines().filter(|line| !line.is_empty()) {
+			eprintln!("< {}", line);
+		}
+	}
+
+	if opt.fail && response.status >= 400 {
+		eprintln!("siip-curl: (22) the server answered {}", response.status);
+		process::exit(22);
+	}
+	let mut out = if opt.include { response.head } else { Vec::new() };
+	out.extend_from_slice(&response.body);
+	let written = match opt.output {
+		Some(path) => fs::write(path, &out),
+		None => io::stdout().write_all(&out).and_then(|_| io::stdout().flush()),
+	};
+	if let Err(e) = written {
+		eprintln!("siip-curl: (23) can't write the body: {}", e);
+		process::exit(23);
+	}
+	Ok(())
+}
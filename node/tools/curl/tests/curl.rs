@@ -0,0 +1,190 @@
+//! Fetches from local TLS servers, with a stand-in for the node's storage RPC.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use codec::Encode;
+use rustls::{NoClientAuth, ServerConfig};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+use siip_client::{storage, Certificate, Client, KeyRotation, Signer};
+use siip_curl::{http, Curl, Error, Target};
+use siip_node_runtime::pallet_siip;
+
+const PAGE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+
+async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> String {
+	let mut head = Vec::new();
+	while !head.ends_with(b"\r\n\r\n") {
+		head.push(stream.read_u8().await.unwrap());
+	}
+	String::from_utf8(head).unwrap()
+}
+
+/// A TLS server for `domain` that answers every request with `PAGE`. Returns its address and
+/// the public key in the form a SIIP certificate holds it.
+async fn tls_server(domain: &str) -> (SocketAddr, String) {
+	let cert = rcgen::generate_simple_self_signed(vec![domain.to_string()]).unwrap();
+	let key = siip_dane::der_to_key(&cert.get_key_pair().public_key_der());
+	let mut config = ServerConfig::new(NoClientAuth::new());
+	config.set_single_cert(
+		vec![rustls::Certificate(cert.serialize_der().unwrap())],
+		rustls::PrivateKey(cert.serialize_private_key_der()),
+	).unwrap();
+	let acceptor = TlsAcceptor::from(Arc::new(config));
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		loop {
+			let (stream, _) = listener.accept().await.unwrap();
+			let acceptor = acceptor.clone();
+			tokio::spawn(async move {
+				if let Ok(mut stream) = acceptor.accept(stream).await {
+					read_head(&mut stream).await;
+					let _ = stream.write_all(PAGE).await;
+					let _ = stream.shutdown().await;
+				}
+			});
+		}
+	});
+	(addr, key)
+}
+
+fn certificate(domain: &str, key: &str) -> Certificate {
+	Certificate {
+		version_number: pallet_siip::CERTIFICATE_VERSION,
+		owner_id: Signer::from_seed("//Alice").unwrap().account_id(),
+		name: b"Alice".to_vec(),
+		info: b"{}".to_vec(),
+		key: key.as_bytes().to_vec(),
+		ip_addr: b"127.0.0.1".to_vec(),
+		domain: domain.as_bytes().to_vec(),
+	}
+}
+
+/// Answers `state_getStorage` for the `CertificateMap` entries of `certificates`.
+async fn node(certificates: Vec<Certificate>) -> String {
+	node_with_rotations(certificates, Vec::new()).await
+}
+
+/// Answers `state_getStorage` for the `CertificateMap` entries of `certificates`, and the
+/// `KeyRotations` entries of `rotations`.
+async fn node_with_rotations(certificates: Vec<Certificate>, rotations: Vec<(&str, KeyRotation)>) -> String {
+	let storage: HashMap<String, String> = certificates.iter()
+		.map(|cert| (storage::certificate_key(&String::from_utf8_lossy(&cert.domain)), cert.encode()))
+		.chain(rotations.iter().map(|(domain, rotation)| (storage::key_rotation_key(domain), rotation.encode())))
+		.map(|(key, value)| (format!("0x{}", hex::encode(key)), format!("0x{}", hex::encode(value))))
+		.collect();
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let url = format!("http://{}", listener.local_addr().unwrap());
+	tokio::spawn(async move {
+		loop {
+			let (mut stream, _) = listener.accept().await.unwrap();
+			let head = read_head(&mut stream).await.to_ascii_lowercase();
+			let length: usize = head.lines()
+				.find_map(|line| line.strip_prefix("content-length:"))
+				.map(|length| length.trim().parse().unwrap())
+				.unwrap_or(0);
+			let mut body = vec![0u8; length];
+			stream.read_exact(&mut body).await.unwrap();
+
+			let request: Value = serde_json::from_slice(&body).unwrap();
+			assert_eq!(request["method"], "state_getStorage");
+			let result = storage.get(request["params"][0].as_str().unwrap()).cloned();
+			let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string();
+			let _ = stream.write_all(format!(
+				"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+				response.len(), response,
+			).as_bytes()).await;
+		}
+	});
+	url
+}
+
+async fn fetch(node: String, url: &str) -> Result<http::Response, Error> {
+	let curl = Curl { client: Client::new(node, Duration::from_secs(2)) };
+	let target = Target::parse(url)?;
+	let request = http::Request {
+		method: "GET".into(),
+		host: target.host(),
+		target: target.path.clone(),
+		headers: Vec::new(),
+		body: None,
+	};
+	curl.fetch(&target, &request).await.map(|(response, _)| response)
+}
+
+#[tokio::test]
+async fn pinned_key_is_fetched() {
+	let (server, key) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", &key)]).await;
+
+	let response = fetch(node, &format!("https://example.com:{}/", server.port())).await.unwrap();
+	assert_eq!((response.status, &response.body[..]), (200, &b"hello"[..]));
+}
+
+#[tokio::test]
+async fn key_mismatch() {
+	let (server, _) = tls_server("example.com").await;
+	let (_, other_key) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", &other_key)]).await;
+
+	let result = fetch(node, &format!("https://example.com:{}/", server.port())).await;
+	assert!(matches!(result, Err(Error::KeyMismatch(_))), "{:?}", result);
+	assert_eq!(result.unwrap_err().exit_code(), 60);
+}
+
+#[tokio::test]
+async fn next_key_is_accepted_during_rotation() {
+	let (server, next_key) = tls_server("example.com").await;
+	let (_, key) = tls_server("example.com").await;
+	let rotation = KeyRotation { next_key: next_key.into_bytes(), activation: 100 };
+	let node = node_with_rotations(vec![certificate("example.com", &key)], vec![("example.com", rotation)]).await;
+
+	// The server has switched to its next key before the rotation activates
+	let response = fetch(node, &format!("https://example.com:{}/", server.port())).await.unwrap();
+	assert_eq!((response.status, &response.body[..]), (200, &b"hello"[..]));
+}
+
+#[tokio::test]
+async fn missing_domain() {
+	let (server, _) = tls_server("example.com").await;
+	let node = node(Vec::new()).await;
+
+	let result = fetch(node, &format!("https://example.com:{}/", server.port())).await;
+	assert!(matches!(result, Err(Error::MissingDomain(_))), "{:?}", result);
+	assert_eq!(result.unwrap_err().exit_code(), 6);
+}
+
+#[tokio::test]
+async fn connection_failure() {
+	let (_, key) = tls_server("example.com").await;
+	// A port nothing listens on any more
+	let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+	let node = node(vec![certificate("example.com", &key)]).await;
+
+	let result = fetch(node, &format!("https://example.com:{}/", closed)).await;
+	assert!(matches!(result, Err(Error::ConnectionFailure(_))), "{:?}", result);
+	assert_eq!(result.unwrap_err().exit_code(), 7);
+}
+
+#[tokio::test]
+async fn malformed_key() {
+	let (server, _) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", "B4:02:EE:13")]).await;
+
+	let result = fetch(node, &format!("https://example.com:{}/", server.port())).await;
+	assert!(matches!(result, Err(Error::BadCertificate(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn unreachable_node() {
+	let result = fetch("http://127.0.0.1:9".into(), "https://example.com/").await;
+	assert!(matches!(result, Err(Error::Node(_))), "{:?}", result);
+}
@@ -0,0 +1,269 @@
+//! Just enough DER to find the public key inside the X.509 structures SIIP deals with.
+
+use crate::Error;
+
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+/// `[0]`, as used for the optional version of a `TBSCertificate`.
+pub const CONTEXT_0: u8 = 0xA0;
+/// `[1]`, as used for the public key in a SEC 1 private key.
+pub const CONTEXT_1: u8 = 0xA1;
+/// `[1] IMPLICIT`, as used for the public key in a version 2 PKCS #8 private key.
+pub const IMPLICIT_1: u8 = 0x81;
+
+/// The algorithm identifier of RSA keys: `rsaEncryption`, with NULL parameters.
+const RSA_ALGORITHM: &[u8] = &[
+	0x30, 0x0D, 0x06, 0x09, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01, 0x05, 0x00,
+];
+/// The OID `id-ecPublicKey`, which the curve follows in an EC algorithm identifier.
+const EC_PUBLIC_KEY: &[u8] = &[0x06, 0x07, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+
+/// One tag-length-value element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tlv<'a> {
+	pub tag: u8,
+	/// The contents, without the tag and length.
+	pub value: &'a [u8],
+	/// The whole element, including the tag and length.
+	pub raw: &'a [u8],
+}
+
+/// Reads the element at the front of `input`, and returns it along with whatever follows it.
+pub fn read(input: &[u8]) -> Result<(Tlv<'_>, &[u8]), Error> {
+	let tag = *input.first().ok_or(Error::MalformedDer("truncated tag"))?;
+	if tag & 0x1F == 0x1F {
+		return Err(Error::MalformedDer("multi-byte tags are not supported"));
+	}
+
+	let first = *input.get(1).ok_or(Error::MalformedDer("truncated length"))?;
+	let (len, header) = match first {
+		0..=0x7F => (first as usize, 2),
+		0x81..=0x84 => {
+			let count = (first & 0x7F) as usize;
+			let bytes = input.get(2..2 + count).ok_or(Error::MalformedDer("truncated length"))?;
+			let len = bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+			(len, 2 + count)
+		},
+		_ => return Err(Error::MalformedDer("unsupported length encoding")),
+	};
+
+	let end = header.checked_add(len).ok_or(Error::MalformedDer("length overflows"))?;
+	let raw = input.get(..end).ok_or(Error::MalformedDer("truncated value"))?;
+	Ok((Tlv { tag, value: &raw[header..], raw }, &input[end..]))
+}
+
+/// Encodes one element, the inverse of `read`.
+pub fn encode(tag: u8, value: &[u8]) -> Vec<u8> {
+	let mut out = vec![tag];
+	match value.len() {
+		len @ 0..=0x7F => out.push(len as u8),
+		len => {
+			let bytes = (len as u32).to_be_bytes();
+			let skip = bytes.iter().take_while(|b| **b == 0).count();
+			out.push(0x80 | (4 - skip) as u8);
+			out.extend_from_slice(&bytes[skip..]);
+		},
+	}
+	out.extend_from_slice(value);
+	out
+}
+
+/// Reads an element that must have the given tag.
+pub fn expect(input: &[u8], tag: u8) -> Result<(Tlv<'_>, &[u8]), Error> {
+	let (tlv, rest) = read(input)?;
+	if tlv.tag != tag {
+		return Err(Error::MalformedDer("unexpected tag"));
+	}
+	Ok((tlv, rest))
+}
+
+/// Splits the contents of a constructed element into its children.
+pub fn children(mut input: &[u8]) -> Result<Vec<Tlv<'_>>, Error> {
+	let mut children = Vec::new();
+	while !input.is_empty() {
+		let (child, rest) = read(input)?;
+		children.push(child);
+		input = rest;
+	}
+	Ok(children)
+}
+
+/// Checks that `der` is exactly one `SubjectPublicKeyInfo`: an algorithm and a key.
+pub fn check_spki(der: &[u8]) -> Result<(), Error> {
+	let (spki, rest) = expect(der, SEQUENCE)?;
+	if !rest.is_empty() {
+		return Err(Error::MalformedDer("trailing data after the public key"));
+	}
+	match &children(spki.value)?[..] {
+		[algorithm, key] if algorithm.tag == SEQUENCE && key.tag == BIT_STRING => Ok(()),
+		_ => Err(Error::MalformedDer("not a SubjectPublicKeyInfo")),
+	}
+}
+
+/// Finds the `SubjectPublicKeyInfo` in a DER-encoded X.509 certificate.
+pub fn spki_from_certificate(der: &[u8]) -> Result<&[u8], Error> {
+	let (cert, _) = expect(der, SEQUENCE)?;
+	let (tbs, _) = expect(cert.value, SEQUENCE)?;
+	let mut fields = children(tbs.value)?;
+
+	// The version is optional, and everything else is positional
+	if fields.first().map(|f| f.tag) == Some(CONTEXT_0) {
+		fields.remove(0);
+	}
+	// serialNumber, signature, issuer, validity, subject, subjectPublicKeyInfo
+	let spki = fields.get(5).ok_or(Error::MalformedDer("certificate has no public key"))?;
+	check_spki(spki.raw)?;
+	Ok(spki.raw)
+}
+
+/// Finds the `SubjectPublicKeyInfo` in a DER-encoded PKCS #10 certificate signing request.
+pub fn spki_from_csr(der: &[u8]) -> Result<&[u8], Error> {
+	let (csr, _) = expect(der, SEQUENCE)?;
+	let (info, _) = expect(csr.value, SEQUENCE)?;
+	// version, subject, subjectPKInfo, attributes
+	let spki = children(info.value)?.get(2).copied().ok_or(Error::MalformedDer("request has no public key"))?;
+	check_spki(spki.raw)?;
+	Ok(spki.raw)
+}
+
+/// Builds the `SubjectPublicKeyInfo` for a DER-encoded PKCS #8 private key. Only the public
+/// halves already written into the key are used: RSA keys always carry them, EC keys nearly
+/// always do, and other algorithms must use version 2 of the format.
+pub fn spki_from_private_key(der: &[u8]) -> Result<Vec<u8>, Error> {
+	let (info, _) = expect(der, SEQUENCE)?;
+	let fields = children(info.value)?;
+	let (algorithm, private_key) = match &fields[..] {
+		[version, algorithm, private_key, ..] if version.tag == INTEGER
+			&& algorithm.tag == SEQUENCE && private_key.tag == OCTET_STRING => (algorithm, private_key),
+		_ => return Err(Error::MalformedDer("not a PKCS #8 private key")),
+	};
+
+	let spki = if let Some(public_key) = fields[3..].iter().find(|f| f.tag == IMPLICIT_1) {
+		spki(algorithm.raw, &encode(BIT_STRING, public_key.value))
+	} else if algorithm.raw == RSA_ALGORITHM {
+		spki_from_rsa_private_key(private_key.value)?
+	} else if algorithm.value.starts_with(EC_PUBLIC_KEY) {
+		let (_, public_key) = ec_private_key(private_key.value)?;
+		spki(algorithm.raw, public_key.ok_or(Error::MalformedDer("the key doesn't include its public key"))?.raw)
+	} else {
+		return Err(Error::MalformedDer("the key doesn't include its public key"));
+	};
+	check_spki(&spki)?;
+	Ok(spki)
+}
+
+/// Builds the `SubjectPublicKeyInfo` for a DER-encoded PKCS #1 RSA private key.
+pub fn spki_from_rsa_private_key(der: &[u8]) -> Result<Vec<u8>, Error> {
+	let (key, _) = expect(der, SEQUENCE)?;
+	match &children(key.value)?[..] {
+		// version, modulus, publicExponent, ...
+		[version, modulus, exponent, ..] if [version.tag, modulus.tag, exponent.tag] == [INTEGER; 3] => {
+			spki_from_rsa_public_key(&encode(SEQUENCE, &[modulus.raw, exponent.raw].concat()))
+		},
+		_ => Err(Error::MalformedDer("not an RSA private key")),
+	}
+}
+
+/// Wraps a DER-encoded PKCS #1 RSA public key in a `SubjectPublicKeyInfo`.
+pub fn spki_from_rsa_public_key(der: &[u8]) -> Result<Vec<u8>, Error> {
+	let (key, rest) = expect(der, SEQUENCE)?;
+	match &children(key.value)?[..] {
+		[modulus, exponent] if rest.is_empty() && [modulus.tag, exponent.tag] == [INTEGER; 2] => {
+			Ok(spki(RSA_ALGORITHM, &encode(BIT_STRING, &[&[0], der].concat())))
+		},
+		_ => Err(Error::MalformedDer("not an RSA public key")),
+	}
+}
+
+/// Builds the `SubjectPublicKeyInfo` for a DER-encoded SEC 1 EC private key. The key must
+/// name its curve and include its public key, as openssl writes them.
+pub fn spki_from_ec_private_key(der: &[u8]) -> Result<Vec<u8>, Error> {
+	let (curve, public_key) = ec_private_key(der)?;
+	let curve = curve.ok_or(Error::MalformedDer("the key doesn't name its curve"))?;
+	let public_key = public_key.ok_or(Error::MalformedDer("the key doesn't include its public key"))?;
+	let spki = spki(&encode(SEQUENCE, &[EC_PUBLIC_KEY, curve.raw].concat()), public_key.raw);
+	check_spki(&spki)?;
+	Ok(spki)
+}
+
+/// The curve and public key in a SEC 1 `ECPrivateKey`, where present.
+fn ec_private_key(der: &[u8]) -> Result<(Option<Tlv<'_>>, Option<Tlv<'_>>), Error> {
+	let (key, _) = expect(der, SEQUENCE)?;
+	let fields = children(key.value)?;
+	match &fields[..] {
+		[version, private_key, ..] if version.tag == INTEGER && private_key.tag == OCTET_STRING => (),
+		_ => return Err(Error::MalformedDer("not an EC private key")),
+	}
+	let public_key = explicit(&fields[2..], CONTEXT_1)?;
+	if matches!(public_key, Some(key) if key.tag != BIT_STRING) {
+		return Err(Error::MalformedDer("not an EC private key"));
+	}
+	Ok((explicit(&fields[2..], CONTEXT_0)?, public_key))
+}
+
+/// The element wrapped in the explicitly tagged field `tag`, if there is one.
+fn explicit<'a>(fields: &[Tlv<'a>], tag: u8) -> Result<Option<Tlv<'a>>, Error> {
+	match fields.iter().find(|f| f.tag == tag) {
+		Some(field) => Ok(Some(read(field.value)?.0)),
+		None => Ok(None),
+	}
+}
+
+fn spki(algorithm: &[u8], public_key: &[u8]) -> Vec<u8> {
+	encode(SEQUENCE, &[algorithm, public_key].concat())
+}
+
+/// Finds or builds the `SubjectPublicKeyInfo` in DER of unknown type: a certificate, a
+/// signing request, a public key, or an unencrypted private key.
+pub fn spki_from_any(der: &[u8]) -> Result<Vec<u8>, Error> {
+	check_spki(der).map(|_| der.to_vec())
+		.or_else(|_| spki_from_certificate(der).map(<[u8]>::to_vec))
+		.or_else(|_| spki_from_csr(der).map(<[u8]>::to_vec))
+		.or_else(|_| spki_from_private_key(der))
+		.or_else(|_| spki_from_rsa_private_key(der))
+		.or_else(|_| spki_from_ec_private_key(der))
+		.map_err(|_| Error::MalformedDer("not a certificate, signing request or key"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn short_and_long_lengths() {
+		let (tlv, rest) = read(&[0x02, 0x01, 0x05, 0xFF]).unwrap();
+		assert_eq!((tlv.tag, tlv.value, rest), (INTEGER, &[0x05][..], &[0xFF][..]));
+
+		let mut long = vec![0x04, 0x81, 0x80];
+		long.extend(vec![0u8; 0x80]);
+		let (tlv, rest) = read(&long).unwrap();
+		assert_eq!((tlv.value.len(), rest.len()), (0x80, 0));
+	}
+
+	#[test]
+	fn malformed_input() {
+		assert!(read(&[]).is_err());
+		assert!(read(&[0x30]).is_err());
+		assert!(read(&[0x30, 0x05, 0x00]).is_err());
+		assert!(read(&[0x30, 0x80]).is_err());
+		assert!(read(&[0x30, 0x85, 1, 1, 1, 1, 1]).is_err());
+		assert!(read(&[0x30, 0x84, 0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+		assert!(read(&[0x1F, 0x00]).is_err());
+	}
+
+	#[test]
+	fn encode_round_trip() {
+		for len in &[0usize, 0x7F, 0x80, 0x100, 0x10000] {
+			let value = vec![0xAB; *len];
+			let encoded = encode(OCTET_STRING, &value);
+			let (tlv, rest) = read(&encoded).unwrap();
+			assert_eq!((tlv.tag, tlv.value, rest.len()), (OCTET_STRING, &value[..], 0));
+		}
+		assert_eq!(encode(NULL, &[]), vec![0x05, 0x00]);
+		assert_eq!(&encode(OCTET_STRING, &[0; 0x80])[..3], &[0x04, 0x81, 0x80]);
+	}
+}
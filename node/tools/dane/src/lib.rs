@@ -0,0 +1,208 @@
+//! Conversions between the `key` field of a SIIP certificate, the DER-encoded public key it
+//! stands for, and the DANE TLSA records (RFC 6698) that pin that key.
+//!
+//! A certificate's key is a `SubjectPublicKeyInfo`, written as colon-separated hexadecimal
+//! (`30:59:30:13:...`). SIIP vouches for the key itself rather than for any CA, so the
+//! matching TLSA record is always "DANE-EE" (usage 3) over the public key (selector 1).
+
+use std::fmt;
+use sha2::{Digest, Sha256};
+
+pub mod der;
+pub mod pem;
+#[cfg(feature = "verifier")]
+mod verifier;
+
+#[cfg(feature = "verifier")]
+pub use verifier::PinnedSpki;
+
+/// Certificate usage 3, DANE-EE: the record pins the server's own key, no CA involved.
+pub const USAGE_DANE_EE: u8 = 3;
+/// Selector 1, SPKI: the record covers the `SubjectPublicKeyInfo`, not the whole certificate.
+pub const SELECTOR_SPKI: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+	/// The key isn't colon-separated pairs of hexadecimal digits.
+	MalformedKey,
+	/// The bytes aren't the DER structure they should be.
+	MalformedDer(&'static str),
+	/// The text isn't well-formed PEM.
+	MalformedPem(&'static str),
+	/// The PEM block holds something no public key can be taken from.
+	UnsupportedPem(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::MalformedKey => write!(f, "key is not colon-separated hexadecimal"),
+			Error::MalformedDer(e) => write!(f, "malformed DER: {}", e),
+			Error::MalformedPem(e) => write!(f, "malformed PEM: {}", e),
+			Error::UnsupportedPem(label) => write!(f, "can't take a public key from a PEM {}", label),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Decodes a certificate's `key` field into the DER public key it spells out.
+pub fn key_to_der(key: &[u8]) -> Result<Vec<u8>, Error> {
+	key.split(|c| *c == b':')
+		.map(|pair| match pair {
+			[_, _] => hex::decode(pair).map(|byte| byte[0]).map_err(|_| Error::MalformedKey),
+			_ => Err(Error::MalformedKey),
+		})
+		.collect()
+}
+
+/// Encodes a DER public key the way a certificate's `key` field expects it.
+pub fn der_to_key(der: &[u8]) -> String {
+	der.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// Finds or builds the `SubjectPublicKeyInfo` in the contents of a PEM or DER file holding a
+/// certificate, signing request, public key or unencrypted private key.
+pub fn spki_from_file(contents: &[u8]) -> Result<Vec<u8>, Error> {
+	match std::str::from_utf8(contents) {
+		Ok(text) if text.contains("-----BEGIN ") => pem::spki_from_pem(text),
+		_ => der::spki_from_any(contents),
+	}
+}
+
+/// How a TLSA record's data relates to the key it pins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Matching {
+	/// The data is the `SubjectPublicKeyInfo` itself.
+	Full = 0,
+	/// The data is the SHA-256 hash of the `SubjectPublicKeyInfo`.
+	Sha256 = 1,
+}
+
+/// A TLSA record pinning one public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tlsa {
+	pub usage: u8,
+	pub selector: u8,
+	pub matching: Matching,
+	pub data: Vec<u8>,
+}
+
+impl Tlsa {
+	/// The DANE-EE record for a DER-encoded `SubjectPublicKeyInfo`.
+	pub fn from_spki(spki: &[u8], matching: Matching) -> Self {
+		let data = match matching {
+			Matching::Full => spki.to_vec(),
+			Matching::Sha256 => Sha256::digest(spki).to_vec(),
+		};
+		Tlsa { usage: USAGE_DANE_EE, selector: SELECTOR_SPKI, matching, data }
+	}
+
+	/// The DANE-EE record for a certificate's `key` field, which must hold a well-formed
+	/// `SubjectPublicKeyInfo`.
+	pub fn from_key(key: &[u8], matching: Matching) -> Result<Self, Error> {
+		let spki = key_to_der(key)?;
+		der::check_spki(&spki)?;
+		Ok(Self::from_spki(&spki, matching))
+	}
+
+	/// Whether a server presenting `spki` satisfies this record.
+	pub fn matches(&self, spki: &[u8]) -> bool {
+		self.usage == USAGE_DANE_EE
+			&& self.selector == SELECTOR_SPKI
+			&& *self == Self::from_spki(spki, self.matching)
+	}
+
+	/// The record's wire-format RDATA.
+	pub fn rdata(&self) -> Vec<u8> {
+		let mut rdata = vec![self.usage, self.selector, self.matching as u8];
+		rdata.extend_from_slice(&self.data);
+		rdata
+	}
+}
+
+/// The presentation format, as it would appear in a zone file: `3 1 1 9F622C...`.
+impl fmt::Display for Tlsa {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} {} {} {}", self.usage, self.selector, self.matching as u8, hex::encode_upper(&self.data))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// An EC P-256 key, and a self-signed certificate for it, made with openssl
+	const EC_SPKI: &str = "3059301306072a8648ce3d020106082a8648ce3d030107034200048e3db08ecc6a613c44c8073035b66cbfabe4ecb36f569a2e06a81fae2a1baa530d5bd72a0a747c6466f05717f6a632169cfc8721b7e67dd42700a57353659beb";
+	const EC_SHA256: &str = "9f622c508712c4afa5dabecac5a0a58c401d9005765dde0747294570832570e6";
+	const EC_CERT: &str = "3082018130820127a00302010202141739164fc96d8ebff92aa01aec2f764e5653c7b3300a06082a8648ce3d04030230163114301206035504030c0b6578616d706c652e636f6d301e170d3236313031393036303433355a170d3336313031363036303433355a30163114301206035504030c0b6578616d706c652e636f6d3059301306072a8648ce3d020106082a8648ce3d030107034200048e3db08ecc6a613c44c8073035b66cbfabe4ecb36f569a2e06a81fae2a1baa530d5bd72a0a747c6466f05717f6a632169cfc8721b7e67dd42700a57353659beba3533051301d0603551d0e04160414bf2e52c7363d2b6e75b9a4809aed81848e4e8d32301f0603551d23041830168014bf2e52c7363d2b6e75b9a4809aed81848e4e8d32300f0603551d130101ff040530030101ff300a06082a8648ce3d04030203480030450220540e09c05cd4f1d3563cc55d49bec2cf385c4b7809237c329f6e1a0507c3e91d02210096004f5ebc893ae108152a9c5605ca5c43d0906f90df9821b8d82f563177b3c0";
+
+	// An RSA-1024 key
+	const RSA_SPKI: &str = "30819f300d06092a864886f70d010101050003818d0030818902818100c429e4407d543c2e602bcea739ad91c116c00de79ba37b88cb26f964998121df952723d3fc7e4d95a260d888a0df6a991b4f91182ea65452abb84ab45bdc3e93705b37e743de35123c53d0e3810ee7432a83c05296f1f7ede635aa7e4cf13f3dcb871c72f21465208b299f6aa129067b66b32a9629d2d54c72fb52253c9cd81b0203010001";
+	const RSA_SHA256: &str = "3c93d47bb66d744ba4ba209215669fae19ae5cb5a863ee8447fd86e26d64d4c0";
+
+	fn key(spki_hex: &str) -> Vec<u8> {
+		der_to_key(&hex::decode(spki_hex).unwrap()).into_bytes()
+	}
+
+	#[test]
+	fn key_round_trip() {
+		assert_eq!(der_to_key(&[0xB4, 0x02, 0xEE, 0x13]), "B4:02:EE:13");
+		assert_eq!(key_to_der(b"B4:02:EE:13"), Ok(vec![0xB4, 0x02, 0xEE, 0x13]));
+		let spki = hex::decode(EC_SPKI).unwrap();
+		assert_eq!(key_to_der(&key(EC_SPKI)), Ok(spki));
+	}
+
+	#[test]
+	fn malformed_keys() {
+		assert_eq!(key_to_der(b""), Err(Error::MalformedKey));
+		assert_eq!(key_to_der(b"01:23:4"), Err(Error::MalformedKey));
+		assert_eq!(key_to_der(b"01:23:"), Err(Error::MalformedKey));
+		assert_eq!(key_to_der(b"0123"), Err(Error::MalformedKey));
+		assert_eq!(key_to_der(b"01:G3"), Err(Error::MalformedKey));
+	}
+
+	#[test]
+	fn known_spki_hashes() {
+		let tlsa = Tlsa::from_key(&key(EC_SPKI), Matching::Sha256).unwrap();
+		assert_eq!(hex::encode(&tlsa.data), EC_SHA256);
+		let tlsa = Tlsa::from_key(&key(RSA_SPKI), Matching::Sha256).unwrap();
+		assert_eq!(hex::encode(&tlsa.data), RSA_SHA256);
+	}
+
+	#[test]
+	fn full_match_is_the_key() {
+		let tlsa = Tlsa::from_key(&key(RSA_SPKI), Matching::Full).unwrap();
+		assert_eq!(hex::encode(&tlsa.data), RSA_SPKI);
+		assert_eq!(&tlsa.rdata()[..3], &[3, 1, 0]);
+	}
+
+	#[test]
+	fn presentation_and_wire_format() {
+		let tlsa = Tlsa::from_key(&key(EC_SPKI), Matching::Sha256).unwrap();
+		assert_eq!(tlsa.to_string(), format!("3 1 1 {}", EC_SHA256.to_uppercase()));
+		let mut rdata = vec![3, 1, 1];
+		rdata.extend(hex::decode(EC_SHA256).unwrap());
+		assert_eq!(tlsa.rdata(), rdata);
+	}
+
+	#[test]
+	fn not_a_public_key() {
+		// Well-formed hex, but not a SubjectPublicKeyInfo
+		assert!(matches!(Tlsa::from_key(b"B4:02:EE:13", Matching::Sha256), Err(Error::MalformedDer(_))));
+		let mut spki = hex::decode(EC_SPKI).unwrap();
+		spki.push(0);
+		assert!(Tlsa::from_key(der_to_key(&spki).as_bytes(), Matching::Full).is_err());
+	}
+
+	#[test]
+	fn certificate_matches_its_key() {
+		let cert = hex::decode(EC_CERT).unwrap();
+		let spki = der::spki_from_certificate(&cert).unwrap();
+		assert_eq!(hex::encode(spki), EC_SPKI);
+
+		let tlsa = Tlsa::from_key(&key(EC_SPKI), Matching::Sha256).unwrap();
+		assert!(tlsa.matches(spki));
+		assert!(!tlsa.matches(&hex::decode(RSA_SPKI).unwrap()));
+	}
+}
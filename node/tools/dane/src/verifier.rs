@@ -0,0 +1,61 @@
+//! A rustls verifier with DANE-EE semantics: the server is trusted if and only if its
+//! certificate carries the key registered on the chain, whoever issued it and whatever it says.
+
+use rustls::{Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+use webpki::DNSNameRef;
+
+use crate::{der, key_to_der, Error};
+
+pub struct PinnedSpki {
+	spkis: Vec<Vec<u8>>,
+}
+
+impl PinnedSpki {
+	/// Pins a DER-encoded `SubjectPublicKeyInfo`.
+	pub fn new(spki: Vec<u8>) -> Self {
+		PinnedSpki { spkis: vec![spki] }
+	}
+
+	/// Pins the key in a certificate's `key` field.
+	pub fn from_key(key: &[u8]) -> Result<Self, Error> {
+		Self::from_keys(&[key])
+	}
+
+	/// Pins several keys in the form of a certificate's `key` field, any of which the server may
+	/// present: a certificate's key and its next key during a key rotation, say.
+	pub fn from_keys(keys: &[&[u8]]) -> Result<Self, Error> {
+		let spkis = keys.iter()
+			.map(|key| {
+				let spki = key_to_der(key)?;
+				der::check_spki(&spki)?;
+				Ok(spki)
+			})
+			.collect::<Result<_, Error>>()?;
+		Ok(PinnedSpki { spkis })
+	}
+}
+
+impl ServerCertVerifier for PinnedSpki {
+	// Names and dates are deliberately ignored: the chain, not the certificate, vouches for
+	// the domain. rustls still checks the handshake signature against the presented key, so
+	// the server must hold the matching private key.
+	fn verify_server_cert(
+		&self,
+		_roots: &RootCertStore,
+		presented_certs: &[Certificate],
+		_dns_name: DNSNameRef,
+		_ocsp_response: &[u8],
+	) -> Result<ServerCertVerified, TLSError> {
+		let end_entity = presented_certs.first().ok_or(TLSError::NoCertificatesPresented)?;
+		let spki = der::spki_from_certificate(&end_entity.0)
+			.map_err(|e| TLSError::General(e.to_string()))?;
+		if self.spkis.iter().any(|pinned| spki == &pinned[..]) {
+			Ok(ServerCertVerified::assertion())
+		} else {
+			Err(TLSError::General(format!(
+				"server key {} doesn't match its SIIP certificate",
+				crate::der_to_key(spki),
+			)))
+		}
+	}
+}
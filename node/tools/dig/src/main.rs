@@ -0,0 +1,171 @@
+//! A dig-like query tool for SIIP certificates.
+//!
+//! `siip-dig example.com` prints the certificate for a domain, `siip-dig -x 10.0.0.1` the
+//! certificates pointing at an address, and `siip-dig --owner 5Grw...` the certificates an
+//! account owns. Every answer names the block it was read at. As with dig, `@NODE` picks the
+//! node to ask and `+short`, `+json`, `+trace` and `+proof` change what is printed.
+
+use std::process;
+use std::time::Duration;
+use sp_core::crypto::Ss58Codec;
+use structopt::StructOpt;
+use siip_client::{AccountId, Client};
+use siip_node_runtime::Hash;
+
+mod output;
+mod query;
+
+use output::Format;
+use query::{Dig, Query};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "siip-dig", about = "Looks up SIIP certificates, in the style of dig")]
+struct Opt {
+	/// Look up the certificates pointing at an IP address instead of a domain.
+	#[structopt(short = "x", long)]
+	reverse: bool,
+
+	/// Look up the certificates owned by an SS58 address instead of a domain.
+	#[structopt(long, conflicts_with = "reverse")]
+	owner: bool,
+
+	/// Read at this block hash instead of the best block.
+	#[structopt(long)]
+	at: Option<Hash>,
+
+	/// HTTP RPC endpoint of the node to ask, unless `@NODE` is given.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	node: String,
+
+	/// How long to wait for the node before giving up, in milliseconds.
+	#[structopt(long, default_value = "5000")]
+	node_timeout: u64,
+
+	/// What to look up, followed by any of `@NODE`, `+short`, `+json`, `+trace` and `+proof`.
+	args: Vec<String>,
+}
+
+/// What the arguments after the options ask for.
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+	subject: String,
+	node: Option<String>,
+	format: Format,
+	trace: bool,
+	proof: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Request, String> {
+	let mut subject = None;
+	let mut request = Request { subject: String::new(), node: None, format: Format::Full, trace: false, proof: false };
+	for arg in args {
+		if let Some(node) = arg.strip_prefix('@') {
+			request.node = Some(node_url(node));
+		} else if let Some(option) = arg.strip_prefix('+') {
+			match option {
+				"short" => request.format = Format::Short,
+				"json" => request.format = Format::Json,
+				"trace" => request.trace = true,
+				"proof" => request.proof = true,
+				_ => return Err(format!("unknown option +{}", option)),
+			}
+		} else if subject.replace(arg.clone()).is_some() {
+			return Err(format!("unexpected argument {}", arg));
+		}
+	}
+	request.subject = subject.ok_or("nothing to look up")?;
+	Ok(request)
+}
+
+/// `@host` and `@host:port` mean the node's default HTTP RPC endpoint on that host.
+fn node_url(node: &str) -> String {
+	match (node.contains("://"), node.contains(':')) {
+		(true, _) => node.to_string(),
+		(false, true) => format!("http://{}", node),
+		(false, false) => format!("http://{}:9933", node),
+	}
+}
+
+/// Exits with 0 if a certificate was found, 1 if none was, and 2 on any error.
+#[tokio::main]
+async fn main() {
+	let opt = Opt::from_args();
+	let request = parse_args(&opt.args).unwrap_or_else(|e| {
+		eprintln!("siip-dig: {}", e);
+		process::exit(2);
+	});
+	let query = if opt.reverse {
+		Query::Reverse(request.subject.clone())
+	} else if opt.owner {
+		Query::Owner(AccountId::from_ss58check(&request.subject).unwrap_or_else(|_| {
+			eprintln!("siip-dig: {} isn't an SS58 address", request.subject);
+			process::exit(2);
+		}))
+	} else {
+		Query::Forward(request.subject.clone())
+	};
+
+	let node = request.node.unwrap_or(opt.node);
+	let dig = Dig::new(Client::new(node.clone(), Duration::from_millis(opt.node_timeout)), request.trace);
+	let result = dig.query(&query, opt.at, request.proof).await;
+	let trace = dig.take_trace();
+	if request.format != Format::Json {
+		print!("{}", output::trace(&trace));
+	}
+
+	let trace = if request.trace { Some(trace) } else { None };
+
+	let answer = match result {
+		Ok(answer) => answer,
+		Err(e) => {
+			if request.format == Format::Json {
+				println!("{:#}", serde_json::json!({ "error": e.to_string(), "trace": trace }));
+			}
+			eprintln!("siip-dig: {}", e);
+			process::exit(2);
+		},
+	};
+	match request.format {
+		Format::Full => print!("{}", output::full(&query, &node, &answer)),
+		Format::Short => print!("{}", output::short(&query, &answer)),
+		Format::Json => println!("{:#}", output::json(&query, &node, &answer, trace)),
+	}
+	if answer.certificates.is_empty() {
+		process::exit(1);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn dig_style_arguments() {
+		let request = parse_args(&args(&["example.com", "@10.0.0.2", "+short", "+proof"])).unwrap();
+		assert_eq!(request, Request {
+			subject: "example.com".into(),
+			node: Some("http://10.0.0.2:9933".into()),
+			format: Format::Short,
+			trace: false,
+			proof: true,
+		});
+
+		let request = parse_args(&args(&["+trace", "+json", "example.com"])).unwrap();
+		assert_eq!((request.format, request.trace, request.node), (Format::Json, true, None));
+
+		assert!(parse_args(&args(&["example.com", "+verbose"])).is_err());
+		assert!(parse_args(&args(&["example.com", "example.org"])).is_err());
+		assert!(parse_args(&args(&["+short"])).is_err());
+	}
+
+	#[test]
+	fn node_urls() {
+		assert_eq!(node_url("localhost"), "http://localhost:9933");
+		assert_eq!(node_url("localhost:9944"), "http://localhost:9944");
+		assert_eq!(node_url("https://node.example.com"), "https://node.example.com");
+	}
+}
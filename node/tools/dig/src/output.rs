@@ -0,0 +1,180 @@
+//! How answers are printed: in dig's layout, one value per line with `+short`, or as JSON.
+
+use serde_json::{json, Value};
+use sp_core::crypto::Ss58Codec;
+use siip_client::Certificate;
+
+use crate::query::{Answer, Query};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	Full,
+	Short,
+	Json,
+}
+
+/// Sections in the style of dig: the question, the answer, and where it was read.
+pub fn full(query: &Query, node: &str, answer: &Answer) -> String {
+	let (subject, kind) = describe(query);
+	let mut out = format!("; <<>> siip-dig {} <<>> {}\n", env!("CARGO_PKG_VERSION"), subject);
+	let status = if answer.certificates.is_empty() { "NOTFOUND" } else { "FOUND" };
+	out += &format!(";; status: {}, certificates: {}\n\n", status, answer.certificates.len());
+	out += &format!(";; QUESTION SECTION:\n;{}\t\t{}\n\n", subject, kind);
+
+	if !answer.certificates.is_empty() {
+		out += ";; ANSWER SECTION:\n";
+		for cert in &answer.certificates {
+			out += &format!(
+				"{}\tip={}\towner={}\tkey={}\tname=\"{}\"\tinfo={}\tversion={}\n",
+				text(&cert.domain), text(&cert.ip_addr), cert.owner_id.to_ss58check(), text(&cert.key),
+				text(&cert.name), text(&cert.info), cert.version_number,
+			);
+		}
+		out += "\n";
+	}
+
+	out += &format!(";; BLOCK: #{} {:?}\n", answer.block.number, answer.block.hash);
+	out += &format!(";; STATE ROOT: {:?}\n", answer.block.state_root);
+	if let Some(nodes) = answer.proof {
+		out += &format!(";; PROOF: verified, {} trie nodes\n", nodes);
+	}
+	out += &format!(";; SERVER: {}\n", node);
+	out
+}
+
+/// What a script wants from each certificate: the address for a domain, or the domain for
+/// an address or owner.
+pub fn short(query: &Query, answer: &Answer) -> String {
+	answer.certificates.iter()
+		.map(|cert| match query {
+			Query::Forward(_) => text(&cert.ip_addr),
+			Query::Reverse(_) | Query::Owner(_) => text(&cert.domain),
+		})
+		.map(|line| line + "\n")
+		.collect()
+}
+
+/// The whole answer, with certificates in the same shape as the node's lookup RPCs.
+pub fn json(query: &Query, node: &str, answer: &Answer, trace: Option<Vec<Value>>) -> Value {
+	let (subject, kind) = describe(query);
+	let mut out = json!({
+		"query": { "type": kind.to_ascii_lowercase(), "subject": subject },
+		"node": node,
+		"block": {
+			"number": answer.block.number,
+			"hash": answer.block.hash,
+			"stateRoot": answer.block.state_root,
+		},
+		"certificates": answer.certificates.iter().map(certificate).collect::<Vec<_>>(),
+		"proof": answer.proof.map(|nodes| json!({ "verified": true, "nodes": nodes })),
+	});
+	if let Some(trace) = trace {
+		out["trace"] = Value::Array(trace);
+	}
+	out
+}
+
+/// The RPC calls behind an answer, as comments to print before it.
+pub fn trace(calls: &[Value]) -> String {
+	let mut out = String::new();
+	for call in calls {
+		out += &format!(";; {} {}\n", call["method"].as_str().unwrap_or_default(), call["params"]);
+		match call["outcome"].get("error") {
+			Some(error) => out += &format!(";;   !! {}\n", error.as_str().unwrap_or_default()),
+			None => out += &format!(";;   -> {}\n", call["outcome"]["result"]),
+		}
+	}
+	out
+}
+
+fn certificate(cert: &Certificate) -> Value {
+	json!({
+		"versionNumber": cert.version_number,
+		"ownerId": cert.owner_id.to_ss58check(),
+		"name": text(&cert.name),
+		"info": text(&cert.info),
+		"key": text(&cert.key),
+		"ipAddr": text(&cert.ip_addr),
+		"domain": text(&cert.domain),
+	})
+}
+
+fn describe(query: &Query) -> (String, &'static str) {
+	match query {
+		Query::Forward(domain) => (domain.clone(), "CERTIFICATE"),
+		Query::Reverse(ip_addr) => (ip_addr.clone(), "REVERSE"),
+		Query::Owner(owner) => (owner.to_ss58check(), "OWNER"),
+	}
+}
+
+fn text(bytes: &[u8]) -> String {
+	String::from_utf8_lossy(bytes).into()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use siip_client::{AccountId, Signer};
+	use siip_node_runtime::{pallet_siip, Hash};
+	use crate::query::Block;
+
+	fn alice() -> AccountId {
+		Signer::from_seed("//Alice").unwrap().account_id()
+	}
+
+	fn answer(domains: &[&str]) -> Answer {
+		let certificates = domains.iter()
+			.map(|domain| Certificate {
+				version_number: pallet_siip::CERTIFICATE_VERSION,
+				owner_id: alice(),
+				name: b"Alice".to_vec(),
+				info: b"{}".to_vec(),
+				key: b"AB:CD".to_vec(),
+				ip_addr: b"10.0.0.1".to_vec(),
+				domain: domain.as_bytes().to_vec(),
+			})
+			.collect();
+		Answer {
+			block: Block { hash: Hash::repeat_byte(1), number: 12, state_root: Hash::repeat_byte(2) },
+			certificates,
+			proof: Some(3),
+		}
+	}
+
+	#[test]
+	fn short_prints_one_value_per_certificate() {
+		let answer = answer(&["a.com", "b.com"]);
+		assert_eq!(short(&Query::Forward("a.com".into()), &answer), "10.0.0.1\n10.0.0.1\n");
+		assert_eq!(short(&Query::Reverse("10.0.0.1".into()), &answer), "a.com\nb.com\n");
+		assert_eq!(short(&Query::Owner(alice()), &answer), "a.com\nb.com\n");
+		assert_eq!(short(&Query::Forward("c.com".into()), &self::answer(&[])), "");
+	}
+
+	#[test]
+	fn full_names_the_block() {
+		let out = full(&Query::Forward("a.com".into()), "http://127.0.0.1:9933", &answer(&["a.com"]));
+		assert!(out.contains(";; status: FOUND, certificates: 1"));
+		assert!(out.contains(";a.com\t\tCERTIFICATE"));
+		assert!(out.contains("a.com\tip=10.0.0.1\towner=5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"));
+		assert!(out.contains(&format!(";; BLOCK: #12 {:?}", Hash::repeat_byte(1))));
+		assert!(out.contains(";; PROOF: verified, 3 trie nodes"));
+
+		let out = full(&Query::Forward("c.com".into()), "http://127.0.0.1:9933", &answer(&[]));
+		assert!(out.contains(";; status: NOTFOUND"));
+		assert!(!out.contains("ANSWER SECTION"));
+	}
+
+	#[test]
+	fn json_matches_rpc_field_names() {
+		let out = json(&Query::Reverse("10.0.0.1".into()), "node", &answer(&["a.com"]), None);
+		assert_eq!(out["query"], json!({ "type": "reverse", "subject": "10.0.0.1" }));
+		assert_eq!(out["block"]["number"], 12);
+		assert_eq!(out["certificates"][0]["ipAddr"], "10.0.0.1");
+		assert_eq!(out["certificates"][0]["ownerId"], "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY");
+		assert_eq!(out["proof"]["nodes"], 3);
+		assert!(out.get("trace").is_none());
+
+		let trace = vec![json!({ "method": "chain_getBlockHash", "params": [], "outcome": { "result": "0x01" } })];
+		assert_eq!(json(&Query::Reverse("10.0.0.1".into()), "node", &answer(&[]), Some(trace.clone()))["trace"], json!(trace));
+	}
+}
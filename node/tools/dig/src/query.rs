@@ -0,0 +1,264 @@
+//! Reads certificates straight from the pallet's storage, pinned to one block so that a
+//! storage proof can be checked against that block's state root.
+
+use std::cell::RefCell;
+use std::fmt;
+use codec::Decode;
+use serde_json::{json, Value};
+use sp_runtime::traits::{BlakeTwo256, Header as _};
+use sp_state_machine::{read_proof_check, StorageProof};
+use siip_client::{storage, AccountId, Certificate, Client};
+use siip_node_runtime::{BlockNumber, Hash, Header};
+
+/// How many keys to ask for at once when scanning `CertificateMap`.
+const PAGE_SIZE: u32 = 256;
+
+/// A storage key, and the value under it if there is one.
+pub type Entry = (Vec<u8>, Option<Vec<u8>>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+	/// The certificate for a domain, from `CertificateMap`.
+	Forward(String),
+	/// The certificates pointing at an IP address, from `ReverseMap`.
+	Reverse(String),
+	/// The certificates owned by an account. There is no index by owner, so this scans
+	/// `CertificateMap`.
+	Owner(AccountId),
+}
+
+#[derive(Debug)]
+pub enum Error {
+	/// The node couldn't be reached, or answered with something unexpected.
+	Node(siip_client::Error),
+	/// The node's answer doesn't match the block it claims to be from.
+	Proof(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Node(e) => write!(f, "{}", e),
+			Error::Proof(e) => write!(f, "proof check failed: {}", e),
+		}
+	}
+}
+
+impl From<siip_client::Error> for Error {
+	fn from(e: siip_client::Error) -> Self {
+		Error::Node(e)
+	}
+}
+
+/// The block a query was answered at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+	pub hash: Hash,
+	pub number: BlockNumber,
+	pub state_root: Hash,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Answer {
+	pub block: Block,
+	pub certificates: Vec<Certificate>,
+	/// How many trie nodes the storage proof took, if one was asked for. A proof that fails
+	/// to check is an error, not an answer.
+	pub proof: Option<usize>,
+}
+
+pub struct Dig {
+	client: Client,
+	/// Every RPC call made so far, if tracing.
+	trace: RefCell<Option<Vec<Value>>>,
+}
+
+impl Dig {
+	pub fn new(client: Client, trace: bool) -> Self {
+		Dig { client, trace: RefCell::new(if trace { Some(Vec::new()) } else { None }) }
+	}
+
+	/// The RPC calls made so far, each with its parameters and result.
+	pub fn take_trace(&self) -> Vec<Value> {
+		self.trace.borrow_mut().as_mut().map(std::mem::take).unwrap_or_default()
+	}
+
+	/// Answers `query` at block `at`, or at the best block. With `prove`, also checks the
+	/// storage the answer was read from against the block's state root.
+	pub async fn query(&self, query: &Query, at: Option<Hash>, prove: bool) -> Result<Answer, Error> {
+		let block = self.block(at).await?;
+		let entries = match query {
+			Query::Forward(domain) => {
+				let key = storage::certificate_key(domain);
+				vec![(key.clone(), self.storage(&key, block.hash).await?)]
+			},
+			Query::Reverse(ip_addr) => {
+				let key = storage::reverse_key(ip_addr);
+				vec![(key.clone(), self.storage(&key, block.hash).await?)]
+			},
+			Query::Owner(owner) => self.owned_by(owner, block.hash).await?,
+		};
+
+		let mut certificates = Vec::new();
+		for value in entries.iter().filter_map(|(_, value)| value.as_ref()) {
+			match query {
+				Query::Reverse(_) => certificates.extend(decode::<Vec<Certificate>>(value)?),
+				_ => certificates.push(decode::<Certificate>(value)?),
+			}
+		}
+
+		let proof = if prove { Some(self.prove(&entries, &block).await?) } else { None };
+		Ok(Answer { block, certificates, proof })
+	}
+
+	async fn rpc(&self, method: &str, params: Value) -> Result<Value, siip_client::Error> {
+		let result = self.client.request(method, params.clone()).await;
+		if let Some(trace) = self.trace.borrow_mut().as_mut() {
+			let outcome = match &result {
+				Ok(value) => json!({ "result": value }),
+				Err(e) => json!({ "error": e.to_string() }),
+			};
+			trace.push(json!({ "method": method, "params": params, "outcome": outcome }));
+		}
+		result
+	}
+
+	/// The header of block `at`, or of the best block. The header is checked against its hash,
+	/// so its state root can be trusted as much as the hash is.
+	async fn block(&self, at: Option<Hash>) -> Result<Block, Error> {
+		let hash = match at {
+			Some(hash) => hash,
+			None => from_value(self.rpc("chain_getBlockHash", json!([])).await?)?,
+		};
+		let header = match self.rpc("chain_getHeader", json!([hash])).await? {
+			Value::Null => return Err(siip_client::Error::Decode(format!("unknown block {:?}", hash)).into()),
+			header => from_value::<Header>(header)?,
+		};
+		if header.hash() != hash {
+			return Err(Error::Proof(format!("the header of block {:?} hashes to {:?}", hash, header.hash())));
+		}
+		Ok(Block { hash, number: header.number, state_root: header.state_root })
+	}
+
+	async fn storage(&self, key: &[u8], at: Hash) -> Result<Option<Vec<u8>>, Error> {
+		match self.rpc("state_getStorage", json!([to_hex(key), at])).await? {
+			Value::String(value) => Ok(Some(from_hex(&value)?)),
+			_ => Ok(None),
+		}
+	}
+
+	/// Every `CertificateMap` entry whose certificate belongs to `owner`, a page at a time.
+	async fn owned_by(&self, owner: &AccountId, at: Hash) -> Result<Vec<Entry>, Error> {
+		let prefix = to_hex(&storage::storage_prefix(b"SiipModule", b"CertificateMap"));
+		let mut owned = Vec::new();
+		let mut start: Option<String> = None;
+		loop {
+			let keys: Vec<String> = from_value(
+				self.rpc("state_getKeysPaged", json!([prefix, PAGE_SIZE, start, at])).await?
+			)?;
+			if keys.is_empty() {
+				return Ok(owned);
+			}
+
+			let sets = self.rpc("state_queryStorageAt", json!([keys, at])).await?;
+			let changes: Vec<(String, Option<String>)> = from_value(sets[0]["changes"].clone())?;
+			for (key, value) in changes {
+				if let Some(value) = value {
+					let value = from_hex(&value)?;
+					if decode::<Certificate>(&value)?.owner_id == *owner {
+						owned.push((from_hex(&key)?, Some(value)));
+					}
+				}
+			}
+
+			if keys.len() < PAGE_SIZE as usize {
+				return Ok(owned);
+			}
+			start = keys.last().cloned();
+		}
+	}
+
+	/// Asks the node to prove `entries` at `block`, and checks the proof.
+	async fn prove(&self, entries: &[Entry], block: &Block) -> Result<usize, Error> {
+		if entries.is_empty() {
+			return Ok(0);
+		}
+		let keys: Vec<String> = entries.iter().map(|(key, _)| to_hex(key)).collect();
+		let proof = self.rpc("state_getReadProof", json!([keys, block.hash])).await?;
+		let nodes: Vec<String> = from_value(proof["proof"].clone())?;
+		let nodes = nodes.iter().map(|node| from_hex(node)).collect::<Result<_, _>>()?;
+		verify_proof(block.state_root, nodes, entries).map_err(Error::Proof)
+	}
+}
+
+/// Checks that the trie `nodes` prove each of `entries` under `state_root`, absent values
+/// included. Returns how many nodes the proof took.
+pub fn verify_proof(state_root: Hash, nodes: Vec<Vec<u8>>, entries: &[Entry]) -> Result<usize, String> {
+	let count = nodes.len();
+	let proven = read_proof_check::<BlakeTwo256, _>(
+		state_root,
+		StorageProof::new(nodes),
+		entries.iter().map(|(key, _)| key),
+	).map_err(|e| e.to_string())?;
+
+	for (key, value) in entries {
+		if proven.get(key) != Some(value) {
+			return Err(format!("the node's value at {} isn't the proven one", to_hex(key)));
+		}
+	}
+	Ok(count)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(data: &str) -> Result<Vec<u8>, siip_client::Error> {
+	hex::decode(data.trim_start_matches("0x")).map_err(|e| siip_client::Error::Decode(e.to_string()))
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, siip_client::Error> {
+	serde_json::from_value(value).map_err(|e| siip_client::Error::Decode(e.to_string()))
+}
+
+fn decode<T: Decode>(mut bytes: &[u8]) -> Result<T, siip_client::Error> {
+	T::decode(&mut bytes).map_err(|e| siip_client::Error::Decode(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_state_machine::{prove_read, InMemoryBackend};
+
+	fn backend() -> InMemoryBackend<BlakeTwo256> {
+		InMemoryBackend::from(vec![(None, vec![
+			(b"certificate".to_vec(), Some(b"value".to_vec())),
+			(b"other".to_vec(), Some(b"other value".to_vec())),
+		])])
+	}
+
+	#[test]
+	fn proof_checks_present_and_absent_values() {
+		let backend = backend();
+		let root = *backend.root();
+		let entries = vec![(b"certificate".to_vec(), Some(b"value".to_vec())), (b"missing".to_vec(), None)];
+		let proof = prove_read(backend, entries.iter().map(|(key, _)| key)).unwrap();
+
+		let nodes: Vec<Vec<u8>> = proof.iter_nodes().collect();
+		assert_eq!(verify_proof(root, nodes.clone(), &entries), Ok(nodes.len()));
+	}
+
+	#[test]
+	fn proof_rejects_a_different_value() {
+		let backend = backend();
+		let root = *backend.root();
+		let proof = prove_read(backend, vec![b"certificate".to_vec()]).unwrap();
+		let nodes: Vec<Vec<u8>> = proof.iter_nodes().collect();
+
+		let forged = vec![(b"certificate".to_vec(), Some(b"forged".to_vec()))];
+		assert!(verify_proof(root, nodes.clone(), &forged).is_err());
+		let hidden = vec![(b"certificate".to_vec(), None)];
+		assert!(verify_proof(root, nodes.clone(), &hidden).is_err());
+		assert!(verify_proof(Hash::repeat_byte(1), nodes, &[(b"certificate".to_vec(), Some(b"value".to_vec()))]).is_err());
+	}
+}
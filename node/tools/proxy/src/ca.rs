@@ -0,0 +1,170 @@
+//! The proxy's own certificate authority. Browsers talk TLS to the proxy, not to the server, so
+//! for every domain the proxy presents a certificate it issued itself. Trust the authority's
+//! certificate in the browser once, and those certificates are accepted.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use log::info;
+use parking_lot::Mutex;
+use rcgen::{BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair, RcgenError};
+use rustls::{NoClientAuth, ServerConfig, TLSError};
+
+/// The subject of the authority's certificate. Issued certificates name it as their issuer, so it
+/// must never change once browsers trust the authority.
+const AUTHORITY_NAME: &str = "SIIP Proxy CA";
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	Certificate(RcgenError),
+	Tls(TLSError),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Io(e) => write!(f, "{}", e),
+			Error::Certificate(e) => write!(f, "can't issue certificate: {}", e),
+			Error::Tls(e) => write!(f, "can't use issued certificate: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Self {
+		Error::Io(e)
+	}
+}
+
+impl From<RcgenError> for Error {
+	fn from(e: RcgenError) -> Self {
+		Error::Certificate(e)
+	}
+}
+
+impl From<TLSError> for Error {
+	fn from(e: TLSError) -> Self {
+		Error::Tls(e)
+	}
+}
+
+pub struct Authority {
+	ca: rcgen::Certificate,
+	/// A TLS configuration per domain, so each domain's certificate is only issued once.
+	configs: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl Authority {
+	/// An authority with a fresh key.
+	pub fn generate() -> Result<Self, Error> {
+		Self::with_key(KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256)?)
+	}
+
+	/// The authority whose key is kept in `dir`, creating it (and writing its certificate next
+	/// to it, as `ca.crt`) if there is none yet.
+	pub fn load_or_generate(dir: &Path) -> Result<Self, Error> {
+		let key_path = dir.join("ca.key");
+		let cert_path = dir.join("ca.crt");
+		if key_path.exists() {
+			let key = fs::read_to_string(&key_path)?;
+			return Self::with_key(KeyPair::from_pem(&key)?);
+		}
+
+		let authority = Self::generate()?;
+		fs::create_dir_all(dir)?;
+		write_private(&key_path, &authority.ca.serialize_private_key_pem())?;
+		fs::write(&cert_path, authority.certificate_pem()?)?;
+		info!("Created a new certificate authority; trust {} in your browser", cert_path.display());
+		Ok(authority)
+	}
+
+	/// Reconstructs the authority from its key alone. Certificates it issues only depend on its
+	/// name and key, so they chain to the certificate written when the key was created.
+	fn with_key(key: KeyPair) -> Result<Self, Error> {
+		let mut params = CertificateParams::default();
+		params.distinguished_name = DistinguishedName::new();
+		params.distinguished_name.push(DnType::CommonName, AUTHORITY_NAME);
+		params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+		params.key_pair = Some(key);
+		Ok(Authority {
+			ca: rcgen::Certificate::from_params(params)?,
+			configs: Default::default(),
+		})
+	}
+
+	/// The authority's certificate, to be trusted by browsers.
+	pub fn certificate_pem(&self) -> Result<String, Error> {
+		Ok(self.ca.serialize_pem()?)
+	}
+
+	pub fn certificate_der(&self) -> Result<Vec<u8>, Error> {
+		Ok(self.ca.serialize_der()?)
+	}
+
+	/// The TLS configuration to present to browsers connecting to `domain`.
+	pub fn server_config(&self, domain: &str) -> Result<Arc<ServerConfig>, Error> {
+		if let Some(config) = self.configs.lock().get(domain) {
+			return Ok(config.clone());
+		}
+
+		// Every certificate gets its own key: rcgen derives the serial number from the key, and
+		// browsers reject two certificates from one issuer with the same serial number
+		let mut params = CertificateParams::new(vec![domain.to_string()]);
+		params.distinguished_name = DistinguishedName::new();
+		params.distinguished_name.push(DnType::CommonName, domain);
+		params.use_authority_key_identifier_extension = true;
+		let cert = rcgen::Certificate::from_params(params)?;
+
+		let chain = vec![rustls::Certificate(cert.serialize_der_with_signer(&self.ca)?)];
+		let mut config = ServerConfig::new(NoClientAuth::new());
+		config.set_single_cert(chain, rustls::PrivateKey(cert.serialize_private_key_der()))?;
+		config.set_protocols(&[b"http/1.1".to_vec()]);
+
+		let config = Arc::new(config);
+		self.configs.lock().insert(domain.to_string(), config.clone());
+		Ok(config)
+	}
+}
+
+/// Writes a file that only its owner may read.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+	fs::write(path, contents)?;
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reloaded_authority_keeps_its_identity() {
+		let dir = std::env::temp_dir().join(format!("siip-proxy-ca-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+
+		let created = Authority::load_or_generate(&dir).unwrap();
+		assert!(dir.join("ca.crt").exists());
+		let reloaded = Authority::load_or_generate(&dir).unwrap();
+		assert_eq!(created.ca.get_key_identifier(), reloaded.ca.get_key_identifier());
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn configs_are_cached_per_domain() {
+		let authority = Authority::generate().unwrap();
+		let first = authority.server_config("example.com").unwrap();
+		assert!(Arc::ptr_eq(&first, &authority.server_config("example.com").unwrap()));
+		assert!(!Arc::ptr_eq(&first, &authority.server_config("example.org").unwrap()));
+	}
+}
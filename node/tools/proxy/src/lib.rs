@@ -0,0 +1,316 @@
+//! An HTTPS forward proxy that only lets a browser reach servers holding the key in their SIIP
+//! certificate.
+//!
+//! The browser sends `CONNECT example.com:443`. The proxy looks `example.com` up on the chain,
+//! connects to the IP address in its certificate, and completes the TLS handshake only if the
+//! server presents the certificate's key. It then answers the browser with a certificate of its
+//! own (see [`ca`]) and relays the decrypted traffic between the two connections.
+
+use std::fmt;
+use std::io;
+use std::net::Ipv4Addr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use log::{debug, info, warn};
+use rustls::{ClientConfig, RootCertStore};
+use sp_core::H256;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use webpki::DNSNameRef;
+
+pub mod ca;
+
+use siip_client::{Certificate, Client};
+use siip_dane::PinnedSpki;
+
+/// The longest CONNECT request the proxy reads before giving up on the client.
+const MAX_REQUEST_LENGTH: usize = 8192;
+
+pub struct Proxy {
+	pub client: Client,
+	/// Only trust certificates this many blocks below the node's best block.
+	pub min_confirmations: u32,
+	pub authority: ca::Authority,
+	/// Reach domains that have no SIIP certificate through DNS, verifying them the usual way.
+	pub legacy_fallback: bool,
+	/// The certificate authorities trusted for domains reached through legacy fallback.
+	pub legacy_roots: RootCertStore,
+}
+
+/// Why a CONNECT request was refused. Each becomes an error page for the browser.
+#[derive(Debug)]
+pub enum Error {
+	/// The request wasn't a well-formed CONNECT.
+	BadRequest(&'static str),
+	/// The domain has no SIIP certificate, and legacy fallback is off (or DNS failed too).
+	NotFound(String),
+	/// The chain couldn't be asked about the domain.
+	Chain(siip_client::Error),
+	/// No block is `min_confirmations` deep yet.
+	Unconfirmed(String),
+	/// The certificate on the chain is unusable.
+	BadCertificate(String),
+	/// The server couldn't be reached.
+	Unreachable(io::Error),
+	/// The server didn't prove it holds the expected key, or its certificate isn't trusted.
+	Untrusted(io::Error),
+	/// The proxy couldn't issue a certificate of its own.
+	Authority(ca::Error),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::BadRequest(e) => write!(f, "bad request: {}", e),
+			Error::NotFound(domain) => write!(f, "can't resolve {}", domain),
+			Error::Chain(e) => write!(f, "{}", e),
+			Error::Unconfirmed(e) => write!(f, "{}", e),
+			Error::BadCertificate(e) => write!(f, "unusable SIIP certificate: {}", e),
+			Error::Unreachable(e) => write!(f, "server unreachable: {}", e),
+			Error::Untrusted(e) => write!(f, "untrusted server: {}", e),
+			Error::Authority(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl Error {
+	/// The response sent to the browser instead of `200 Connection established`.
+	fn response(&self) -> Vec<u8> {
+		let status = match self {
+			Error::BadRequest(_) => "400 Bad Request",
+			Error::NotFound(_) => "404 Not Found",
+			Error::Chain(_) | Error::Unconfirmed(_) | Error::Unreachable(_) | Error::Untrusted(_) | Error::BadCertificate(_) => "502 Bad Gateway",
+			Error::Authority(_) => "500 Internal Server Error",
+		};
+		let body = format!("{}\n", self);
+		format!(
+			"HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			status, body.len(), body,
+		).into_bytes()
+	}
+}
+
+impl Proxy {
+	/// Accepts browsers on `listener` until it fails.
+	pub async fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+		loop {
+			let (client, peer) = listener.accept().await?;
+			let proxy = self.clone();
+			tokio::spawn(async move {
+				if let Err(e) = proxy.handle(client).await {
+					debug!("Connection from {} ended: {}", peer, e);
+				}
+			});
+		}
+	}
+
+	async fn handle(&self, mut client: TcpStream) -> io::Result<()> {
+		let (domain, port, early) = match read_connect(&mut client).await? {
+			Ok(target) => target,
+			Err(e) => return client.write_all(&e.response()).await,
+		};
+
+		let server = match self.connect(&domain, port).await {
+			Ok(server) => server,
+			Err(e) => {
+				warn!("Refusing CONNECT {}:{}: {}", domain, port, e);
+				return client.write_all(&e.response()).await;
+			},
+		};
+		let acceptor = match self.authority.server_config(&domain) {
+			Ok(config) => TlsAcceptor::from(config),
+			Err(e) => return client.write_all(&Error::Authority(e).response()).await,
+		};
+
+		client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await?;
+		//A browser may send its ClientHello without waiting for the answer to CONNECT
+		let mut client = acceptor.accept(Prefixed { prefix: early, read: 0, inner: client }).await?;
+		let mut server = server;
+		info!("Tunnelling to {}:{}", domain, port);
+		tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+		Ok(())
+	}
+
+	/// Opens a verified TLS connection to `domain`: pinned to its SIIP certificate if it has one,
+	/// or verified against `legacy_roots` if it doesn't and legacy fallback is on.
+	async fn connect(&self, domain: &str, port: u16)
+		-> Result<tokio_rustls::client::TlsStream<TcpStream>, Error>
+	{
+		let dns_name = DNSNameRef::try_from_ascii_str(domain)
+			.map_err(|_| Error::BadRequest("the target isn't a domain name"))?;
+
+		let mut config = ClientConfig::new();
+		config.set_protocols(&[b"http/1.1".to_vec()]);
+		let tcp = match self.lookup(domain).await? {
+			Some((cert, next_key)) => {
+				let ip_addr = String::from_utf8_lossy(&cert.ip_addr);
+				let ip: Ipv4Addr = ip_addr.parse()
+					.map_err(|_| Error::BadCertificate(format!("{} isn't an IPv4 address", ip_addr)))?;
+				//Mid-rotation, the server may already present the next key
+				let keys: Vec<&[u8]> = Some(&cert.key).into_iter().chain(next_key.as_ref()).map(|key| &key[..]).collect();
+				let verifier = PinnedSpki::from_keys(&keys)
+					.map_err(|e| Error::BadCertificate(e.to_string()))?;
+				config.dangerous().set_certificate_verifier(Arc::new(verifier));
+				TcpStream::connect((ip, port)).await.map_err(Error::Unreachable)?
+			},
+			None if self.legacy_fallback => {
+				debug!("{} has no SIIP certificate, falling back to DNS", domain);
+				config.root_store = self.legacy_roots.clone();
+				TcpStream::connect((domain, port)).await.map_err(|_| Error::NotFound(domain.to_string()))?
+			},
+			None => return Err(Error::NotFound(domain.to_string())),
+		};
+
+		TlsConnector::from(Arc::new(config))
+			.connect(dns_name, tcp)
+			.await
+			.map_err(Error::Untrusted)
+	}
+}
+
+	/// The certificate for `domain` and the key it is rotating to, if any, read
+	/// `min_confirmations` blocks below the best. A malformed next key is left out: it pins
+	/// nothing, but doesn't stop the current key from working.
+	async fn lookup(&self, domain: &str) -> Result<Option<(Certificate, Option<Vec<u8>>)>, Error> {
+		let at = self.confirmed_block().await?;
+		let cert = match self.client.lookup(domain, at).await.map_err(Error::Chain)? {
+			Some(cert) => cert,
+			None => return Ok(None),
+		};
+		let next_key = self.client.key_rotation(domain, at).await
+			.map_err(Error::Chain)?
+			.map(|rotation| rotation.next_key)
+			.filter(|key| PinnedSpki::from_key(key).is_ok());
+		Ok(Some((cert, next_key)))
+	}
+
+	/// The block `min_confirmations` below the node's best, or `None` for the best block itself.
+	async fn confirmed_block(&self) -> Result<Option<H256>, Error> {
+		if self.min_confirmations == 0 {
+			return Ok(None);
+		}
+		let best = self.client.best_number().await.map_err(Error::Chain)?;
+		let number = best.checked_sub(self.min_confirmations).ok_or_else(|| Error::Unconfirmed(format!(
+			"the chain is only {} blocks long; no block has {} confirmations", best, self.min_confirmations,
+		)))?;
+		let hash = self.client.block_hash(number).await.map_err(Error::Chain)?
+			.ok_or_else(|| Error::Unconfirmed(format!("block #{} is unknown", number)))?;
+		Ok(Some(hash))
+	}
+}
+
+/// Reads a CONNECT request, and returns the domain and port it asks for, and whatever the
+/// client sent after the request.
+async fn read_connect<S: AsyncRead + Unpin>(client: &mut S) -> io::Result<Result<(String, u16, Vec<u8>), Error>> {
+	let mut buf = Vec::new();
+	loop {
+		let mut chunk = [0u8; 1024];
+		let len = client.read(&mut chunk).await?;
+		if len == 0 {
+			return Err(io::ErrorKind::UnexpectedEof.into());
+		}
+		buf.extend_from_slice(&chunk[..len]);
+
+		let mut headers = [httparse::EMPTY_HEADER; 64];
+		let mut request = httparse::Request::new(&mut headers);
+		match request.parse(&buf) {
+			Ok(httparse::Status::Complete(len)) => {
+				return Ok(parse_target(&request).map(|(domain, port)| (domain, port, buf[len..].to_vec())));
+			},
+			Ok(httparse::Status::Partial) if buf.len() < MAX_REQUEST_LENGTH => continue,
+			Ok(httparse::Status::Partial) => return Ok(Err(Error::BadRequest("request too long"))),
+			Err(_) => return Ok(Err(Error::BadRequest("malformed request"))),
+		}
+	}
+}
+
+fn parse_target(request: &httparse::Request) -> Result<(String, u16), Error> {
+	if request.method != Some("CONNECT") {
+		return Err(Error::BadRequest("only CONNECT is supported"));
+	}
+	let target = request.path.ok_or(Error::BadRequest("missing target"))?;
+	let (domain, port) = match target.rfind(':') {
+		Some(colon) => (&target[..colon], target[colon + 1..].parse().map_err(|_| Error::BadRequest("bad port"))?),
+		None => (target, 443),
+	};
+	Ok((domain.to_ascii_lowercase(), port))
+}
+
+/// A stream that reads `prefix` before anything from `inner`.
+struct Prefixed<S> {
+	prefix: Vec<u8>,
+	/// How much of `prefix` has been read.
+	read: usize,
+	inner: S,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Prefixed<S> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+		let this = self.get_mut();
+		if this.read < this.prefix.len() {
+			let len = buf.remaining().min(this.prefix.len() - this.read);
+			buf.put_slice(&this.prefix[this.read..this.read + len]);
+			this.read += len;
+			return Poll::Ready(Ok(()));
+		}
+		Pin::new(&mut this.inner).poll_read(cx, buf)
+	}
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Prefixed<S> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+		Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn target(request: &[u8]) -> Result<(String, u16), Error> {
+		let mut headers = [httparse::EMPTY_HEADER; 4];
+		let mut parsed = httparse::Request::new(&mut headers);
+		parsed.parse(request).unwrap();
+		parse_target(&parsed)
+	}
+
+	#[test]
+	fn connect_targets() {
+		assert_eq!(target(b"CONNECT Example.com:8443 HTTP/1.1\r\n\r\n").unwrap(), ("example.com".into(), 8443));
+		assert_eq!(target(b"CONNECT example.com HTTP/1.1\r\n\r\n").unwrap(), ("example.com".into(), 443));
+		assert!(target(b"CONNECT example.com:https HTTP/1.1\r\n\r\n").is_err());
+		assert!(target(b"GET http://example.com/ HTTP/1.1\r\n\r\n").is_err());
+	}
+
+	#[tokio::test]
+	async fn bytes_after_connect_are_kept() {
+		let (mut browser, mut proxy) = tokio::io::duplex(1024);
+		browser.write_all(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n\x16\x03\x01").await.unwrap();
+		let (domain, port, early) = read_connect(&mut proxy).await.unwrap().unwrap();
+		assert_eq!((domain.as_str(), port, &early[..]), ("example.com", 443, &b"\x16\x03\x01"[..]));
+
+		//The tunnel reads them before anything sent later
+		browser.write_all(b"\x00\x05").await.unwrap();
+		let mut tunnel = Prefixed { prefix: early, read: 0, inner: proxy };
+		let mut hello = [0u8; 5];
+		tunnel.read_exact(&mut hello).await.unwrap();
+		assert_eq!(hello, *b"\x16\x03\x01\x00\x05");
+	}
+
+	#[test]
+	fn error_pages() {
+		let page = String::from_utf8(Error::NotFound("example.com".into()).response()).unwrap();
+		assert!(page.starts_with("HTTP/1.1 404 Not Found\r\n"));
+		assert!(page.ends_with("\r\n\r\ncan't resolve example.com\n"));
+	}
+}
@@ -0,0 +1,64 @@
+//! Runs the SIIP HTTPS proxy. Point the browser's HTTPS proxy at `--listen`, and trust the
+//! `ca.crt` written to `--ca-dir` on the first run.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use log::info;
+use rustls::RootCertStore;
+use structopt::StructOpt;
+use tokio::net::TcpListener;
+
+use siip_client::Client;
+use siip_proxy::{ca::Authority, Proxy};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "siip-proxy", about = "An HTTPS proxy that verifies servers against SIIP certificates")]
+struct Opt {
+	/// Address to accept browsers on.
+	#[structopt(long, default_value = "127.0.0.1:8080")]
+	listen: SocketAddr,
+
+	/// HTTP RPC endpoint of a trusted SIIP node.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	node: String,
+
+	/// How long to wait for the node before giving up, in milliseconds.
+	#[structopt(long, default_value = "2000")]
+	node_timeout: u64,
+
+	/// Only trust certificates this many blocks below the node's best block.
+	#[structopt(long, default_value = "0")]
+	min_confirmations: u32,
+
+	/// Where the proxy's certificate authority is kept (`ca.key` and `ca.crt`).
+	#[structopt(long, default_value = ".", parse(from_os_str))]
+	ca_dir: PathBuf,
+
+	/// Reach domains without a SIIP certificate through DNS and the usual certificate authorities.
+	#[structopt(long)]
+	legacy_fallback: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	let opt = Opt::from_args();
+
+	let mut legacy_roots = RootCertStore::empty();
+	legacy_roots.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+	let proxy = Arc::new(Proxy {
+		client: Client::new(opt.node.clone(), Duration::from_millis(opt.node_timeout)),
+		min_confirmations: opt.min_confirmations,
+		authority: Authority::load_or_generate(&opt.ca_dir)?,
+		legacy_fallback: opt.legacy_fallback,
+		legacy_roots,
+	});
+
+	let listener = TcpListener::bind(opt.listen).await?;
+	info!("Proxying on {} through SIIP node {}", opt.listen, opt.node);
+	proxy.serve(listener).await?;
+	Ok(())
+}
@@ -0,0 +1,279 @@
+//! Drives the proxy end to end: a browser-like client on one side, a local TLS server on the
+//! other, and a stand-in for the node's storage RPC in between.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use codec::Encode;
+use rustls::{ClientConfig, NoClientAuth, RootCertStore, ServerConfig};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use siip_client::{storage, Certificate, Client, KeyRotation, Signer};
+use siip_node_runtime::pallet_siip;
+use siip_proxy::{ca::Authority, Proxy};
+
+const PAGE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+
+/// Reads until the end of an HTTP head, one byte at a time so nothing after it is consumed.
+async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> String {
+	let mut head = Vec::new();
+	while !head.ends_with(b"\r\n\r\n") {
+		head.push(stream.read_u8().await.unwrap());
+	}
+	String::from_utf8(head).unwrap()
+}
+
+/// A TLS server for `domain` that answers every request with `PAGE`. Returns its address, its
+/// certificate, and the public key in it in the form a SIIP certificate holds.
+async fn tls_server(domain: &str) -> (SocketAddr, rustls::Certificate, String) {
+	let cert = rcgen::generate_simple_self_signed(vec![domain.to_string()]).unwrap();
+	let der = rustls::Certificate(cert.serialize_der().unwrap());
+	let key = siip_dane::der_to_key(&cert.get_key_pair().public_key_der());
+
+	let mut config = ServerConfig::new(NoClientAuth::new());
+	config.set_single_cert(vec![der.clone()], rustls::PrivateKey(cert.serialize_private_key_der())).unwrap();
+	let acceptor = TlsAcceptor::from(Arc::new(config));
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		loop {
+			let (stream, _) = listener.accept().await.unwrap();
+			let acceptor = acceptor.clone();
+			tokio::spawn(async move {
+				if let Ok(mut stream) = acceptor.accept(stream).await {
+					read_head(&mut stream).await;
+					let _ = stream.write_all(PAGE).await;
+					let _ = stream.shutdown().await;
+				}
+			});
+		}
+	});
+	(addr, der, key)
+}
+
+/// The number of the stand-in node's best block.
+const BEST: u64 = 3;
+
+/// The stand-in node's hash for block `number`.
+fn block_hash(number: u64) -> String {
+	format!("0x{:064x}", number)
+}
+
+fn certificate(domain: &str, key: &str) -> Certificate {
+	Certificate {
+		version_number: pallet_siip::CERTIFICATE_VERSION,
+		owner_id: Signer::from_seed("//Alice").unwrap().account_id(),
+		name: b"Alice".to_vec(),
+		info: b"{}".to_vec(),
+		key: key.as_bytes().to_vec(),
+		ip_addr: b"127.0.0.1".to_vec(),
+		domain: domain.as_bytes().to_vec(),
+	}
+}
+
+/// Answers `state_getStorage` for the `CertificateMap` entries of `certificates`.
+async fn node(certificates: Vec<Certificate>) -> String {
+	node_with_rotations(certificates, Vec::new()).await
+}
+
+/// Answers `state_getStorage` for the `CertificateMap` entries of `certificates` and the
+/// `KeyRotations` entries of `rotations`, at any of its `BEST` blocks, and `chain_getHeader` and
+/// `chain_getBlockHash` for those blocks.
+async fn node_with_rotations(certificates: Vec<Certificate>, rotations: Vec<(&str, KeyRotation)>) -> String {
+	let storage: HashMap<String, String> = certificates.iter()
+		.map(|cert| (storage::certificate_key(&String::from_utf8_lossy(&cert.domain)), cert.encode()))
+		.chain(rotations.iter().map(|(domain, rotation)| (storage::key_rotation_key(domain), rotation.encode())))
+		.map(|(key, value)| (format!("0x{}", hex::encode(key)), format!("0x{}", hex::encode(value))))
+		.collect();
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let url = format!("http://{}", listener.local_addr().unwrap());
+	tokio::spawn(async move {
+		loop {
+			let (mut stream, _) = listener.accept().await.unwrap();
+			let head = read_head(&mut stream).await.to_ascii_lowercase();
+			let length: usize = head.lines()
+				.find_map(|line| line.strip_prefix("content-length:"))
+				.map(|length| length.trim().parse().unwrap())
+				.unwrap_or(0);
+			let mut body = vec![0u8; length];
+			stream.read_exact(&mut body).await.unwrap();
+
+			let request: Value = serde_json::from_slice(&body).unwrap();
+			let params = &request["params"];
+			let result = match request["method"].as_str().unwrap() {
+				"chain_getHeader" => json!({ "number": format!("0x{:x}", BEST) }),
+				"chain_getBlockHash" => match params[0].as_u64().unwrap() {
+					number if number <= BEST => json!(block_hash(number)),
+					_ => Value::Null,
+				},
+				"state_getStorage" => {
+					let known = params[1].is_null() || (0..=BEST).any(|number| params[1] == json!(block_hash(number)));
+					assert!(known, "storage read at unknown block {}", params[1]);
+					json!(storage.get(params[0].as_str().unwrap()))
+				},
+				method => panic!("unexpected {}", method),
+			};
+			let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string();
+			let _ = stream.write_all(format!(
+				"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+				response.len(), response,
+			).as_bytes()).await;
+		}
+	});
+	url
+}
+
+/// Starts a proxy, and returns its address and the certificate a client must trust to use it.
+async fn start_proxy(node: String, legacy_fallback: bool, legacy_roots: RootCertStore) -> (SocketAddr, rustls::Certificate) {
+	start_proxy_with(node, 0, legacy_fallback, legacy_roots).await
+}
+
+async fn start_proxy_with(node: String, min_confirmations: u32, legacy_fallback: bool, legacy_roots: RootCertStore)
+	-> (SocketAddr, rustls::Certificate)
+{
+	let authority = Authority::generate().unwrap();
+	let ca = rustls::Certificate(authority.certificate_der().unwrap());
+	let proxy = Arc::new(Proxy {
+		client: Client::new(node, Duration::from_secs(2)),
+		min_confirmations,
+		authority,
+		legacy_fallback,
+		legacy_roots,
+	});
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(proxy.serve(listener));
+	(addr, ca)
+}
+
+/// Fetches `/` from `domain:port` through the proxy. Returns the proxy's answer to CONNECT,
+/// and the page if the tunnel was opened.
+async fn fetch(proxy: SocketAddr, ca: &rustls::Certificate, domain: &str, port: u16) -> (String, Option<Vec<u8>>) {
+	let mut stream = TcpStream::connect(proxy).await.unwrap();
+	stream.write_all(format!("CONNECT {}:{} HTTP/1.1\r\nHost: {}:{}\r\n\r\n", domain, port, domain, port).as_bytes())
+		.await.unwrap();
+	let head = read_head(&mut stream).await;
+	if !head.starts_with("HTTP/1.1 200") {
+		return (head, None);
+	}
+
+	let mut config = ClientConfig::new();
+	config.root_store.add(ca).unwrap();
+	let name = webpki::DNSNameRef::try_from_ascii_str(domain).unwrap();
+	let mut stream = TlsConnector::from(Arc::new(config)).connect(name, stream).await.unwrap();
+	stream.write_all(format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", domain).as_bytes()).await.unwrap();
+	let mut page = Vec::new();
+	let _ = stream.read_to_end(&mut page).await;
+	(head, Some(page))
+}
+
+fn assert_tunnelled(result: (String, Option<Vec<u8>>)) {
+	assert_eq!(result.1.as_deref(), Some(PAGE), "proxy answered {}", result.0);
+}
+
+#[tokio::test]
+async fn pinned_key_is_tunnelled() {
+	let (server, _, key) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", &key)]).await;
+	let (proxy, ca) = start_proxy(node, false, RootCertStore::empty()).await;
+
+	assert_tunnelled(fetch(proxy, &ca, "example.com", server.port()).await);
+}
+
+#[tokio::test]
+async fn mismatched_key_is_refused() {
+	let (server, _, _) = tls_server("example.com").await;
+	let (_, _, other_key) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", &other_key)]).await;
+	let (proxy, ca) = start_proxy(node, false, RootCertStore::empty()).await;
+
+	let (head, page) = fetch(proxy, &ca, "example.com", server.port()).await;
+	assert!(head.starts_with("HTTP/1.1 502"), "proxy answered {}", head);
+	assert_eq!(page, None);
+}
+
+#[tokio::test]
+async fn next_key_is_tunnelled() {
+	let (server, _, key) = tls_server("example.com").await;
+	let (_, _, old_key) = tls_server("example.com").await;
+	let rotation = KeyRotation { next_key: key.into_bytes(), activation: 600 };
+	let node = node_with_rotations(vec![certificate("example.com", &old_key)], vec![("example.com", rotation)]).await;
+	let (proxy, ca) = start_proxy(node, false, RootCertStore::empty()).await;
+
+	assert_tunnelled(fetch(proxy, &ca, "example.com", server.port()).await);
+}
+
+#[tokio::test]
+async fn min_confirmations_are_asked_for() {
+	let (server, _, key) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", &key)]).await;
+
+	let (proxy, ca) = start_proxy_with(node.clone(), BEST as u32, false, RootCertStore::empty()).await;
+	assert_tunnelled(fetch(proxy, &ca, "example.com", server.port()).await);
+
+	// No block is that deep yet
+	let (proxy, ca) = start_proxy_with(node, BEST as u32 + 1, false, RootCertStore::empty()).await;
+	let (head, _) = fetch(proxy, &ca, "example.com", server.port()).await;
+	assert!(head.starts_with("HTTP/1.1 502"), "proxy answered {}", head);
+}
+
+#[tokio::test]
+async fn malformed_key_is_refused() {
+	let (server, _, _) = tls_server("example.com").await;
+	let node = node(vec![certificate("example.com", "B4:02:EE:13")]).await;
+	let (proxy, ca) = start_proxy(node, false, RootCertStore::empty()).await;
+
+	let (head, _) = fetch(proxy, &ca, "example.com", server.port()).await;
+	assert!(head.starts_with("HTTP/1.1 502"), "proxy answered {}", head);
+}
+
+#[tokio::test]
+async fn unknown_domain_without_fallback() {
+	let (server, _, _) = tls_server("localhost").await;
+	let node = node(Vec::new()).await;
+	let (proxy, ca) = start_proxy(node, false, RootCertStore::empty()).await;
+
+	let (head, _) = fetch(proxy, &ca, "localhost", server.port()).await;
+	assert!(head.starts_with("HTTP/1.1 404"), "proxy answered {}", head);
+}
+
+#[tokio::test]
+async fn legacy_fallback_uses_roots() {
+	let (server, cert, _) = tls_server("localhost").await;
+	let node = node(Vec::new()).await;
+
+	let mut roots = RootCertStore::empty();
+	roots.add(&cert).unwrap();
+	let (proxy, ca) = start_proxy(node.clone(), true, roots).await;
+	assert_tunnelled(fetch(proxy, &ca, "localhost", server.port()).await);
+
+	// Without SIIP, a self-signed certificate is just untrusted
+	let (proxy, ca) = start_proxy(node, true, RootCertStore::empty()).await;
+	let (head, _) = fetch(proxy, &ca, "localhost", server.port()).await;
+	assert!(head.starts_with("HTTP/1.1 502"), "proxy answered {}", head);
+}
+
+#[tokio::test]
+async fn unreachable_node_is_refused() {
+	let (server, _, _) = tls_server("example.com").await;
+	let (proxy, ca) = start_proxy("http://127.0.0.1:9".into(), true, RootCertStore::empty()).await;
+
+	let (head, _) = fetch(proxy, &ca, "example.com", server.port()).await;
+	assert!(head.starts_with("HTTP/1.1 502"), "proxy answered {}", head);
+}
+
+#[tokio::test]
+async fn only_connect_is_supported() {
+	let node = node(Vec::new()).await;
+	let (proxy, _) = start_proxy(node, false, RootCertStore::empty()).await;
+
+	let mut stream = TcpStream::connect(proxy).await.unwrap();
+	stream.write_all(b"GET http://example.com/ HTTP/1.1\r\nHost: example.com\r\n\r\n").await.unwrap();
+	assert!(read_head(&mut stream).await.starts_with("HTTP/1.1 400"));
+}
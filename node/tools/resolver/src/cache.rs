@@ -0,0 +1,117 @@
+//! A small in-memory cache of DNS answers, each kept for as long as its TTL allows. Answers come
+//! back out with their records' TTLs reduced by the time they spent in the cache.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use trust_dns_proto::op::ResponseCode;
+use trust_dns_proto::rr::{Name, Record, RecordType};
+
+/// The most entries the cache holds before it starts evicting expired ones.
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Answer {
+	pub code: ResponseCode,
+	pub records: Vec<Record>,
+}
+
+/// An answer, with when it was inserted and how long it may be kept.
+struct Entry {
+	answer: Answer,
+	inserted: Instant,
+	ttl: Duration,
+}
+
+impl Entry {
+	fn expired(&self) -> bool {
+		self.inserted.elapsed() >= self.ttl
+	}
+}
+
+#[derive(Default)]
+pub struct Cache {
+	entries: Mutex<HashMap<(Name, RecordType), Entry>>,
+}
+
+impl Cache {
+	pub fn get(&self, name: &Name, record_type: RecordType) -> Option<Answer> {
+		let mut entries = self.entries.lock();
+		let key = (name.to_lowercase(), record_type);
+		match entries.get(&key) {
+			Some(entry) if !entry.expired() => {
+				let elapsed = entry.inserted.elapsed().as_secs() as u32;
+				let mut answer = entry.answer.clone();
+				for record in answer.records.iter_mut() {
+					let ttl = record.ttl().saturating_sub(elapsed);
+					record.set_ttl(ttl);
+				}
+				Some(answer)
+			},
+			Some(_) => {
+				entries.remove(&key);
+				None
+			},
+			None => None,
+		}
+	}
+
+	pub fn insert(&self, name: &Name, record_type: RecordType, answer: Answer, ttl: Duration) {
+		if ttl == Duration::from_secs(0) {
+			return;
+		}
+		let mut entries = self.entries.lock();
+		if entries.len() >= MAX_ENTRIES {
+			entries.retain(|_, entry| !entry.expired());
+		}
+		if entries.len() < MAX_ENTRIES {
+			entries.insert((name.to_lowercase(), record_type), Entry { answer, inserted: Instant::now(), ttl });
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::net::Ipv4Addr;
+	use std::str::FromStr;
+	use trust_dns_proto::rr::RData;
+
+	fn answer() -> Answer {
+		Answer { code: ResponseCode::NoError, records: vec![] }
+	}
+
+	#[test]
+	fn hit_is_case_insensitive() {
+		let cache = Cache::default();
+		cache.insert(&Name::from_str("Example.COM.").unwrap(), RecordType::A, answer(), Duration::from_secs(60));
+		assert_eq!(cache.get(&Name::from_str("example.com.").unwrap(), RecordType::A), Some(answer()));
+		assert_eq!(cache.get(&Name::from_str("example.com.").unwrap(), RecordType::AAAA), None);
+	}
+
+	#[test]
+	fn entries_expire() {
+		let cache = Cache::default();
+		let name = Name::from_str("example.com.").unwrap();
+		cache.insert(&name, RecordType::A, answer(), Duration::from_millis(10));
+		std::thread::sleep(Duration::from_millis(20));
+		assert_eq!(cache.get(&name, RecordType::A), None);
+
+		// A zero TTL means "don't cache"
+		cache.insert(&name, RecordType::A, answer(), Duration::from_secs(0));
+		assert_eq!(cache.get(&name, RecordType::A), None);
+	}
+
+	#[test]
+	fn ttls_count_down() {
+		let cache = Cache::default();
+		let name = Name::from_str("example.com.").unwrap();
+		let record = Record::from_rdata(name.clone(), 60, RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+		let answer = Answer { code: ResponseCode::NoError, records: vec![record] };
+		cache.insert(&name, RecordType::A, answer, Duration::from_secs(60));
+		assert_eq!(cache.get(&name, RecordType::A).unwrap().records[0].ttl(), 60);
+
+		std::thread::sleep(Duration::from_millis(1100));
+		assert_eq!(cache.get(&name, RecordType::A).unwrap().records[0].ttl(), 59);
+	}
+}
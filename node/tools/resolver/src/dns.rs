@@ -0,0 +1,410 @@
+//! Turns DNS queries into answers: from SIIP certificates where they exist, and from an
+//! upstream DNS server everywhere else.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::from_utf8;
+use std::time::Duration;
+use log::{debug, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::timeout;
+use trust_dns_proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_proto::rr::{RData, Record, RecordType};
+use trust_dns_proto::rr::rdata::tlsa::{self, CertUsage, Selector, TLSA};
+
+use siip_client::{Certificate, Client, KeyRotation};
+
+use crate::cache::{Answer, Cache};
+
+/// How long to wait for the upstream server before giving up on it.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct Resolver {
+	pub chain: Client,
+	pub cache: Cache,
+	/// Where to send queries for names that have no SIIP certificate.
+	pub upstream: SocketAddr,
+	/// The TTL given to answers synthesized from certificates.
+	pub ttl: u32,
+	/// Answer SERVFAIL, rather than asking upstream, when the chain can't be reached.
+	pub fail_closed: bool,
+}
+
+impl Resolver {
+	/// Answers one DNS message. Returns `None` if the message can't be parsed (or upstream
+	/// didn't answer it), in which case the client is best left to time out and retry.
+	pub async fn handle(&self, packet: &[u8]) -> Option<Vec<u8>> {
+		let request = match Message::from_vec(packet) {
+			Ok(request) => request,
+			Err(e) => {
+				debug!("Dropping malformed query: {}", e);
+				return None;
+			},
+		};
+		let query = match request.queries().first() {
+			Some(query) if request.message_type() == MessageType::Query => query.clone(),
+			_ => return reply(&request, &Answer { code: ResponseCode::FormErr, records: vec![] }),
+		};
+
+		if let Some(answer) = self.cache.get(query.name(), query.query_type()) {
+			return reply(&request, &answer);
+		}
+
+		match self.resolve_siip(&query).await {
+			Ok(Some(records)) => {
+				let answer = Answer { code: ResponseCode::NoError, records };
+				self.cache.insert(query.name(), query.query_type(), answer.clone(), Duration::from_secs(self.ttl as u64));
+				reply(&request, &answer)
+			},
+			Ok(None) => self.forward(&request, packet).await,
+			Err(e) if self.fail_closed => {
+				warn!("Can't resolve {} through SIIP: {}", query.name(), e);
+				reply(&request, &Answer { code: ResponseCode::ServFail, records: vec![] })
+			},
+			Err(e) => {
+				warn!("Can't resolve {} through SIIP, asking upstream: {}", query.name(), e);
+				self.forward(&request, packet).await
+			},
+		}
+	}
+
+	/// Looks `query` up on the chain. `Ok(None)` means SIIP has nothing to say about it.
+	async fn resolve_siip(&self, query: &Query) -> Result<Option<Vec<Record>>, siip_client::Error> {
+		let domain = domain_of(query);
+		let cert = match self.chain.lookup(&domain, None).await? {
+			Some(cert) => cert,
+			None => return Ok(None),
+		};
+		let rotation = match query.query_type() {
+			RecordType::TLSA => self.chain.key_rotation(&domain, None).await?,
+			_ => None,
+		};
+		Ok(records_for(query, &cert, rotation.as_ref(), self.ttl))
+	}
+
+	/// Relays a query to the upstream server, verbatim, and caches what comes back. Only a reply
+	/// that answers `request`, with its ID and question, is taken, and a truncated one is asked
+	/// for again over TCP rather than cached.
+	async fn forward(&self, request: &Message, packet: &[u8]) -> Option<Vec<u8>> {
+		let query = &request.queries()[0];
+		let exchange = async {
+			let (response, buf) = self.exchange_udp(request, packet).await?;
+			if response.truncated() {
+				debug!("Upstream truncated the answer for {}, asking over TCP", query.name());
+				return self.exchange_tcp(request, packet).await;
+			}
+			Ok((response, buf))
+		};
+		let (response, buf) = match timeout(UPSTREAM_TIMEOUT, exchange).await {
+			Ok(Ok(exchanged)) => exchanged,
+			Ok(Err(e)) => {
+				warn!("Can't ask upstream {} for {}: {}", self.upstream, query.name(), e);
+				return None;
+			},
+			Err(_) => {
+				warn!("Upstream {} didn't answer for {}", self.upstream, query.name());
+				return None;
+			},
+		};
+
+		if !response.truncated() {
+			let ttl = response.answers().iter().map(|r| r.ttl()).min().unwrap_or(0);
+			let answer = Answer { code: response.response_code(), records: response.answers().to_vec() };
+			self.cache.insert(query.name(), query.query_type(), answer, Duration::from_secs(ttl as u64));
+		}
+		Some(buf)
+	}
+
+	/// Sends `packet` to upstream over UDP, and waits for the reply that answers `request`.
+	async fn exchange_udp(&self, request: &Message, packet: &[u8]) -> io::Result<(Message, Vec<u8>)> {
+		let bind: SocketAddr = if self.upstream.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }
+			.parse()
+			.expect("static addresses are valid; qed");
+		let socket = UdpSocket::bind(bind).await?;
+		// Connected, the socket drops datagrams from anyone but upstream
+		socket.connect(self.upstream).await?;
+		socket.send(packet).await?;
+
+		let mut buf = vec![0u8; 4096];
+		loop {
+			let len = socket.recv(&mut buf).await?;
+			match Message::from_vec(&buf[..len]) {
+				Ok(response) if answers(request, &response) => return Ok((response, buf[..len].to_vec())),
+				_ => debug!("Ignoring a reply from upstream that doesn't answer {}", request.queries()[0].name()),
+			}
+		}
+	}
+
+	/// Sends `packet` to upstream over TCP, and reads back its reply to `request`.
+	async fn exchange_tcp(&self, request: &Message, packet: &[u8]) -> io::Result<(Message, Vec<u8>)> {
+		let mut stream = TcpStream::connect(self.upstream).await?;
+		stream.write_u16(packet.len() as u16).await?;
+		stream.write_all(packet).await?;
+
+		let mut buf = vec![0u8; stream.read_u16().await? as usize];
+		stream.read_exact(&mut buf).await?;
+		match Message::from_vec(&buf) {
+			Ok(response) if answers(request, &response) => Ok((response, buf)),
+			_ => Err(io::Error::new(io::ErrorKind::InvalidData, "the reply doesn't answer the query")),
+		}
+	}
+}
+
+/// Whether `response` is a reply to `request`: a response with the same ID and question.
+fn answers(request: &Message, response: &Message) -> bool {
+	response.message_type() == MessageType::Response
+		&& response.id() == request.id()
+		&& response.queries() == request.queries()
+}
+
+/// The domain a query asks about, in the form `CertificateMap` is keyed by. TLSA queries are
+/// for `_port._protocol.domain`, so their leading underscore labels are dropped.
+pub fn domain_of(query: &Query) -> String {
+	let name = query.name().to_lowercase().to_ascii();
+	let name = name.trim_end_matches('.');
+	if query.query_type() != RecordType::TLSA {
+		return name.to_string();
+	}
+	name.split('.').skip_while(|label| label.starts_with('_')).collect::<Vec<_>>().join(".")
+}
+
+/// The records `cert` provides for `query`, or `None` if the certificate doesn't cover
+/// that record type and the query should be answered upstream. While the key is being rotated,
+/// `rotation` pins the next key too, so the server may switch to it before it activates.
+pub fn records_for(query: &Query, cert: &Certificate, rotation: Option<&KeyRotation>, ttl: u32) -> Option<Vec<Record>> {
+	let name = query.name().clone();
+	match query.query_type() {
+		RecordType::A => {
+			let ip = from_utf8(&cert.ip_addr).ok()?.parse::<Ipv4Addr>().ok()?;
+			Some(vec![Record::from_rdata(name, ttl, RData::A(ip))])
+		},
+		// SIIP certificates only carry IPv4 addresses, so the name exists with no AAAA records
+		RecordType::AAAA => Some(vec![]),
+		// The same key serves every port, and a malformed key pins nothing
+		RecordType::TLSA => Some(
+			Some(&cert.key).into_iter().chain(rotation.map(|rotation| &rotation.next_key))
+				.flat_map(|key| [siip_dane::Matching::Sha256, siip_dane::Matching::Full].iter()
+					.filter_map(move |matching| siip_dane::Tlsa::from_key(key, *matching).ok()))
+				.map(|record| Record::from_rdata(name.clone(), ttl, RData::TLSA(tlsa_rdata(record))))
+				.collect()
+		),
+		_ => None,
+	}
+}
+
+fn tlsa_rdata(record: siip_dane::Tlsa) -> TLSA {
+	let matching = match record.matching {
+		siip_dane::Matching::Full => tlsa::Matching::Raw,
+		siip_dane::Matching::Sha256 => tlsa::Matching::Sha256,
+	};
+	TLSA::new(CertUsage::DomainIssued, Selector::Spki, matching, record.data)
+}
+
+fn reply(request: &Message, answer: &Answer) -> Option<Vec<u8>> {
+	let mut response = Message::new();
+	response
+		.set_id(request.id())
+		.set_message_type(MessageType::Response)
+		.set_op_code(OpCode::Query)
+		.set_recursion_desired(request.recursion_desired())
+		.set_recursion_available(true)
+		.set_response_code(answer.code)
+		.add_queries(request.queries().to_vec())
+		.add_answers(answer.records.clone());
+	response.to_vec().ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+	use tokio::net::TcpListener;
+	use trust_dns_proto::rr::Name;
+
+	const EC_SPKI: &str = "3059301306072a8648ce3d020106082a8648ce3d030107034200048e3db08ecc6a613c44c8073035b66cbfabe4ecb36f569a2e06a81fae2a1baa530d5bd72a0a747c6466f05717f6a632169cfc8721b7e67dd42700a57353659beb";
+	const EC_SHA256: &str = "9f622c508712c4afa5dabecac5a0a58c401d9005765dde0747294570832570e6";
+
+	fn certificate(ip_addr: &str) -> Certificate {
+		Certificate {
+			version_number: pallet_siip::CERTIFICATE_VERSION,
+			owner_id: Default::default(),
+			name: b"Adrian Teigen".to_vec(),
+			info: b"{}".to_vec(),
+			key: b"B4:02:EE:13".to_vec(),
+			ip_addr: ip_addr.as_bytes().to_vec(),
+			domain: b"adrianteigen.com".to_vec(),
+		}
+	}
+
+	#[test]
+	fn domain_is_normalized() {
+		let query = Query::query(Name::from_str("AdrianTeigen.COM.").unwrap(), RecordType::A);
+		assert_eq!(domain_of(&query), "adrianteigen.com");
+	}
+
+	#[test]
+	fn a_record_from_certificate() {
+		let query = Query::query(Name::from_str("adrianteigen.com.").unwrap(), RecordType::A);
+		let records = records_for(&query, &certificate("13.49.70.106"), None, 300).unwrap();
+		assert_eq!(records.len(), 1);
+		assert_eq!(records[0].rdata(), &RData::A(Ipv4Addr::new(13, 49, 70, 106)));
+		assert_eq!(records[0].ttl(), 300);
+	}
+
+	#[test]
+	fn aaaa_is_empty_and_others_go_upstream() {
+		let name = Name::from_str("adrianteigen.com.").unwrap();
+		let cert = certificate("13.49.70.106");
+		assert_eq!(records_for(&Query::query(name.clone(), RecordType::AAAA), &cert, None, 300), Some(vec![]));
+		assert_eq!(records_for(&Query::query(name, RecordType::MX), &cert, None, 300), None);
+	}
+
+	#[test]
+	fn tlsa_query_names_the_domain() {
+		let query = Query::query(Name::from_str("_443._tcp.AdrianTeigen.com.").unwrap(), RecordType::TLSA);
+		assert_eq!(domain_of(&query), "adrianteigen.com");
+		let query = Query::query(Name::from_str("_443._tcp.adrianteigen.com.").unwrap(), RecordType::A);
+		assert_eq!(domain_of(&query), "_443._tcp.adrianteigen.com");
+	}
+
+	#[test]
+	fn tlsa_records_from_certificate() {
+		let spki = hex::decode(EC_SPKI).unwrap();
+		let mut cert = certificate("13.49.70.106");
+		cert.key = siip_dane::der_to_key(&spki).into_bytes();
+
+		let query = Query::query(Name::from_str("_443._tcp.adrianteigen.com.").unwrap(), RecordType::TLSA);
+		let records = records_for(&query, &cert, None, 300).unwrap();
+		assert_eq!(records.len(), 2);
+		assert_eq!(records[0].name(), query.name());
+		assert_eq!(
+			records[0].rdata(),
+			&RData::TLSA(TLSA::new(CertUsage::DomainIssued, Selector::Spki, tlsa::Matching::Sha256, hex::decode(EC_SHA256).unwrap()))
+		);
+		assert_eq!(
+			records[1].rdata(),
+			&RData::TLSA(TLSA::new(CertUsage::DomainIssued, Selector::Spki, tlsa::Matching::Raw, spki))
+		);
+
+		// The placeholder key isn't a public key, so there is nothing to pin
+		assert_eq!(records_for(&query, &certificate("13.49.70.106"), None, 300), Some(vec![]));
+	}
+
+	#[test]
+	fn tlsa_records_pin_the_next_key_too() {
+		let spki = hex::decode(EC_SPKI).unwrap();
+		let cert = certificate("13.49.70.106");
+		let rotation = KeyRotation { next_key: siip_dane::der_to_key(&spki).into_bytes(), activation: 100 };
+
+		// The current, placeholder key pins nothing, so only the next key's records remain
+		let query = Query::query(Name::from_str("_443._tcp.adrianteigen.com.").unwrap(), RecordType::TLSA);
+		let records = records_for(&query, &cert, Some(&rotation), 300).unwrap();
+		assert_eq!(records.len(), 2);
+		assert_eq!(
+			records[0].rdata(),
+			&RData::TLSA(TLSA::new(CertUsage::DomainIssued, Selector::Spki, tlsa::Matching::Sha256, hex::decode(EC_SHA256).unwrap()))
+		);
+
+		// With both keys well-formed, both are pinned
+		let mut cert = cert;
+		cert.key = rotation.next_key.clone();
+		assert_eq!(records_for(&query, &cert, Some(&rotation), 300).unwrap().len(), 4);
+
+		// Other record types don't depend on the key
+		let query = Query::query(Name::from_str("adrianteigen.com.").unwrap(), RecordType::A);
+		assert_eq!(records_for(&query, &cert, Some(&rotation), 300), records_for(&query, &cert, None, 300));
+	}
+
+	#[test]
+	fn reply_keeps_request_id() {
+		let mut request = Message::new();
+		request.set_id(4242).add_query(Query::query(Name::from_str("adrianteigen.com.").unwrap(), RecordType::A));
+		let response = reply(&request, &Answer { code: ResponseCode::ServFail, records: vec![] }).unwrap();
+		let response = Message::from_vec(&response).unwrap();
+		assert_eq!(response.id(), 4242);
+		assert_eq!(response.response_code(), ResponseCode::ServFail);
+		assert_eq!(response.queries(), request.queries());
+	}
+
+	/// A resolver that sends every query to `upstream`. Its chain is never asked.
+	fn forwarding_to(upstream: SocketAddr) -> Resolver {
+		Resolver {
+			chain: Client::new("http://127.0.0.1:9933", Duration::from_secs(1)),
+			cache: Default::default(),
+			upstream,
+			ttl: 60,
+			fail_closed: false,
+		}
+	}
+
+	fn query(name: &str, id: u16) -> Message {
+		let mut request = Message::new();
+		request.set_id(id).add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+		request
+	}
+
+	fn response_to(request: &Message, ip: Ipv4Addr) -> Message {
+		let mut response = request.clone();
+		let name = request.queries()[0].name().clone();
+		response.set_message_type(MessageType::Response).add_answer(Record::from_rdata(name, 300, RData::A(ip)));
+		response
+	}
+
+	#[tokio::test]
+	async fn forward_ignores_replies_to_other_queries() {
+		let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+		let resolver = forwarding_to(upstream.local_addr().unwrap());
+		let request = query("example.com.", 4242);
+		let answer = response_to(&request, Ipv4Addr::new(10, 0, 0, 1));
+
+		// Forged replies, with the wrong ID or for another name, race the real one
+		let mut wrong_id = response_to(&request, Ipv4Addr::new(6, 6, 6, 6));
+		wrong_id.set_id(4243);
+		let wrong_question = response_to(&query("attacker.com.", 4242), Ipv4Addr::new(6, 6, 6, 6));
+		let replies = vec![wrong_id.to_vec().unwrap(), wrong_question.to_vec().unwrap(), answer.to_vec().unwrap()];
+		tokio::spawn(async move {
+			let mut buf = [0u8; 512];
+			let (_, resolver) = upstream.recv_from(&mut buf).await.unwrap();
+			for reply in replies {
+				upstream.send_to(&reply, resolver).await.unwrap();
+			}
+		});
+
+		let response = resolver.forward(&request, &request.to_vec().unwrap()).await.unwrap();
+		assert_eq!(response, answer.to_vec().unwrap());
+		let cached = resolver.cache.get(request.queries()[0].name(), RecordType::A).unwrap();
+		assert_eq!(cached.records, answer.answers());
+	}
+
+	#[tokio::test]
+	async fn truncated_replies_are_asked_for_over_tcp() {
+		let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let udp = UdpSocket::bind(tcp.local_addr().unwrap()).await.unwrap();
+		let resolver = forwarding_to(udp.local_addr().unwrap());
+		let request = query("example.com.", 4242);
+		let answer = response_to(&request, Ipv4Addr::new(10, 0, 0, 1));
+
+		let mut truncated = request.clone();
+		truncated.set_message_type(MessageType::Response).set_truncated(true);
+		let truncated = truncated.to_vec().unwrap();
+		tokio::spawn(async move {
+			let mut buf = [0u8; 512];
+			let (_, resolver) = udp.recv_from(&mut buf).await.unwrap();
+			udp.send_to(&truncated, resolver).await.unwrap();
+		});
+		let full = answer.to_vec().unwrap();
+		tokio::spawn(async move {
+			let (mut stream, _) = tcp.accept().await.unwrap();
+			let mut packet = vec![0u8; stream.read_u16().await.unwrap() as usize];
+			stream.read_exact(&mut packet).await.unwrap();
+			stream.write_u16(full.len() as u16).await.unwrap();
+			stream.write_all(&full).await.unwrap();
+		});
+
+		let response = resolver.forward(&request, &request.to_vec().unwrap()).await.unwrap();
+		assert_eq!(response, answer.to_vec().unwrap());
+		let cached = resolver.cache.get(request.queries()[0].name(), RecordType::A).unwrap();
+		assert_eq!(cached.records, answer.answers());
+	}
+}
@@ -0,0 +1,108 @@
+//! A DNS server backed by the SIIP blockchain.
+//!
+//! Point any stub resolver at it: names with a SIIP certificate are answered from the
+//! certificate, and everything else is forwarded to a regular DNS server.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use log::{info, warn};
+use structopt::StructOpt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+mod cache;
+mod dns;
+
+use dns::Resolver;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "siip-resolver", about = "Answers DNS queries from SIIP certificates")]
+struct Opt {
+	/// Address to serve DNS on, over both UDP and TCP.
+	#[structopt(long, default_value = "127.0.0.1:53")]
+	listen: SocketAddr,
+
+	/// HTTP RPC endpoint of a trusted SIIP node.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	node: String,
+
+	/// How long to wait for the node before giving up, in milliseconds.
+	#[structopt(long, default_value = "2000")]
+	node_timeout: u64,
+
+	/// DNS server for names that have no SIIP certificate.
+	#[structopt(long, default_value = "1.1.1.1:53")]
+	upstream: SocketAddr,
+
+	/// TTL, in seconds, of answers taken from SIIP certificates.
+	#[structopt(long, default_value = "60")]
+	ttl: u32,
+
+	/// Answer SERVFAIL instead of asking upstream when the node can't be reached.
+	#[structopt(long)]
+	fail_closed: bool,
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	let opt = Opt::from_args();
+
+	let resolver = Arc::new(Resolver {
+		chain: siip_client::Client::new(opt.node.clone(), Duration::from_millis(opt.node_timeout)),
+		cache: Default::default(),
+		upstream: opt.upstream,
+		ttl: opt.ttl,
+		fail_closed: opt.fail_closed,
+	});
+
+	let udp = UdpSocket::bind(opt.listen).await?;
+	let tcp = TcpListener::bind(opt.listen).await?;
+	info!("Serving DNS on {} from SIIP node {}", opt.listen, opt.node);
+
+	tokio::try_join!(serve_udp(udp, resolver.clone()), serve_tcp(tcp, resolver))?;
+	Ok(())
+}
+
+async fn serve_udp(socket: UdpSocket, resolver: Arc<Resolver>) -> std::io::Result<()> {
+	let socket = Arc::new(socket);
+	let mut buf = [0u8; 4096];
+	loop {
+		let (len, peer) = socket.recv_from(&mut buf).await?;
+		let packet = buf[..len].to_vec();
+		let (socket, resolver) = (socket.clone(), resolver.clone());
+		tokio::spawn(async move {
+			if let Some(response) = resolver.handle(&packet).await {
+				if let Err(e) = socket.send_to(&response, peer).await {
+					warn!("Can't answer {}: {}", peer, e);
+				}
+			}
+		});
+	}
+}
+
+async fn serve_tcp(listener: TcpListener, resolver: Arc<Resolver>) -> std::io::Result<()> {
+	loop {
+		let (stream, _) = listener.accept().await?;
+		tokio::spawn(serve_connection(stream, resolver.clone()));
+	}
+}
+
+/// DNS over TCP prefixes every message with its length, and allows several per connection.
+async fn serve_connection(mut stream: TcpStream, resolver: Arc<Resolver>) {
+	while let Ok(len) = stream.read_u16().await {
+		let mut packet = vec![0u8; len as usize];
+		if stream.read_exact(&mut packet).await.is_err() {
+			break;
+		}
+		let response = match resolver.handle(&packet).await {
+			Some(response) => response,
+			None => break,
+		};
+		if stream.write_u16(response.len() as u16).await.is_err()
+			|| stream.write_all(&response).await.is_err() {
+			break;
+		}
+	}
+}
@@ -1,6 +1,8 @@
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import './css/Siip.css';
 import TextareaAutosize from 'react-autosize-textarea';
+import { blake2AsHex, randomAsHex } from '@polkadot/util-crypto';
+import { useSubstrate } from './substrate-lib';
 import { TxButton } from './substrate-lib/components';
 import { Button } from 'semantic-ui-react';
 
@@ -29,12 +31,7 @@ export function SubmitButton (props) {
   let paramFields = [];
   let inputParams = [];
   let color = 'black';
-  if (props.method === 'Register') {
-    color = 'green';
-    callable = 'registerCertificate';
-    paramFields = [nameField, domainField, ipAddrField, infoField, keyField];
-    inputParams = [name, domain, ipAddr, info, publicKey];
-  } else if (props.method === 'Modify') {
+  if (props.method === 'Modify') {
     color = 'yellow';
     callable = 'modifyCertificate';
     paramFields = [nameField, domainField, ipAddrField, infoField, keyField];
@@ -48,7 +45,16 @@ export function SubmitButton (props) {
 
   const [status, setStatus] = useState('');
 
-  if (props.enable) {
+  if (props.enable && props.method === 'Register') {
+    return (
+      <RegisterButtons
+        {...props}
+        paramFields={[nameField, domainField, ipAddrField, infoField, keyField]}
+        inputParams={[name, domain, ipAddr, info, publicKey]}
+        tip={tip}
+      />
+    );
+  } else if (props.enable) {
     return (
       <div>
         <br />
@@ -85,6 +91,105 @@ export function SubmitButton (props) {
   }
 }
 
+// Registering takes two transactions, so nobody watching the transaction pool can see the
+// domain and register it first. The first commits to a salted hash of the domain, and once
+// that commitment is MinCommitmentAge blocks old, the second reveals it.
+function RegisterButtons (props) {
+  const { api } = useSubstrate();
+  const { accountPair, paramFields, inputParams, tip } = props;
+  const [salt] = useState(() => randomAsHex(16).slice(2));
+  const [committedAt, setCommittedAt] = useState(null);
+  const [bestNumber, setBestNumber] = useState(0);
+  const [status, setStatus] = useState('');
+
+  const interxType = 'EXTRINSIC';
+  const palletRpc = 'siipModule';
+  const minAge = api.consts.siipModule.minCommitmentAge.toNumber();
+
+  // The same hash as SiipModule::registration_commitment: blake2_256 of (domain, salt, owner)
+  const address = accountPair ? accountPair.address : null;
+  const commitment = address
+    ? blake2AsHex(api.createType('(Bytes, Bytes, AccountId)', [props.domain.trim(), salt, address]).toU8a())
+    : null;
+
+  useEffect(() => {
+    let unsubscribeAll = null;
+
+    api.derive.chain.bestNumber(number => setBestNumber(number.toNumber()))
+      .then(unsub => {
+        unsubscribeAll = unsub;
+      })
+      .catch(console.error);
+
+    return () => unsubscribeAll && unsubscribeAll();
+  }, [api]);
+
+  useEffect(() => {
+    if (!commitment) { return; }
+    let unsubscribeAll = null;
+
+    api.query.siipModule.commitments(address, commitment, committed => {
+      setCommittedAt(committed.isSome ? committed.unwrap().toNumber() : null);
+    })
+      .then(unsub => {
+        unsubscribeAll = unsub;
+      })
+      .catch(console.error);
+
+    return () => unsubscribeAll && unsubscribeAll();
+  }, [api, address, commitment]);
+
+  // The reveal goes in the next block at the earliest
+  const revealFrom = committedAt === null ? null : committedAt + minAge;
+  const revealable = revealFrom !== null && bestNumber + 1 >= revealFrom;
+
+  const commitField = { name: 'commitment', type: 'Hash', optional: false };
+  const saltField = { name: 'salt', type: 'Bytes', optional: false };
+
+  return (
+    <div>
+      <br />
+      <div className='button_pos'>
+        <TxButton
+          label='Commit'
+          type='SIGNED-TX'
+          color='green'
+          accountPair={accountPair}
+          setStatus={setStatus}
+          attrs={{
+            interxType,
+            palletRpc,
+            callable: 'commitRegistration',
+            inputParams: [{ type: 'Hash', value: commitment }],
+            paramFields: [commitField]
+          }}
+          disabled={committedAt !== null}
+          tip={tip}
+        />
+        <TxButton
+          label='Reveal'
+          type='SIGNED-TX'
+          color='green'
+          accountPair={accountPair}
+          setStatus={setStatus}
+          attrs={{
+            interxType,
+            palletRpc,
+            callable: 'revealRegistration',
+            inputParams: [{ type: 'Bytes', value: salt }, ...inputParams],
+            paramFields: [saltField, ...paramFields]
+          }}
+          disabled={!revealable}
+          tip={tip}
+        />
+      </div>
+      <p>
+        {revealFrom !== null && !revealable ? `Committed in block ${committedAt}; reveal from block ${revealFrom}` : status}
+      </p>
+    </div>
+  );
+}
+
 export async function updateDb (domain, setDbName, setDbIpAddr, setDbInfo, setDbPublicKey, setDbTip, setDomainExists) {
   const palletRpc = 'siipModule';
   const callable = 'certificateMap';